@@ -0,0 +1,41 @@
+//! Benchmark of pushing large items into an unlimited queue.
+//!
+//! Pushing a 4 KiB item by value copies it into the queue and out again
+//! when it is popped. `push_boxed` only moves the pointer so the cost no
+//! longer depends on the size of the item.
+//!
+//! Run with `cargo bench --bench boxed`.
+
+use std::hint::black_box;
+use std::time::Instant;
+
+use deadqueue::unlimited::{BoxQueue, Queue};
+
+const ITERATIONS: u32 = 100_000;
+
+type Large = [u8; 4096];
+
+fn report(name: &str, start: Instant) {
+    println!(
+        "{:<20} {:>8.1} ns/iter",
+        name,
+        start.elapsed().as_nanos() as f64 / f64::from(ITERATIONS)
+    );
+}
+
+fn main() {
+    let queue: Queue<Large> = Queue::new();
+    let start = Instant::now();
+    for i in 0..ITERATIONS {
+        queue.push([i as u8; 4096]);
+        black_box(queue.try_pop().unwrap());
+    }
+    report("push", start);
+    let queue: BoxQueue<Large> = BoxQueue::new();
+    let start = Instant::now();
+    for i in 0..ITERATIONS {
+        queue.push_boxed(Box::new([i as u8; 4096]));
+        black_box(queue.try_pop().unwrap());
+    }
+    report("push_boxed", start);
+}
@@ -0,0 +1,41 @@
+//! Benchmark of draining an unlimited queue in a tight loop.
+//!
+//! Every drain ends with the queue becoming empty which triggers the
+//! empty notification. Nothing is sent while there are no receivers so
+//! draining without subscribers only pays for checking the receiver
+//! count.
+//!
+//! Run with `cargo bench --bench drain`.
+
+use std::hint::black_box;
+use std::time::Instant;
+
+use deadqueue::unlimited::Queue;
+
+const ITERATIONS: u32 = 1_000_000;
+const BATCH: u32 = 4;
+
+fn bench(name: &str, queue: &Queue<u32>) {
+    let start = Instant::now();
+    for i in 0..ITERATIONS / BATCH {
+        for j in 0..BATCH {
+            queue.push(i + j);
+        }
+        while let Some(item) = queue.try_pop() {
+            black_box(item);
+        }
+    }
+    let elapsed = start.elapsed();
+    println!(
+        "{:<20} {:>8.1} ns/item",
+        name,
+        elapsed.as_nanos() as f64 / f64::from(ITERATIONS)
+    );
+}
+
+fn main() {
+    let queue = Queue::new();
+    bench("no receivers", &queue);
+    let _empty = queue.subscribe_empty();
+    bench("with receivers", &queue);
+}
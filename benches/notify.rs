@@ -0,0 +1,37 @@
+//! Benchmark of pushing and popping at the full/empty boundary.
+//!
+//! A limited queue with a capacity of one becomes full on every push and
+//! empty on every pop which triggers a notification each time. The
+//! benchmark compares the cost with and without subscribed receivers.
+//!
+//! Run with `cargo bench --bench notify`.
+
+use std::hint::black_box;
+use std::time::Instant;
+
+use deadqueue::limited::Queue;
+
+const ITERATIONS: u32 = 1_000_000;
+
+fn bench(name: &str, queue: &Queue<u32>) {
+    let start = Instant::now();
+    for i in 0..ITERATIONS {
+        queue.try_push(i).unwrap();
+        black_box(queue.try_pop().unwrap());
+    }
+    let elapsed = start.elapsed();
+    println!(
+        "{:<20} {:>8.1} ns/iter",
+        name,
+        elapsed.as_nanos() as f64 / f64::from(ITERATIONS)
+    );
+}
+
+fn main() {
+    let queue = Queue::with_capacity(1);
+    bench("no receivers", &queue);
+    let _full = queue.subscribe_full();
+    let _empty = queue.subscribe_empty();
+    let _not_full = queue.subscribe_not_full();
+    bench("with receivers", &queue);
+}
@@ -0,0 +1,43 @@
+//! Benchmark of `push().await` on a limited queue with free room.
+//!
+//! When there is room in the queue `push` takes the permit via
+//! `try_acquire` without constructing an `Acquire` future. Its cost is
+//! compared to `try_push` which never awaits.
+//!
+//! Run with `cargo bench --bench push`.
+
+use std::hint::black_box;
+use std::time::Instant;
+
+use deadqueue::limited::Queue;
+
+const ITERATIONS: u32 = 1_000_000;
+
+fn report(name: &str, start: Instant) {
+    println!(
+        "{:<20} {:>8.1} ns/iter",
+        name,
+        start.elapsed().as_nanos() as f64 / f64::from(ITERATIONS)
+    );
+}
+
+fn main() {
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .build()
+        .unwrap();
+    let queue = Queue::with_capacity(16);
+    runtime.block_on(async {
+        let start = Instant::now();
+        for i in 0..ITERATIONS {
+            queue.push(i).await;
+            black_box(queue.try_pop().unwrap());
+        }
+        report("push().await", start);
+    });
+    let start = Instant::now();
+    for i in 0..ITERATIONS {
+        queue.try_push(i).unwrap();
+        black_box(queue.try_pop().unwrap());
+    }
+    report("try_push", start);
+}
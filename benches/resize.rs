@@ -0,0 +1,39 @@
+//! Benchmark of shrinking a resizable queue which has enough free slots.
+//!
+//! Shrinking the queue only needs to remove free slots if it holds no
+//! more items than the target capacity. Those are taken in one batch
+//! without awaiting so the cost does not depend on the number of slots
+//! removed.
+//!
+//! Run with `cargo bench --bench resize`.
+
+use std::time::Instant;
+
+use deadqueue::resizable::Queue;
+
+const ITERATIONS: u32 = 10_000;
+
+fn bench(rt: &tokio::runtime::Runtime, name: &str, slots: usize) {
+    let queue: Queue<u32> = Queue::with_capacity(slots);
+    queue.try_push(1).unwrap();
+    let start = Instant::now();
+    rt.block_on(async {
+        for _ in 0..ITERATIONS {
+            queue.resize(1).await;
+            queue.resize(slots).await;
+        }
+    });
+    println!(
+        "{:<20} {:>8.1} ns/iter",
+        name,
+        start.elapsed().as_nanos() as f64 / f64::from(ITERATIONS)
+    );
+}
+
+fn main() {
+    let rt = tokio::runtime::Builder::new_current_thread()
+        .build()
+        .unwrap();
+    bench(&rt, "16 slots", 16);
+    bench(&rt, "1024 slots", 1024);
+}
@@ -0,0 +1,36 @@
+//! Example of a queue stored in a `static`.
+//!
+//! The queues can't be created in a const context so the queue is
+//! initialized lazily on first use. `OnceLock::get_or_init` only costs
+//! an atomic load once the queue has been created.
+
+use std::sync::OnceLock;
+use std::time::Duration;
+
+use deadqueue::limited::Queue;
+
+const WORKER_COUNT: usize = 4;
+
+fn jobs() -> &'static Queue<usize> {
+    static JOBS: OnceLock<Queue<usize>> = OnceLock::new();
+    JOBS.get_or_init(|| Queue::with_capacity(16))
+}
+
+#[tokio::main]
+async fn main() {
+    for worker in 0..WORKER_COUNT {
+        tokio::spawn(async move {
+            loop {
+                let job = jobs().pop().await;
+                println!("worker[{}] processing job[{}] ...", worker, job);
+            }
+        });
+    }
+    for job in 0..100 {
+        jobs().push(job).await;
+    }
+    while !jobs().is_empty() {
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+    println!("All jobs done. :-)");
+}
@@ -1,5 +1,23 @@
+#[cfg(feature = "loom")]
+use loom::sync::atomic::{AtomicIsize, Ordering};
+#[cfg(not(feature = "loom"))]
 use std::sync::atomic::{AtomicIsize, Ordering};
 
+/// Counter of available items.
+///
+/// The counter is incremented for every item pushed and decremented for
+/// every started pop. It therefore equals the number of items in the queue
+/// minus the number of futures waiting for an item. Every decrement is
+/// wrapped in a [`TransactionSub`] which restores the counter if the pop
+/// is cancelled before it got hold of an item. Thus the counter can never
+/// drift: each increment is paired with exactly one committed decrement.
+///
+/// In debug builds overflows and underflows of the counter are detected
+/// and cause a panic as they can only be caused by a logic error.
+///
+/// With the `loom` feature enabled the counter is backed by the atomics
+/// of the `loom` crate so the model tests can explore all interleavings
+/// of the counter updates.
 #[derive(Debug)]
 pub struct Available(AtomicIsize);
 
@@ -7,18 +25,41 @@ impl Available {
     pub fn new(value: isize) -> Self {
         Self(AtomicIsize::new(value))
     }
-    pub fn sub(&self) -> (TransactionSub, isize) {
-        let new_len = self.0.fetch_sub(1, Ordering::Relaxed) - 1;
-        (TransactionSub(&self.0), new_len)
+    pub fn sub(&self) -> TransactionSub<'_> {
+        let old_len = self.0.fetch_sub(1, Ordering::Relaxed);
+        debug_assert!(old_len > isize::MIN, "available counter underflow");
+        TransactionSub(&self.0)
     }
     pub fn add(&self) -> isize {
-        self.0.fetch_add(1, Ordering::Relaxed) + 1
+        let old_len = self.0.fetch_add(1, Ordering::Relaxed);
+        debug_assert!(old_len < isize::MAX, "available counter overflow");
+        old_len + 1
     }
     pub fn get(&self) -> isize {
         self.0.load(Ordering::Relaxed)
     }
+    /// Check the counter against the length of the queue. While no
+    /// operation is in progress the counter is either the length of the
+    /// queue or, if the queue is empty, the negated number of waiting
+    /// futures. In other words `len == max(available, 0)`.
+    #[cfg(all(feature = "testing", any(feature = "unlimited", feature = "limited")))]
+    pub fn matches_len(&self, len: usize) -> bool {
+        let available = self.get();
+        if available >= 0 {
+            available as usize == len
+        } else {
+            len == 0
+        }
+    }
 }
 
+/// Pending decrement of the [`Available`] counter. Dropping it without
+/// calling `commit` rolls back the decrement.
+///
+/// A rollback leaves the contents of the queue untouched and therefore
+/// never needs to notify anyone. This is also why the queues must not
+/// derive the empty state from the counter value observed when starting
+/// the transaction: rolled back transactions make that value stale.
 #[must_use]
 pub struct TransactionSub<'a>(&'a AtomicIsize);
 
@@ -30,6 +71,56 @@ impl<'a> TransactionSub<'a> {
 
 impl<'a> Drop for TransactionSub<'a> {
     fn drop(&mut self) {
-        self.0.fetch_add(1, Ordering::Relaxed);
+        let old_len = self.0.fetch_add(1, Ordering::Relaxed);
+        debug_assert!(old_len < isize::MAX, "available counter overflow");
+    }
+}
+
+/// Ledger of the permits forgotten and added back by a bounded queue.
+///
+/// Every push or pop of a bounded queue forgets a permit of one semaphore
+/// before adding a permit to the other one. The ledger records both so its
+/// balance is zero while no operation is in progress and never positive.
+/// A positive balance means permits were added without being taken first
+/// and a negative balance at rest means permits have leaked. Changes of
+/// the capacity forget or add permits on purpose and are not recorded.
+///
+/// The ledger is only kept in debug builds or with the `testing` feature
+/// enabled. Otherwise it is empty and all methods are no-ops.
+#[cfg(any(feature = "limited", feature = "resizable"))]
+#[derive(Debug, Default)]
+pub struct PermitLedger {
+    #[cfg(any(debug_assertions, feature = "testing"))]
+    balance: std::sync::atomic::AtomicIsize,
+}
+
+#[cfg(any(feature = "limited", feature = "resizable"))]
+impl PermitLedger {
+    pub fn forget(&self, permit: tokio::sync::SemaphorePermit<'_>) {
+        self.forgotten(permit.num_permits());
+        permit.forget();
+    }
+    pub fn forgotten(&self, n: usize) {
+        #[cfg(any(debug_assertions, feature = "testing"))]
+        self.balance
+            .fetch_sub(n as isize, std::sync::atomic::Ordering::Relaxed);
+        #[cfg(not(any(debug_assertions, feature = "testing")))]
+        let _ = n;
+    }
+    pub fn added(&self, n: usize) {
+        #[cfg(any(debug_assertions, feature = "testing"))]
+        self.balance
+            .fetch_add(n as isize, std::sync::atomic::Ordering::Relaxed);
+        #[cfg(not(any(debug_assertions, feature = "testing")))]
+        let _ = n;
+    }
+    /// Assert that no permits have been added without being taken first.
+    pub fn check(&self) {
+        #[cfg(debug_assertions)]
+        debug_assert!(self.balance() <= 0, "permits added without being taken");
+    }
+    #[cfg(any(debug_assertions, feature = "testing"))]
+    pub fn balance(&self) -> isize {
+        self.balance.load(std::sync::atomic::Ordering::Relaxed)
     }
 }
@@ -0,0 +1,59 @@
+//! Broadcast adapter delivering every item to all subscribers
+
+use std::fmt::Debug;
+use std::future::Future;
+
+use tokio::sync::broadcast;
+use tokio::task::JoinHandle;
+use tokio_stream::wrappers::BroadcastStream;
+use tokio_stream::{Stream, StreamExt};
+
+/// Adapter which pops items from a queue and delivers a clone of every
+/// item to all subscribers. Unlike `pop` every subscriber receives every
+/// item popped after it subscribed.
+///
+/// The items are popped by a single task which is spawned when the
+/// adapter is created. Items popped while there are no subscribers are
+/// dropped. Subscribers which fall more than `capacity` items behind
+/// skip the items they missed. The streams end once the queue is closed
+/// and drained. Dropping the adapter stops the task.
+pub struct Broadcast<T> {
+    sender: broadcast::WeakSender<T>,
+    task: JoinHandle<()>,
+}
+
+impl<T: Clone + Send + 'static> Broadcast<T> {
+    /// Create a broadcast channel and spawn the task returned by `feed`.
+    pub(crate) fn spawn<F, Fut>(capacity: usize, feed: F) -> Self
+    where
+        F: FnOnce(broadcast::Sender<T>) -> Fut,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        let (sender, _) = broadcast::channel(capacity);
+        Self {
+            sender: sender.downgrade(),
+            task: tokio::spawn(feed(sender)),
+        }
+    }
+    /// Subscribe to the items popped from the queue. The stream ends
+    /// once the queue is closed and drained.
+    pub fn subscribe_stream(&self) -> impl Stream<Item = T> {
+        let receiver = match self.sender.upgrade() {
+            Some(sender) => sender.subscribe(),
+            None => broadcast::channel(1).1,
+        };
+        BroadcastStream::new(receiver).filter_map(Result::ok)
+    }
+}
+
+impl<T> Drop for Broadcast<T> {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+impl<T> Debug for Broadcast<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Broadcast").finish()
+    }
+}
@@ -0,0 +1,181 @@
+//! Queue with weighted classes of items
+
+use std::collections::VecDeque;
+use std::fmt::Debug;
+use std::sync::Mutex;
+
+use tokio::sync::Semaphore;
+
+use crate::atomic::Available;
+
+/// Queue that is unlimited in size and pops items of several classes in
+/// weighted round-robin order.
+///
+/// Every item is pushed with a class which is the index of its weight in
+/// the list of weights passed to [`Queue::new`]. The queue keeps a FIFO
+/// sub-queue per class. Items are popped from one class until it is empty
+/// or as many items as its weight have been popped in a row. Then the
+/// next class is served. Thus classes with a low weight never starve.
+///
+/// The order is deterministic: it only depends on the order of the calls
+/// to `push` and `pop`. With the weights `[3, 1]` and both classes never
+/// running empty the classes are popped as `0, 0, 0, 1, 0, 0, 0, 1, ...`.
+///
+/// This queue implementation has the following characteristics:
+///
+///   - Based on one `VecDeque` per class protected by a `Mutex`
+///   - Has unlimitied capacity and no back pressure on push
+///   - `Send` and `Sync` if `T: Send`
+///   - Enabled via the `classed` feature in your `Cargo.toml`
+pub struct Queue<T> {
+    classes: Mutex<Classes<T>>,
+    semaphore: Semaphore,
+    available: Available,
+}
+
+/// Sub-queues of the classes and the state of the round-robin.
+struct Classes<T> {
+    queues: Vec<VecDeque<T>>,
+    weights: Vec<usize>,
+    /// Class currently being served
+    current: usize,
+    /// Number of items the current class may still pop in a row
+    credit: usize,
+}
+
+impl<T> Classes<T> {
+    fn len(&self) -> usize {
+        self.queues.iter().map(VecDeque::len).sum()
+    }
+    /// Pop the next item in weighted round-robin order.
+    fn pop(&mut self) -> Option<T> {
+        // Every class is visited at most once plus the current one again
+        // as its credit might be used up.
+        for _ in 0..=self.queues.len() {
+            if self.credit > 0 {
+                if let Some(item) = self.queues[self.current].pop_front() {
+                    self.credit -= 1;
+                    return Some(item);
+                }
+            }
+            self.current = (self.current + 1) % self.queues.len();
+            self.credit = self.weights[self.current];
+        }
+        None
+    }
+}
+
+impl<T> Queue<T> {
+    /// Create new empty queue with one class per weight. The weight is
+    /// the maximum number of items popped from the class in a row.
+    ///
+    /// # Panics
+    ///
+    /// Panics if there are no weights, more than 256 weights or if any of
+    /// the weights is zero.
+    pub fn new(weights: &[usize]) -> Self {
+        assert!(!weights.is_empty(), "at least one class is required");
+        assert!(weights.len() <= 256, "at most 256 classes are supported");
+        assert!(
+            weights.iter().all(|&weight| weight > 0),
+            "weights must be greater than zero"
+        );
+        Self {
+            classes: Mutex::new(Classes {
+                queues: weights.iter().map(|_| VecDeque::new()).collect(),
+                weights: weights.to_vec(),
+                current: 0,
+                credit: weights[0],
+            }),
+            semaphore: Semaphore::new(0),
+            available: Available::new(0),
+        }
+    }
+    /// Get an item from the queue. If the queue is currently empty
+    /// this method blocks until an item is available.
+    pub async fn pop(&self) -> T {
+        let (txn, permit, item) = loop {
+            let txn = self.available.sub();
+            let permit = crate::trace::acquire(&self.semaphore, "pop", || self.len(), None).await;
+            match self.classes.lock().unwrap().pop() {
+                Some(item) => break (txn, permit, item),
+                // The permit does not belong to any item which means the
+                // bookkeeping is out of sync. Discard the permit instead of
+                // restoring it as the next consumer would not find an item
+                // either and wait for the next one.
+                None => permit.forget(),
+            }
+        };
+        txn.commit();
+        permit.forget();
+        item
+    }
+    /// Try to get an item from the queue. If the queue is currently
+    /// empty return None instead.
+    pub fn try_pop(&self) -> Option<T> {
+        let txn = self.available.sub();
+        let permit = self.semaphore.try_acquire().ok()?;
+        let item = match self.classes.lock().unwrap().pop() {
+            Some(item) => item,
+            // See `pop`
+            None => {
+                permit.forget();
+                return None;
+            }
+        };
+        txn.commit();
+        permit.forget();
+        Some(item)
+    }
+    /// Push an item of the given class into the queue.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `class` is not the index of one of the weights passed
+    /// to `new`.
+    pub fn push(&self, item: T, class: u8) {
+        self.classes
+            .lock()
+            .unwrap()
+            .queues
+            .get_mut(usize::from(class))
+            .expect("unknown class")
+            .push_back(item);
+        self.semaphore.add_permits(1);
+        self.available.add();
+    }
+    /// Get current length of queue (number of items currently stored).
+    pub fn len(&self) -> usize {
+        self.classes.lock().unwrap().len()
+    }
+    /// Get the number of items of the given class currently stored.
+    pub fn class_len(&self, class: u8) -> usize {
+        self.classes
+            .lock()
+            .unwrap()
+            .queues
+            .get(usize::from(class))
+            .map_or(0, VecDeque::len)
+    }
+    /// Returns `true` if the queue is empty.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+    /// The number of available items in the queue. If there are no
+    /// items in the queue this number can become negative and stores the
+    /// number of futures waiting for an item.
+    pub fn available(&self) -> isize {
+        self.available.get()
+    }
+}
+
+impl<T> Debug for Queue<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let classes = self.classes.lock().unwrap();
+        f.debug_struct("Queue")
+            .field("weights", &classes.weights)
+            .field("len", &classes.len())
+            .field("available", &self.available())
+            .finish()
+    }
+}
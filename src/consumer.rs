@@ -0,0 +1,47 @@
+use std::future::{poll_fn, Future};
+use std::pin::Pin;
+use std::task::Poll;
+
+/// Pop items using `pop` until it returns `None` and run `f` for each of
+/// them with at most `concurrency` futures running at the same time. The
+/// futures are polled by the calling task so they do not need to be `Send`.
+pub async fn for_each_concurrent<T, P, PF, F, Fut>(pop: P, concurrency: usize, f: F)
+where
+    P: Fn() -> PF,
+    PF: Future<Output = Option<T>>,
+    F: Fn(T) -> Fut,
+    Fut: Future<Output = ()>,
+{
+    assert!(concurrency > 0, "concurrency must be greater than zero");
+    let mut running: Vec<Pin<Box<Fut>>> = Vec::with_capacity(concurrency);
+    let mut closed = false;
+    loop {
+        if !closed && running.len() < concurrency {
+            tokio::select! {
+                item = pop() => match item {
+                    Some(item) => running.push(Box::pin(f(item))),
+                    None => closed = true,
+                },
+                _ = next_completed(&mut running), if !running.is_empty() => {}
+            }
+        } else if running.is_empty() {
+            return;
+        } else {
+            next_completed(&mut running).await;
+        }
+    }
+}
+
+/// Poll all running futures and resolve once one of them completed.
+async fn next_completed<Fut: Future<Output = ()>>(running: &mut Vec<Pin<Box<Fut>>>) {
+    poll_fn(|cx| {
+        for i in 0..running.len() {
+            if running[i].as_mut().poll(cx).is_ready() {
+                drop(running.swap_remove(i));
+                return Poll::Ready(());
+            }
+        }
+        Poll::Pending
+    })
+    .await
+}
@@ -28,6 +28,40 @@
 //! | `unlimited` | Enable unlimited queue implementation | – | yes |
 //! | `resizable` | Enable resizable queue implementation | `deadqueue/unlimited` | yes |
 //! | `limited` | Enable limited queue implementation | – | yes |
+//! | `dedup` | Enable `unlimited::DedupQueue` | `deadqueue/unlimited` | no |
+//! | `classed` | Enable the `classed` queue popping items of several classes in weighted round-robin order | – | no |
+//! | `tracing` | Emit `tracing` events when `push` or `pop` has to wait | `tracing` | no |
+//! | `metrics` | Export statistics of named queues via the `metrics` crate | `metrics` | no |
+//! | `testing` | Enable `check_invariants` and `debug_permit_balance` for validating the queue in tests | – | no |
+//! | `tokio-mpsc` | Enable `forward_from` and `forward_to` bridging `tokio::sync::mpsc` channels | – | no |
+//! | `std-mpsc` | Enable `pump_from_std` feeding the queue from a `std::sync::mpsc` channel | – | no |
+//! | `broadcast` | Enable the `broadcast` adapter delivering every item to all subscribers | `tokio-stream` | no |
+//! | `cancellation` | Enable `pop_cancellable` on all queues, `push_all_cancellable` on the bounded queues and `resize_cancellable` on the resizable queue | `tokio-util` | no |
+//! | `pool` | Enable `worker_pool` spawning a fixed number of workers processing the items of a queue | – | no |
+//! | `ttl` | Enable the `ttl` queue dropping items which are older than a time to live | – | no |
+//! | `tracing-context` | Enable the `SpanQueue` types carrying the tracing span from `push` to `pop` | `tracing` | no |
+//! | `loom` | Back the internal counters by `loom` atomics for model testing. Not meant for production use. | `loom` | no |
+//!
+//! ## Metrics
+//!
+//! With the `metrics` feature enabled queues created via `new_named` export
+//! the following metrics labeled with `queue` set to the given name:
+//!
+//! - `deadqueue_len` (gauge)
+//! - `deadqueue_capacity` (gauge, not exported by the unlimited queue)
+//! - `deadqueue_pushes_total` (counter)
+//! - `deadqueue_pops_total` (counter)
+//! - `deadqueue_blocked_total` (counter of `push` and `pop` calls which had to wait, labeled with `op`)
+//!
+//! ## Invariants
+//!
+//! While no operation is in progress the following relations hold for all queues:
+//!
+//! - `available() == len() - waiters` where `waiters` is the number of futures waiting in `pop` (see `occupancy()`).
+//! - `available()` is only negative if the queue is empty. Thus `len() == max(available(), 0)`.
+//! - `len() <= capacity()` for the limited and resizable queues.
+//!
+//! The `check_invariants()` method validates these relations if the `testing` feature is enabled.
 //!
 #![cfg_attr(feature = "limited", doc = r##"
 ## Example
@@ -43,7 +77,7 @@ type TaskQueue = deadqueue::limited::Queue<usize>;
 
 #[tokio::main]
 async fn main() {
-    let queue = Arc::new(TaskQueue::new(TASK_COUNT));
+    let queue = Arc::new(TaskQueue::with_capacity(TASK_COUNT));
     for i in 0..TASK_COUNT {
         queue.try_push(i).unwrap();
     }
@@ -73,7 +107,7 @@ async fn main() {
 //!
 //! - **Introspection support.** The methods `.len()`, `.capacity()` and `.available()` provide access the current state of the queue.
 //!
-//! - **Fair scheduling.** Tasks calling `pop` will receive items in a first-come-first-serve fashion. This is mainly due to the use of `tokio::sync::Semaphore` which is fair by nature.
+//! - **Fair scheduling.** Tasks calling `pop` will receive items in a first-come-first-serve fashion. This is mainly due to the use of `tokio::sync::Semaphore` which is fair by nature. Waiting tasks are granted an item in the order they called `pop` and take it out of the queue once the runtime polls them again. See `tests/fairness.rs` for a demonstration.
 //!
 //! - **One struct, not two.** The channels of `tokio`, `async_std` and `futures-intrusive` split the queue in two structs (`Sender` and `Receiver`) which makes the usage sligthly more complicated.
 //!
@@ -101,9 +135,34 @@ async fn main() {
 //! at your option.
 #![warn(missing_docs)]
 
+#[cfg(any(feature = "limited", feature = "resizable"))]
+use tokio::sync::oneshot;
 use tokio::sync::watch;
 
 mod atomic;
+#[cfg(any(feature = "unlimited", feature = "limited"))]
+mod blocking;
+#[cfg(any(feature = "unlimited", feature = "limited"))]
+mod consumer;
+#[cfg(any(feature = "unlimited", feature = "limited"))]
+mod stats;
+mod telemetry;
+mod trace;
+
+#[cfg(feature = "broadcast")]
+pub mod broadcast;
+
+#[cfg(feature = "classed")]
+pub mod classed;
+
+#[cfg(feature = "pool")]
+pub mod pool;
+
+#[cfg(feature = "tracing-context")]
+pub mod span;
+
+#[cfg(feature = "ttl")]
+pub mod ttl;
 
 #[cfg(feature = "unlimited")]
 pub mod unlimited;
@@ -114,14 +173,191 @@ pub mod resizable;
 #[cfg(feature = "limited")]
 pub mod limited;
 
+#[cfg(any(feature = "unlimited", feature = "limited"))]
 /// Private type alias for notify_full and notify_empty
 type Notifier = watch::Sender<()>;
 
-/// Public type alias for subscribe_full and subscribe_empty
-pub type Receiver = watch::Receiver<()>;
+/// Receiver of the notifications returned by `subscribe_full`,
+/// `subscribe_empty` and `subscribe_not_full`.
+///
+/// A receiver only observes notifications sent after it has been
+/// subscribed. Notifications which are sent while nobody awaits
+/// `changed` are coalesced into one. Subscribe first and then await
+/// the notifications in a loop.
+#[cfg_attr(feature = "limited", doc = r##"
+```
+use std::sync::Arc;
+
+use deadqueue::limited::Queue;
+
+#[tokio::main]
+async fn main() {
+    let queue = Arc::new(Queue::with_capacity(2));
+    let mut full = queue.subscribe_full();
+    let producer = {
+        let queue = queue.clone();
+        tokio::spawn(async move {
+            queue.push(1).await;
+            queue.push(2).await;
+        })
+    };
+    full.changed().await.unwrap();
+    assert!(queue.is_full());
+    producer.await.unwrap();
+}
+```
+"##)]
+#[derive(Clone, Debug)]
+pub struct QueueEvent(watch::Receiver<()>);
+
+impl QueueEvent {
+    /// Wait for the next notification. Returns `Err(QueueDropped)` if
+    /// the queue has been dropped.
+    pub async fn changed(&mut self) -> Result<(), QueueDropped> {
+        self.0.changed().await.map_err(|_| QueueDropped)
+    }
+    /// Returns `true` if a notification has been sent since the last
+    /// call to `changed` or since subscribing. Returns
+    /// `Err(QueueDropped)` if the queue has been dropped.
+    pub fn has_changed(&self) -> Result<bool, QueueDropped> {
+        self.0.has_changed().map_err(|_| QueueDropped)
+    }
+}
 
+/// Error returned by [`QueueEvent`] if the queue has been dropped.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct QueueDropped;
+
+impl std::fmt::Display for QueueDropped {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("queue has been dropped")
+    }
+}
+
+impl std::error::Error for QueueDropped {}
+
+/// Wait times of `pop` recorded by a queue with `record_waits` enabled
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct WaitStats {
+    /// Number of completed calls to `pop`
+    pub count: u64,
+    /// Total time spent waiting for items
+    pub total: std::time::Duration,
+    /// Longest time a single call waited for an item
+    pub max: std::time::Duration,
+}
+
+/// Outcome of a `push_reporting` call
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct PushOutcome {
+    /// `true` if the pushed item filled the last free slot of the queue
+    pub became_full: bool,
+    /// Length of the queue right after the item was pushed
+    pub len_after: usize,
+}
+
+/// Snapshot of the state of a queue as passed to `wait_until`
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct QueueStatsSnapshot {
+    /// Number of items in the queue
+    pub len: usize,
+    /// Capacity of the queue or `None` if the queue is unlimited
+    pub capacity: Option<usize>,
+    /// Number of available items as returned by `available()`
+    pub available: isize,
+    /// `true` if the queue has been closed
+    pub closed: bool,
+}
+
+/// Operations shared by the unlimited, limited and resizable queues
+///
+/// This trait makes it possible to write helpers which work with any of
+/// the queues. It is always available but every queue only implements it
+/// if the feature of that queue is enabled. The inherent methods of the
+/// queues take precedence over the methods of this trait.
+///
+/// ```
+/// use deadqueue::Queue;
+///
+/// fn fill<Q: Queue<Item = usize>>(queue: &Q, n: usize) -> usize {
+///     (0..n).take_while(|&i| queue.try_push(i).is_ok()).count()
+/// }
+/// # #[cfg(feature = "limited")]
+/// # {
+/// let queue = deadqueue::limited::Queue::with_capacity(2);
+/// assert_eq!(fill(&queue, 3), 2);
+/// # }
+/// ```
+pub trait Queue {
+    /// Type of the items stored in the queue
+    type Item;
+    /// Try to get an item from the queue. If the queue is currently
+    /// empty return `None` instead.
+    fn try_pop(&self) -> Option<Self::Item>;
+    /// Try to push an item into the queue. If the queue is full or
+    /// closed the item is returned as `Err<T>`.
+    fn try_push(&self, item: Self::Item) -> Result<(), Self::Item>;
+    /// Get current length of queue (number of items currently stored).
+    fn len(&self) -> usize;
+    /// Returns `true` if the queue is empty.
+    fn is_empty(&self) -> bool;
+    /// Get capacity of the queue or `None` if the queue is unlimited.
+    fn capacity(&self) -> Option<usize>;
+    /// Get available count. This is the difference between the current
+    /// queue length and the number of tasks waiting for an item of the
+    /// queue.
+    fn available(&self) -> isize;
+    /// Close the queue. Once closed no new items are accepted.
+    fn close(&self);
+    /// Returns `true` if the queue has been closed.
+    fn is_closed(&self) -> bool;
+}
+
+/// Item of a `push_cancellable` call which is sent back through the
+/// channel if it is dropped before the item has been taken out.
+#[cfg(any(feature = "limited", feature = "resizable"))]
+struct ReturnOnDrop<T>(Option<(T, oneshot::Sender<T>)>);
+
+#[cfg(any(feature = "limited", feature = "resizable"))]
+impl<T> ReturnOnDrop<T> {
+    fn take(&mut self) -> T {
+        self.0.take().unwrap().0
+    }
+}
+
+#[cfg(any(feature = "limited", feature = "resizable"))]
+impl<T> Drop for ReturnOnDrop<T> {
+    fn drop(&mut self) {
+        if let Some((item, sender)) = self.0.take() {
+            // The receiver might be gone in which case the item is dropped.
+            let _ = sender.send(item);
+        }
+    }
+}
+
+#[cfg(any(feature = "unlimited", feature = "limited"))]
 /// Initialize the notify_full sender
 fn new_notifier() -> Notifier {
     let (sender, _) = watch::channel(());
     sender
 }
+
+#[cfg(any(feature = "unlimited", feature = "limited"))]
+/// Wait until `done` returns `true` checking it again after every
+/// notification of the given notifier.
+async fn wait_until(notifier: &Notifier, done: impl Fn() -> bool) {
+    let mut receiver = notifier.subscribe();
+    while !done() {
+        receiver.changed().await.unwrap();
+    }
+}
+
+#[cfg(any(feature = "unlimited", feature = "limited"))]
+/// Notify all receivers of the given notifier. Nothing is sent if there
+/// are no receivers as a receiver only observes notifications sent after
+/// it has been subscribed.
+fn notify(notifier: &Notifier) {
+    if notifier.receiver_count() > 0 {
+        notifier.send_replace(());
+    }
+}
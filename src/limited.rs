@@ -1,13 +1,44 @@
 //! Limited queue implementation
 //!
 
+use std::future::Future;
+use std::iter::FromIterator;
+use std::marker::PhantomData;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+#[cfg(any(
+    feature = "tokio-mpsc",
+    feature = "std-mpsc",
+    feature = "broadcast",
+    feature = "pool"
+))]
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
 use std::{convert::TryInto, fmt::Debug};
 
 use crossbeam_queue::ArrayQueue;
-use tokio::sync::Semaphore;
+use tokio::sync::futures::Notified;
+#[cfg(feature = "tokio-mpsc")]
+use tokio::sync::mpsc;
+use tokio::sync::{oneshot, watch, Notify, Semaphore};
+#[cfg(any(feature = "tokio-mpsc", feature = "std-mpsc"))]
+use tokio::task::JoinHandle;
+#[cfg(feature = "cancellation")]
+use tokio_util::sync::CancellationToken;
+#[cfg(feature = "tracing-context")]
+use tracing::Span;
 
-use crate::atomic::Available;
-use crate::{Notifier, Receiver};
+use crate::atomic::{Available, PermitLedger, TransactionSub};
+#[cfg(feature = "broadcast")]
+use crate::broadcast::Broadcast;
+#[cfg(feature = "pool")]
+use crate::pool::WorkerPool;
+#[cfg(feature = "tracing-context")]
+use crate::span::Spanned;
+use crate::stats::{PopClock, WaitRecorder};
+use crate::telemetry::QueueMetrics;
+use crate::{Notifier, PushOutcome, QueueEvent, QueueStatsSnapshot, WaitStats};
 
 /// Queue that is limited in size and does not support resizing.
 ///
@@ -16,98 +47,734 @@ use crate::{Notifier, Receiver};
 ///   - Based on `crossbeam_queue::ArrayQueue`
 ///   - Has limit capacity with back pressure on push
 ///   - Does not support resizing
+///   - `Send` and `Sync` if `T: Send`
 ///   - Enabled via the `limited` feature in your `Cargo.toml`
 pub struct Queue<T> {
     queue: ArrayQueue<T>,
+    capacity: usize,
     push_semaphore: Semaphore,
     pop_semaphore: Semaphore,
     available: Available,
     notifier_full: Notifier,
     notifier_empty: Notifier,
+    notifier_not_full: Notifier,
+    closed: AtomicBool,
+    notifier_closed: Notifier,
+    notifier_changed: Notifier,
+    len_sender: watch::Sender<usize>,
+    metrics: Option<QueueMetrics>,
+    wait_recorder: Option<WaitRecorder>,
+    pop_clock: PopClock,
+    name: Option<Box<str>>,
+    total_pushed: AtomicU64,
+    total_popped: AtomicU64,
+    notify_poll_pop: Notify,
+    permit_ledger: PermitLedger,
 }
 
+/// Queue of boxed items. This is useful for queueing trait objects like
+/// jobs of different types:
+///
+/// ```
+/// use deadqueue::limited::BoxQueue;
+///
+/// let offset = 1;
+/// let queue: BoxQueue<dyn Fn() -> usize + Send> = BoxQueue::with_capacity(2);
+/// assert!(queue.try_push(Box::new(|| 1)).is_ok());
+/// assert!(queue.try_push(Box::new(move || offset + 1)).is_ok());
+/// assert_eq!(queue.try_pop().unwrap()(), 1);
+/// ```
+pub type BoxQueue<T> = Queue<Box<T>>;
+
+/// Queue of items which carry the tracing span that was current when they
+/// were pushed. Use `push_spanned` and `pop_with_span` to continue the
+/// trace of the producer in the consumer.
+///
+/// Enabled via the `tracing-context` feature in your `Cargo.toml`.
+#[cfg(feature = "tracing-context")]
+pub type SpanQueue<T> = Queue<Spanned<T>>;
+
 impl<T> Debug for Queue<T> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         f.debug_struct("Queue")
-            .field("queue", &self.queue)
-            .field("push_semaphore", &self.push_semaphore)
-            .field("pop_semaphore", &self.pop_semaphore)
-            .field("available", &self.available)
+            .field("name", &self.name())
+            .field("len", &self.len())
+            .field("capacity", &self.capacity)
+            .field("available", &self.available())
+            .field("closed", &self.is_closed())
+            .field("total_pushed", &self.total_pushed())
+            .field("total_popped", &self.total_popped())
             .finish()
     }
 }
 
 impl<T> Queue<T> {
     /// Create new empty queue
+    ///
+    /// # Panics
+    ///
+    /// Panics under the same conditions as `with_capacity`.
+    #[deprecated(note = "use `Queue::with_capacity` instead")]
     pub fn new(max_size: usize) -> Self {
+        Self::with_capacity(max_size)
+    }
+    /// Create new empty queue which can hold up to `capacity` items.
+    ///
+    /// ```
+    /// use deadqueue::limited::Queue;
+    ///
+    /// let queue: Queue<usize> = Queue::with_capacity(2);
+    /// assert!(queue.try_push(1).is_ok());
+    /// assert!(queue.try_push(2).is_ok());
+    /// assert_eq!(queue.try_push(3), Err(3));
+    /// assert_eq!(queue.capacity(), 2);
+    /// ```
+    ///
+    /// **Note:** A queue with a capacity of zero rejects all items:
+    /// `try_push` always fails and `push` blocks forever. Use
+    /// `try_with_capacity` to reject such a capacity.
+    ///
+    /// **Note:** The memory for all `capacity` items is allocated and
+    /// initialized upfront. This method aborts if the allocation fails.
+    /// Use the unlimited queue if there is no sensible upper bound.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `capacity` exceeds `Semaphore::MAX_PERMITS` or the
+    /// memory for `capacity` items exceeds `isize::MAX` bytes. Use
+    /// `try_with_capacity` to reject such capacities instead.
+    pub fn with_capacity(capacity: usize) -> Self {
         Self {
-            queue: ArrayQueue::new(max_size),
-            push_semaphore: Semaphore::new(max_size),
+            // `ArrayQueue` does not support a capacity of zero. The extra
+            // slot is never used as there is no permit for it.
+            queue: ArrayQueue::new(capacity.max(1)),
+            capacity,
+            push_semaphore: Semaphore::new(capacity),
             pop_semaphore: Semaphore::new(0),
             available: Available::new(0),
             notifier_full: crate::new_notifier(),
             notifier_empty: crate::new_notifier(),
+            notifier_not_full: crate::new_notifier(),
+            closed: AtomicBool::new(false),
+            notifier_closed: crate::new_notifier(),
+            notifier_changed: crate::new_notifier(),
+            len_sender: watch::channel(0).0,
+            metrics: None,
+            wait_recorder: None,
+            pop_clock: PopClock::default(),
+            name: None,
+            total_pushed: AtomicU64::new(0),
+            total_popped: AtomicU64::new(0),
+            notify_poll_pop: Notify::new(),
+            permit_ledger: PermitLedger::default(),
+        }
+    }
+    /// Create new empty queue which can hold up to `capacity` items and
+    /// exports its statistics to the `metrics` crate labeled with the
+    /// given name. See the crate documentation for the list of exported
+    /// metrics.
+    #[cfg(feature = "metrics")]
+    pub fn new_named(capacity: usize, name: &str) -> Self {
+        let metrics = QueueMetrics::new(name);
+        metrics.set_capacity(capacity);
+        Self {
+            metrics: Some(metrics),
+            name: Some(name.into()),
+            ..Self::with_capacity(capacity)
         }
     }
+    /// Create new empty queue which can hold up to `capacity` items.
+    ///
+    /// Returns `Err(CapacityError::Zero)` if `capacity` is zero as such a
+    /// queue could never hold any items. Returns
+    /// `Err(CapacityError::TooLarge)` if `capacity` exceeds
+    /// `Semaphore::MAX_PERMITS` or the memory for `capacity` items would
+    /// exceed `isize::MAX` bytes. A capacity that passes this check can
+    /// still be too large for the memory of the machine.
+    ///
+    /// ```
+    /// use deadqueue::limited::{CapacityError, Queue};
+    ///
+    /// let result = Queue::<usize>::try_with_capacity(usize::MAX);
+    /// assert_eq!(result.err(), Some(CapacityError::TooLarge));
+    /// ```
+    pub fn try_with_capacity(capacity: usize) -> Result<Self, CapacityError> {
+        if capacity == 0 {
+            return Err(CapacityError::Zero);
+        }
+        // Every slot of the `ArrayQueue` stores the item and a stamp.
+        let slot_size = std::mem::size_of::<T>() + std::mem::size_of::<usize>();
+        // `Option::is_none_or` would require Rust 1.82.
+        #[allow(clippy::unnecessary_map_or)]
+        let too_large = capacity > Semaphore::MAX_PERMITS
+            || capacity
+                .checked_mul(slot_size)
+                .map_or(true, |size| size > isize::MAX as usize);
+        if too_large {
+            return Err(CapacityError::TooLarge);
+        }
+        Ok(Self::with_capacity(capacity))
+    }
+    /// Create new queue holding clones of the given items. The capacity
+    /// of the queue equals the length of the slice so the queue starts out
+    /// full.
+    ///
+    /// ```
+    /// use deadqueue::limited::Queue;
+    ///
+    /// let queue = Queue::from_slice(&[1, 2, 3]);
+    /// assert_eq!(queue.capacity(), 3);
+    /// assert_eq!(queue.try_pop(), Some(1));
+    /// ```
+    pub fn from_slice(items: &[T]) -> Self
+    where
+        T: Clone,
+    {
+        items.iter().cloned().collect()
+    }
+    /// Create new queue holding the items of the given array. The
+    /// capacity of the queue equals the length of the array so the queue
+    /// starts out full.
+    pub fn from_array<const N: usize>(items: [T; N]) -> Self {
+        IntoIterator::into_iter(items).collect()
+    }
+    /// Give the queue a name which is shown in its `Debug` output. This
+    /// helps telling queues apart in logs. Queues created by `new_named`
+    /// use the name passed to it.
+    pub fn with_name(mut self, name: impl Into<String>) -> Self {
+        self.name = Some(name.into().into_boxed_str());
+        self
+    }
+    /// Get the name of the queue as given to `with_name` or `new_named`.
+    pub fn name(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
+    /// Enable the accounting of the time spent waiting in `pop`. The
+    /// recorded times can be retrieved via `wait_stats`.
+    ///
+    /// This is disabled by default as it requires reading the clock
+    /// twice for every call to `pop`.
+    pub fn record_waits(mut self) -> Self {
+        self.wait_recorder = Some(WaitRecorder::default());
+        self
+    }
+    /// Get the wait times recorded so far. All values are zero unless
+    /// `record_waits` was enabled.
+    pub fn wait_stats(&self) -> WaitStats {
+        self.wait_recorder
+            .as_ref()
+            .map(WaitRecorder::stats)
+            .unwrap_or_default()
+    }
+    /// Get the time since the last successful pop or since the creation
+    /// of the queue if no item has been popped yet. A growing value while
+    /// the queue is not empty indicates stuck consumers.
+    pub fn idle_duration(&self) -> Duration {
+        self.pop_clock.idle_duration()
+    }
+    /// Get the total number of items pushed into the queue since it was
+    /// created. Together with `total_popped` this can be used to compute
+    /// the throughput of the queue. While no operation is in progress
+    /// `total_pushed() == total_popped() + len()` holds.
+    pub fn total_pushed(&self) -> u64 {
+        self.total_pushed.load(Ordering::Relaxed)
+    }
+    /// Get the total number of items popped from the queue since it was
+    /// created.
+    pub fn total_popped(&self) -> u64 {
+        self.total_popped.load(Ordering::Relaxed)
+    }
     /// Get an item from the queue. If the queue is currently empty
     /// this method blocks until an item is available.
+    ///
+    /// **Note:** Closing the queue does not wake tasks waiting in this
+    /// method as there is no item to return. Use `pop_or_closed` if the
+    /// consumer needs to observe the closure of the queue.
+    ///
+    /// **Cancel safety:** Dropping the future before it completes never
+    /// removes an item from the queue, even if an item was handed to it
+    /// while it was waiting. The item stays available to other consumers.
+    ///
+    /// **Fairness:** This method takes part in the cooperative scheduling
+    /// budget of tokio. A task draining a full queue yields to the runtime
+    /// every now and then even though items are available right away.
+    /// This costs a little throughput but keeps other tasks on the same
+    /// thread responsive. `try_pop` never yields.
     pub async fn pop(&self) -> T {
-        let (txn, new_len) = self.available.sub();
-        let permit = self.pop_semaphore.acquire().await.unwrap();
-        let item = self.queue.pop().unwrap();
+        let start = self.wait_recorder.as_ref().map(|_| Instant::now());
+        let (txn, permit, item) = loop {
+            let txn = self.available.sub();
+            let permit = crate::trace::acquire(
+                &self.pop_semaphore,
+                "pop",
+                || self.len(),
+                self.metrics.as_ref(),
+            )
+            .await;
+            match self.queue.pop() {
+                Some(item) => break (txn, permit, item),
+                // The permit does not belong to any item which means the
+                // bookkeeping is out of sync. Discard the permit instead of
+                // restoring it as the next consumer would not find an item
+                // either and wait for the next one.
+                None => self.permit_ledger.forget(permit),
+            }
+        };
         txn.commit();
-        if new_len <= 0 {
-            self.notify_empty();
+        if let (Some(recorder), Some(start)) = (&self.wait_recorder, start) {
+            recorder.record(start.elapsed());
         }
-        permit.forget();
-        self.push_semaphore.add_permits(1);
+        self.popped();
+        self.permit_ledger.forget(permit);
+        self.release_push_permits(1);
         item
     }
+    /// Get an item from the queue like `pop` but return `None` once
+    /// `token` is cancelled. If the token is already cancelled no item is
+    /// taken from the queue.
+    ///
+    /// This relies on the cancel safety of `pop`: an item is never lost
+    /// and `available()` is restored when the wait is cancelled.
+    ///
+    /// Enabled via the `cancellation` feature in your `Cargo.toml`.
+    #[cfg(feature = "cancellation")]
+    pub async fn pop_cancellable(&self, token: &CancellationToken) -> Option<T> {
+        tokio::select! {
+            biased;
+            _ = token.cancelled() => None,
+            item = self.pop() => Some(item),
+        }
+    }
     /// Try to get an item from the queue. If the queue is currently
     /// empty return None instead.
     pub fn try_pop(&self) -> Option<T> {
-        let (txn, new_len) = self.available.sub();
+        let item = self.try_take()?;
+        self.release_push_permits(1);
+        Some(item)
+    }
+    /// Try to get the first item matching `pred` from the queue. Items
+    /// in front of it are popped and dropped. If no item matches the
+    /// queue is drained completely and `None` is returned.
+    ///
+    /// Every dropped item is popped like with `try_pop` so the counters
+    /// of the queue stay in sync.
+    pub fn try_pop_find<F: Fn(&T) -> bool>(&self, pred: F) -> Option<T> {
+        while let Some(item) = self.try_pop() {
+            if pred(&item) {
+                return Some(item);
+            }
+        }
+        None
+    }
+    /// Try to get an item from the queue like `try_pop` and return it
+    /// together with the number of futures waiting in `pop` right after
+    /// the item was taken. See `pending_pops`.
+    ///
+    /// **Note:** Futures waiting in `pop` are handed new items before
+    /// `try_pop` can take them. Thus the count is only non-zero if other
+    /// consumers started waiting concurrently, e.g. because this call
+    /// took the last item. A non-zero count means the queue is running
+    /// dry under contention.
+    pub fn try_pop_with_waiters(&self) -> Option<(T, usize)> {
+        let item = self.try_pop()?;
+        Some((item, self.pending_pops()))
+    }
+    /// Try to get an item from the queue like `try_pop` and return it
+    /// together with the length of the queue right before and right
+    /// after the item was taken as `(item, len_before, len_after)`.
+    ///
+    /// **Note:** The lengths are read right around the pop. Without
+    /// concurrent pushes and pops `len_before == len_after + 1` holds.
+    /// Otherwise they may differ by more as other tasks can modify the
+    /// queue between the reads.
+    pub fn try_pop_delta(&self) -> Option<(T, usize, usize)> {
+        let len_before = self.len();
+        let item = self.try_pop()?;
+        Some((item, len_before, self.len()))
+    }
+    /// Try to take an item out of the queue without releasing its slot.
+    /// The caller is responsible for either releasing the slot or using
+    /// it to push another item.
+    fn try_take(&self) -> Option<T> {
+        let txn = self.available.sub();
         let permit = self.pop_semaphore.try_acquire().ok()?;
-        let item = Some(self.queue.pop().unwrap());
+        let item = match self.queue.pop() {
+            Some(item) => item,
+            // See `pop`
+            None => {
+                self.permit_ledger.forget(permit);
+                return None;
+            }
+        };
         txn.commit();
-        if new_len <= 0 {
+        self.popped();
+        self.permit_ledger.forget(permit);
+        Some(item)
+    }
+    /// Update the counters and notify the subscribers after an item was
+    /// taken out of the queue.
+    fn popped(&self) {
+        self.total_popped.fetch_add(1, Ordering::Relaxed);
+        self.pop_clock.record();
+        if self.queue.is_empty() {
             self.notify_empty();
         }
-        permit.forget();
-        self.push_semaphore.add_permits(1);
-        item
+        self.notify_changed();
+        if let Some(metrics) = &self.metrics {
+            metrics.popped(self.len());
+        }
+    }
+    /// Try to get an item from the queue calling `try_pop` up to
+    /// `max_spins` times in a busy loop. Returns `None` if the queue is
+    /// still empty after that.
+    ///
+    /// **Note:** This burns CPU while spinning and never yields to the
+    /// runtime. It is only meant for latency critical consumers running
+    /// on a dedicated core. Use `pop` in all other cases.
+    pub fn spin_pop(&self, max_spins: usize) -> Option<T> {
+        for _ in 0..max_spins {
+            if let Some(item) = self.try_pop() {
+                return Some(item);
+            }
+            std::hint::spin_loop();
+        }
+        None
+    }
+    /// Get an iterator which pops items from the queue without blocking.
+    /// Every call to `next` is a `try_pop` so the iterator ends as soon
+    /// as the queue is empty at the moment `next` is called.
+    pub fn drain_iter(&self) -> DrainIter<'_, T> {
+        DrainIter { queue: self }
+    }
+    /// Fill `out` with items popped from the queue without blocking and
+    /// return the number of items written. The slots are filled from the
+    /// start of the slice until it is full or the queue is empty. Slots
+    /// after the returned count are left untouched.
+    pub fn try_pop_slice(&self, out: &mut [Option<T>]) -> usize {
+        // `zip` polls `out` first so no item is popped once it is full.
+        out.iter_mut()
+            .zip(self.drain_iter())
+            .map(|(slot, item)| *slot = Some(item))
+            .count()
+    }
+    /// Get up to `max` items from the queue waiting at most `timeout` for
+    /// them. This method returns as soon as `max` items have been
+    /// collected or the timeout has elapsed, whichever comes first. The
+    /// timeout is not reset when an item is received. The returned vector
+    /// contains the items collected so far and may be empty.
+    pub async fn pop_n_timeout(&self, max: usize, timeout: Duration) -> Vec<T> {
+        let mut items = Vec::new();
+        let deadline = tokio::time::sleep(timeout);
+        tokio::pin!(deadline);
+        while items.len() < max {
+            tokio::select! {
+                biased;
+                item = self.pop() => items.push(item),
+                _ = &mut deadline => break,
+            }
+        }
+        items
+    }
+    /// Pop the items currently in the queue until it is empty or `timeout`
+    /// has elapsed, whichever comes first. The popped items are returned
+    /// in order and the items which could not be popped in time are left
+    /// in the queue. Unlike `pop_n_timeout` this method never waits for
+    /// new items to be pushed.
+    ///
+    /// **Note:** If other consumers pop the last item concurrently this
+    /// method waits for a new item until the timeout has elapsed. It
+    /// requires a `tokio` runtime with the time driver enabled.
+    pub async fn drain_for(&self, timeout: Duration) -> Vec<T> {
+        let mut items = Vec::new();
+        let deadline = tokio::time::Instant::now() + timeout;
+        // The clock is checked before every item as popping an item which
+        // is already in the queue completes without giving the timer a
+        // chance to fire.
+        while !self.is_empty() && tokio::time::Instant::now() < deadline {
+            match tokio::time::timeout_at(deadline, self.pop()).await {
+                Ok(item) => items.push(item),
+                Err(_) => break,
+            }
+        }
+        items
+    }
+    /// Get an item from the queue blocking the current thread until an
+    /// item is available.
+    ///
+    /// This method is meant for synchronous code which is not running
+    /// inside an async runtime. The calling thread is parked while waiting
+    /// so no runtime is required. **Never** call this method from within an
+    /// async context as it blocks the executor thread.
+    pub fn pop_blocking(&self) -> T {
+        crate::blocking::block_on(self.pop())
     }
     /// Push an item into the queue
+    ///
+    /// **Note:** If the queue is closed the item is dropped. Use
+    /// `try_push` if you need to get the item back. Dropping the returned
+    /// future before it completes drops the item as well. Use
+    /// `push_cancellable` if the push might be cancelled.
     pub async fn push(&self, item: T) {
-        let permit = self.push_semaphore.acquire().await.unwrap();
-        let new_len = self.available.add();
-        self.queue.push(item).ok().unwrap();
-        if new_len >= self.queue.capacity().try_into().unwrap() {
-            self.notify_full();
+        self.push_reporting(item).await;
+    }
+    /// Push an item into the queue like `push` but give the item back
+    /// if it could not be pushed.
+    ///
+    /// If the returned future is dropped before the item has been pushed,
+    /// e.g. because it lost a `select!` or hit a timeout, or if the queue
+    /// is closed the item is sent through the returned receiver. Once the
+    /// item is pushed the sender is dropped without sending anything.
+    pub fn push_cancellable(
+        &self,
+        item: T,
+    ) -> (impl Future<Output = ()> + '_, oneshot::Receiver<T>) {
+        let (sender, receiver) = oneshot::channel();
+        let mut item = crate::ReturnOnDrop(Some((item, sender)));
+        let future = async move {
+            if self.is_closed() {
+                return;
+            }
+            let permit = crate::trace::acquire(
+                &self.push_semaphore,
+                "push",
+                || self.len(),
+                self.metrics.as_ref(),
+            )
+            .await;
+            if self.is_closed() {
+                return;
+            }
+            self.permit_ledger.forget(permit);
+            self.push_permitted(item.take());
+        };
+        (future, receiver)
+    }
+    /// Push an item into the queue and report whether it filled the
+    /// queue. This can be used by producers to throttle proactively.
+    ///
+    /// **Note:** If the queue is closed the item is dropped and the
+    /// returned outcome describes the unchanged queue.
+    pub async fn push_reporting(&self, item: T) -> PushOutcome {
+        if self.is_closed() {
+            return self.unchanged_outcome();
+        }
+        let permit = crate::trace::acquire(
+            &self.push_semaphore,
+            "push",
+            || self.len(),
+            self.metrics.as_ref(),
+        )
+        .await;
+        if self.is_closed() {
+            return self.unchanged_outcome();
+        }
+        self.permit_ledger.forget(permit);
+        self.push_permitted(item)
+    }
+    fn unchanged_outcome(&self) -> PushOutcome {
+        PushOutcome {
+            became_full: false,
+            len_after: self.len(),
         }
-        permit.forget();
-        self.pop_semaphore.add_permits(1);
+    }
+    /// Push an item into the queue blocking the current thread until
+    /// there is room for the item.
+    ///
+    /// This method is meant for synchronous code which is not running
+    /// inside an async runtime. The calling thread is parked while waiting
+    /// so no runtime is required. **Never** call this method from within an
+    /// async context as it blocks the executor thread.
+    pub fn push_blocking(&self, item: T) {
+        crate::blocking::block_on(self.push(item))
     }
     /// Try to push an item into the queue. If the queue is full
-    /// the item is returned as `Err<T>`.
+    /// or closed the item is returned as `Err<T>`.
     pub fn try_push(&self, item: T) -> Result<(), T> {
+        if self.is_closed() {
+            return Err(item);
+        }
         match self.push_semaphore.try_acquire() {
             Ok(permit) => {
-                let new_len = self.available.add();
-                self.queue.push(item).ok().unwrap();
-                if new_len >= self.queue.capacity().try_into().unwrap() {
-                    self.notify_full();
-                }
-                permit.forget();
-                self.pop_semaphore.add_permits(1);
+                self.permit_ledger.forget(permit);
+                self.push_permitted(item);
                 Ok(())
             }
             Err(_) => Err(item),
         }
     }
+    /// Reserve room for `n` items. The returned [`Reservation`] can be used
+    /// to push up to `n` items without blocking. Reserved room which is
+    /// not used is released when the reservation is dropped. Returns
+    /// `None` if the queue is closed before the room could be reserved.
+    ///
+    /// **Note:** This method blocks forever if `n` exceeds the capacity
+    /// of the queue.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `n` exceeds `u32::MAX`.
+    pub async fn reserve(&self, n: usize) -> Option<Reservation<'_, T>> {
+        let permit = tokio::select! {
+            biased;
+            _ = self.closed() => return None,
+            permit = self.push_semaphore.acquire_many(n.try_into().unwrap()) => permit.unwrap(),
+        };
+        self.permit_ledger.forget(permit);
+        Some(Reservation {
+            queue: self,
+            remaining: n,
+        })
+    }
+    /// Try to reserve room for `n` items without blocking. Either all `n`
+    /// slots are reserved at once or none at all and `None` is returned.
+    /// Reserved room which is not used is released when the returned
+    /// [`Reservation`] is dropped.
+    pub fn try_reserve_slots(&self, n: usize) -> Option<Reservation<'_, T>> {
+        let permit = self
+            .push_semaphore
+            .try_acquire_many(n.try_into().ok()?)
+            .ok()?;
+        self.permit_ledger.forget(permit);
+        Some(Reservation {
+            queue: self,
+            remaining: n,
+        })
+    }
+    /// Push an item into the queue without ever blocking. If the queue is
+    /// full the oldest item is removed to make room for the new one and
+    /// returned. This turns the queue into a ring buffer.
+    ///
+    /// If the queue is closed or there is no item that could be removed
+    /// (e.g. all slots are reserved or the capacity is zero) the new item
+    /// is returned instead.
+    pub fn push_overwrite(&self, item: T) -> Option<T> {
+        if self.is_closed() {
+            return Some(item);
+        }
+        if let Ok(permit) = self.push_semaphore.try_acquire() {
+            self.permit_ledger.forget(permit);
+            self.push_permitted(item);
+            return None;
+        }
+        match self.try_take() {
+            Some(oldest) => {
+                // The slot of the removed item is reused for the new one.
+                self.push_permitted(item);
+                Some(oldest)
+            }
+            None => Some(item),
+        }
+    }
+    /// Try to push as many of the given items into the queue as there is
+    /// room for without blocking. The items which did not fit into the
+    /// queue are returned in their original order.
+    pub fn try_push_n(&self, items: Vec<T>) -> Vec<T> {
+        let mut items = items.into_iter();
+        while let Some(item) = items.next() {
+            if let Err(item) = self.try_push(item) {
+                return std::iter::once(item).chain(items).collect();
+            }
+        }
+        Vec::new()
+    }
+    /// Push all of the given items into the queue in their original order
+    /// waiting for room as needed. If the queue is closed the items which
+    /// have not been pushed yet are returned as `Err`.
+    ///
+    /// **Note:** Dropping the returned future before it completes drops
+    /// the items which have not been pushed yet. Use
+    /// `push_all_cancellable` if the push might be cancelled.
+    pub async fn push_all(&self, items: Vec<T>) -> Result<(), Vec<T>> {
+        let mut items = items.into_iter();
+        while let Some(item) = items.next() {
+            if self.is_closed() {
+                return Err(std::iter::once(item).chain(items).collect());
+            }
+            let permit = crate::trace::acquire(
+                &self.push_semaphore,
+                "push",
+                || self.len(),
+                self.metrics.as_ref(),
+            )
+            .await;
+            if self.is_closed() {
+                return Err(std::iter::once(item).chain(items).collect());
+            }
+            self.permit_ledger.forget(permit);
+            self.push_permitted(item);
+        }
+        Ok(())
+    }
+    /// Push all of the given items into the queue like `push_all` until
+    /// `token` is cancelled. The items which have not been pushed because
+    /// the token was cancelled or the queue was closed are returned in
+    /// their original order. An empty `Vec` means all items were pushed.
+    ///
+    /// Enabled via the `cancellation` feature in your `Cargo.toml`.
+    #[cfg(feature = "cancellation")]
+    pub async fn push_all_cancellable(&self, items: Vec<T>, token: &CancellationToken) -> Vec<T> {
+        let mut items = items.into_iter();
+        while let Some(item) = items.next() {
+            if self.is_closed() {
+                return std::iter::once(item).chain(items).collect();
+            }
+            let permit = tokio::select! {
+                biased;
+                _ = token.cancelled() => None,
+                permit = crate::trace::acquire(
+                    &self.push_semaphore,
+                    "push",
+                    || self.len(),
+                    self.metrics.as_ref(),
+                ) => Some(permit),
+            };
+            match permit {
+                Some(permit) if !self.is_closed() => {
+                    self.permit_ledger.forget(permit);
+                    self.push_permitted(item);
+                }
+                _ => return std::iter::once(item).chain(items).collect(),
+            }
+        }
+        Vec::new()
+    }
+    /// Push an item into the queue for which a permit of the
+    /// `push_semaphore` has already been acquired and forgotten.
+    fn push_permitted(&self, item: T) -> PushOutcome {
+        let new_len = self.available.add();
+        self.queue.push(item).ok().unwrap();
+        self.total_pushed.fetch_add(1, Ordering::Relaxed);
+        let became_full = new_len >= self.capacity.try_into().unwrap();
+        if became_full {
+            self.notify_full();
+        }
+        self.pop_semaphore.add_permits(1);
+        self.permit_ledger.added(1);
+        self.permit_ledger.check();
+        self.notify_poll_pop.notify_one();
+        self.notify_changed();
+        if let Some(metrics) = &self.metrics {
+            metrics.pushed(self.len());
+        }
+        PushOutcome {
+            became_full,
+            len_after: self.len(),
+        }
+    }
     /// Get capacity of the queue (maximum number of items queue can store)
     pub fn capacity(&self) -> usize {
-        self.queue.capacity()
+        self.capacity
+    }
+    /// Get a `watch::Receiver` which is updated with the length of the
+    /// queue whenever it changes. Updates are coalesced so a slow receiver
+    /// only observes the latest length.
+    pub fn subscribe_len(&self) -> watch::Receiver<usize> {
+        // The length is only sent while there are receivers so it might
+        // be outdated.
+        let mut receiver = self.len_sender.subscribe();
+        self.update_len();
+        receiver.borrow_and_update();
+        receiver
     }
     /// Get current length of queue (number of items currently stored)
     pub fn len(&self) -> usize {
@@ -117,9 +784,41 @@ impl<T> Queue<T> {
     pub fn is_empty(&self) -> bool {
         self.queue.is_empty()
     }
+    /// Get the `ArrayQueue` storing the items of the queue.
+    ///
+    /// # Safety
+    ///
+    /// The caller must not push items to or pop items from the returned
+    /// queue. Doing so bypasses the bookkeeping of this queue which can
+    /// make its methods panic, block forever or lose items. Read-only
+    /// methods like `len` and `is_empty` are fine.
+    pub unsafe fn inner(&self) -> &ArrayQueue<T> {
+        &self.queue
+    }
     /// Returns `true` if the queue is full.
+    ///
+    /// This is based on the number of items stored in the queue. Items
+    /// which are about to be pushed or popped are not taken into account
+    /// so it can momentarily disagree with `try_push`. Use
+    /// `is_full_permits` to predict the result of `try_push`.
     pub fn is_full(&self) -> bool {
-        self.queue.is_full()
+        self.len() >= self.capacity
+    }
+    /// Returns `true` if there are no push permits left. Unless the queue
+    /// is closed the next `try_push` fails exactly if this returns `true`
+    /// and no other task pushes or pops in between.
+    ///
+    /// Unlike `is_full` this also counts slots which are held by a
+    /// `Reservation` or by a push which is still in progress.
+    pub fn is_full_permits(&self) -> bool {
+        self.push_semaphore.available_permits() == 0
+    }
+    /// Get the number of items which can be pushed right now without
+    /// blocking.
+    ///
+    /// This number excludes slots held by a `Reservation`.
+    pub fn available_permits(&self) -> usize {
+        self.push_semaphore.available_permits()
     }
     /// The number of available items in the queue. If there are no
     /// items in the queue this number can become negative and stores the
@@ -127,37 +826,746 @@ impl<T> Queue<T> {
     pub fn available(&self) -> isize {
         self.available.get()
     }
+    /// Check the internal invariants of the queue and return `true` if
+    /// they hold:
+    ///
+    ///   - `len() == max(available(), 0)`
+    ///   - Every item in the queue can be popped without waiting
+    ///   - `len() <= capacity()` and the room left in the queue does not
+    ///     exceed `capacity() - len()`
+    ///
+    /// The invariants only hold while no other operation is in progress.
+    /// This method is meant for tests and only available with the
+    /// `testing` feature enabled.
+    #[cfg(feature = "testing")]
+    pub fn check_invariants(&self) -> bool {
+        let len = self.len();
+        self.available.matches_len(len)
+            && self.pop_semaphore.available_permits() == len
+            && len <= self.capacity
+            && self.push_semaphore.available_permits() <= self.capacity - len
+    }
+    /// Get the balance of the permits forgotten and added back by the
+    /// operations of the queue. Every push and pop forgets a permit and
+    /// adds one back afterwards. A negative balance means permits have
+    /// leaked which permanently shrinks the usable capacity of the queue.
+    /// In debug builds every push and pop asserts that the balance is
+    /// never positive.
+    ///
+    /// While no operation is in progress and no [`Reservation`] is held
+    /// the balance is zero. This method is meant for tests and only
+    /// available with the `testing` feature enabled.
+    #[cfg(feature = "testing")]
+    pub fn debug_permit_balance(&self) -> isize {
+        self.permit_ledger.balance()
+    }
+    /// Get the number of items in the queue and the number of futures
+    /// waiting for an item as `(len, waiters)`.
+    ///
+    /// This is a more explicit form of `available` which always equals
+    /// `len - waiters`. As the queue can be modified concurrently the
+    /// returned values are only a snapshot.
+    pub fn occupancy(&self) -> (usize, usize) {
+        let len = self.len();
+        let waiters = (len as isize - self.available()).max(0) as usize;
+        (len, waiters)
+    }
+    /// Get the number of items which can be popped right now without
+    /// waiting. This is `available()` saturated at zero.
+    pub fn items_ready(&self) -> usize {
+        self.available().max(0) as usize
+    }
+    /// Get the number of futures currently waiting for an item. This is
+    /// the negated `available()` saturated at zero.
+    pub fn pending_pops(&self) -> usize {
+        self.available().min(0).unsigned_abs()
+    }
+    /// Get a snapshot of the state of the queue.
+    pub fn stats_snapshot(&self) -> QueueStatsSnapshot {
+        QueueStatsSnapshot {
+            len: self.len(),
+            capacity: Some(self.capacity()),
+            available: self.available(),
+            closed: self.is_closed(),
+        }
+    }
+    /// Wait until `predicate` returns `true` for the state of the queue.
+    /// The predicate is checked right away and again after every push,
+    /// pop or close of the queue.
+    ///
+    /// **Note:** The predicate is checked when the waiting task is polled
+    /// and not at the time of the change. States which only exist for a
+    /// short moment can therefore be missed.
+    pub async fn wait_until<F: Fn(&QueueStatsSnapshot) -> bool>(&self, predicate: F) {
+        crate::wait_until(&self.notifier_changed, || predicate(&self.stats_snapshot())).await
+    }
+    /// Notify any callers awaiting a change of the queue
+    fn notify_changed(&self) {
+        crate::notify(&self.notifier_changed);
+        if self.len_sender.receiver_count() > 0 {
+            self.update_len();
+        }
+    }
+    /// Send the current length to the `subscribe_len` receivers. The
+    /// length is read while holding the lock of the channel so the last
+    /// update always carries the latest length.
+    fn update_len(&self) {
+        self.len_sender.send_if_modified(|old| {
+            let len = self.len();
+            std::mem::replace(old, len) != len
+        });
+    }
     /// Check if the queue is full and notify any waiters
     fn notify_full(&self) {
-        self.notifier_full.send_replace(());
+        crate::notify(&self.notifier_full);
     }
     /// Await until the queue is full.
     pub async fn wait_full(&self) {
-        if self.len() == self.capacity() {
-            return;
-        }
-        self.subscribe_full().changed().await.unwrap();
+        self.wait_until(|stats| Some(stats.len) == stats.capacity)
+            .await
     }
-    /// Get a `Receiver` object that can repeatedly be awaited for
+    /// Get a [`QueueEvent`] receiver that can repeatedly be awaited for
     /// queue-full notifications.
-    pub fn subscribe_full(&self) -> Receiver {
-        self.notifier_full.subscribe()
+    pub fn subscribe_full(&self) -> QueueEvent {
+        QueueEvent(self.notifier_full.subscribe())
     }
     /// Check if the queue is empty and notify any waiters
     fn notify_empty(&self) {
-        self.notifier_empty.send_replace(());
+        crate::notify(&self.notifier_empty);
+    }
+    /// Await until the queue contains at least `n` items. This can be
+    /// used by consumers to wait for a batch of items.
+    pub async fn wait_len(&self, n: usize) {
+        self.wait_until(|stats| stats.len >= n).await
+    }
+    /// Await until the queue contains at least one item.
+    pub async fn wait_not_empty(&self) {
+        self.wait_len(1).await
     }
     /// Await until the queue is empty.
     pub async fn wait_empty(&self) {
-        if self.is_empty() {
-            return;
-        }
-        self.subscribe_empty().changed().await.unwrap();
+        self.wait_until(|stats| stats.len == 0).await
     }
-    /// Get a `Receiver` object that can repeatedly be awaited for
+    /// Get a [`QueueEvent`] receiver that can repeatedly be awaited for
     /// queue-empty notifications.
-    pub fn subscribe_empty(&self) -> Receiver {
-        self.notifier_empty.subscribe()
+    pub fn subscribe_empty(&self) -> QueueEvent {
+        QueueEvent(self.notifier_empty.subscribe())
+    }
+    /// Return permits to the `push_semaphore` and notify any waiters
+    fn release_push_permits(&self, n: usize) {
+        self.push_semaphore.add_permits(n);
+        self.permit_ledger.added(n);
+        self.permit_ledger.check();
+        self.notify_not_full();
+        self.notify_changed();
+    }
+    /// Notify any callers awaiting room in the queue
+    fn notify_not_full(&self) {
+        crate::notify(&self.notifier_not_full);
+    }
+    /// Await until there is room for at least one more item in the queue.
+    ///
+    /// **Note:** The room is not reserved. A concurrent push can use it up
+    /// before the caller gets to push an item. Use `try_push` afterwards and
+    /// wait again if that fails or use `reserve` instead.
+    pub async fn wait_not_full(&self) {
+        self.wait_until(|_| self.push_semaphore.available_permits() > 0)
+            .await
+    }
+    /// Get a [`QueueEvent`] receiver that can repeatedly be awaited for
+    /// notifications about room becoming available in the queue.
+    pub fn subscribe_not_full(&self) -> QueueEvent {
+        QueueEvent(self.notifier_not_full.subscribe())
+    }
+    /// Turn the queue into a [`MappedQueue`] which applies `f` to every
+    /// item that is popped. Items are still pushed as `T`.
+    pub fn map<U, F: Fn(T) -> U>(self, f: F) -> MappedQueue<T, U, F> {
+        MappedQueue {
+            queue: self,
+            f,
+            _marker: PhantomData,
+        }
+    }
+    /// Close the queue. Once closed no new items are accepted: `try_push`
+    /// returns the item as `Err<T>` and `push` drops it. Items which
+    /// are already in the queue can still be popped.
+    ///
+    /// Tasks waiting in `pop_or_closed` are woken and get `None` once the
+    /// queue is empty. Tasks waiting in `pop` are **not** woken and keep
+    /// waiting for an item.
+    pub fn close(&self) {
+        self.closed.store(true, Ordering::Relaxed);
+        crate::notify(&self.notifier_closed);
+        self.notify_changed();
+    }
+    /// Returns `true` if the queue has been closed.
+    pub fn is_closed(&self) -> bool {
+        self.closed.load(Ordering::Relaxed)
+    }
+    /// Wait until the queue is closed. Resolves immediately if the queue
+    /// has already been closed.
+    pub async fn closed(&self) {
+        self.wait_until(|stats| stats.closed).await
+    }
+    /// Get an item from the queue. If the queue is currently empty this
+    /// method blocks until an item is available or the queue is closed.
+    /// Returns `None` once the queue is closed and empty.
+    ///
+    /// Calling `close` wakes all tasks waiting in this method right away.
+    /// The items which are still in the queue are returned first so no
+    /// item is lost. Waiting tasks don't hold on to any item once woken
+    /// so `available()` is restored accordingly.
+    pub async fn pop_or_closed(&self) -> Option<T> {
+        let mut closed = self.notifier_closed.subscribe();
+        loop {
+            if let Some(item) = self.try_pop() {
+                return Some(item);
+            }
+            if self.is_closed() {
+                return None;
+            }
+            tokio::select! {
+                item = self.pop() => return Some(item),
+                _ = closed.changed() => {}
+            }
+        }
+    }
+    /// Pop items from the queue and call `f` for each of them, running at
+    /// most `concurrency` of the returned futures at the same time. This
+    /// method returns once the queue is closed and all items have been
+    /// processed.
+    ///
+    /// The futures are polled by the task calling this method. Spawn a
+    /// task inside of `f` if the work needs to run in parallel.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `concurrency` is zero.
+    pub async fn for_each_concurrent<F, Fut>(&self, concurrency: usize, f: F)
+    where
+        F: Fn(T) -> Fut,
+        Fut: Future<Output = ()>,
+    {
+        crate::consumer::for_each_concurrent(|| self.pop_or_closed(), concurrency, f).await
+    }
+    /// Close the queue and wait up to `drain_timeout` for the consumers
+    /// to drain it. If the queue could not be drained in time the number
+    /// of remaining items is returned as `Err<usize>`.
+    ///
+    /// **Note:** This method requires a `tokio` runtime with the time
+    /// driver enabled.
+    pub async fn shutdown(&self, drain_timeout: Duration) -> Result<(), usize> {
+        self.close();
+        match tokio::time::timeout(drain_timeout, self.wait_empty()).await {
+            Ok(()) => Ok(()),
+            Err(_) => Err(self.len()),
+        }
+    }
+}
+
+#[cfg(feature = "tokio-mpsc")]
+impl<T: Send + 'static> Queue<T> {
+    /// Spawn a task which receives items from `rx` and pushes them into
+    /// the queue waiting for the queue to have room for every item. The
+    /// task finishes once all senders of the channel have been dropped and
+    /// the channel is drained.
+    pub fn forward_from(self: Arc<Self>, mut rx: mpsc::Receiver<T>) -> JoinHandle<()> {
+        tokio::spawn(async move {
+            while let Some(item) = rx.recv().await {
+                self.push(item).await;
+            }
+        })
+    }
+    /// Spawn a task which pops items from the queue and sends them into
+    /// `tx` waiting for the channel to have room for every item. The task
+    /// finishes once the queue is closed and drained or the receiver of
+    /// the channel has been dropped. In the latter case the item which
+    /// could not be sent is dropped.
+    pub fn forward_to(self: Arc<Self>, tx: mpsc::Sender<T>) -> JoinHandle<()> {
+        tokio::spawn(async move {
+            while let Some(item) = self.pop_or_closed().await {
+                if tx.send(item).await.is_err() {
+                    break;
+                }
+            }
+        })
+    }
+}
+
+#[cfg(feature = "std-mpsc")]
+impl<T: Send + 'static> Queue<T> {
+    /// Spawn a blocking task which receives items from the standard
+    /// library channel `rx` and pushes them into the queue blocking the
+    /// thread while the queue is full. The task finishes once all senders
+    /// of the channel have been dropped and the channel is drained.
+    ///
+    /// The task runs on the blocking thread pool of the runtime via
+    /// `tokio::task::spawn_blocking`.
+    pub fn pump_from_std(self: Arc<Self>, rx: std::sync::mpsc::Receiver<T>) -> JoinHandle<()> {
+        tokio::task::spawn_blocking(move || {
+            for item in rx {
+                self.push_blocking(item);
+            }
+        })
+    }
+}
+
+#[cfg(feature = "broadcast")]
+impl<T: Clone + Send + 'static> Queue<T> {
+    /// Turn the queue into a [`Broadcast`] adapter which delivers every
+    /// popped item to all subscribers. `capacity` is the number of items
+    /// a subscriber can fall behind before it starts skipping items.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `capacity` is zero or larger than `usize::MAX / 2`.
+    pub fn broadcast(self: Arc<Self>, capacity: usize) -> Broadcast<T> {
+        Broadcast::spawn(capacity, |sender| async move {
+            while let Some(item) = self.pop_or_closed().await {
+                // Sending only fails if there are no subscribers in
+                // which case the item is dropped.
+                let _ = sender.send(item);
+            }
+        })
+    }
+}
+
+#[cfg(feature = "pool")]
+impl<T: Send + 'static> Queue<T> {
+    /// Spawn a [`WorkerPool`] of `workers` tasks which pop items from the
+    /// queue and call `f` for each of them. Every worker awaits the
+    /// future returned by `f` before popping the next item.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `workers` is zero.
+    pub fn worker_pool<F, Fut>(self: Arc<Self>, workers: usize, f: F) -> WorkerPool<Self>
+    where
+        F: Fn(T) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        let f = Arc::new(f);
+        WorkerPool::spawn(self, workers, Self::close, |queue| {
+            let f = f.clone();
+            async move {
+                while let Some(item) = queue.pop_or_closed().await {
+                    f(item).await;
+                }
+            }
+        })
+    }
+}
+
+/// Error returned by [`Queue::try_with_capacity`] if the requested
+/// capacity can't be used.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum CapacityError {
+    /// The capacity is zero.
+    Zero,
+    /// The capacity is too large to be allocated.
+    TooLarge,
+}
+
+impl std::fmt::Display for CapacityError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Zero => f.write_str("queue capacity must not be zero"),
+            Self::TooLarge => f.write_str("queue capacity is too large"),
+        }
+    }
+}
+
+impl std::error::Error for CapacityError {}
+
+/// Room reserved in a queue for pushing items without blocking.
+///
+/// This object is created by the [`Queue::reserve`] method. Room which has
+/// not been used when it is dropped is released again.
+pub struct Reservation<'a, T> {
+    queue: &'a Queue<T>,
+    remaining: usize,
+}
+
+impl<'a, T> Reservation<'a, T> {
+    /// Push an item into the queue using up one of the reserved slots.
+    /// If all reserved slots have been used up or the queue is closed the
+    /// item is returned as `Err<T>`.
+    pub fn push(&mut self, item: T) -> Result<(), T> {
+        if self.remaining == 0 || self.queue.is_closed() {
+            return Err(item);
+        }
+        self.remaining -= 1;
+        self.queue.push_permitted(item);
+        Ok(())
+    }
+    /// Get the number of reserved slots which have not been used, yet.
+    pub fn remaining(&self) -> usize {
+        self.remaining
+    }
+}
+
+impl<'a, T> Drop for Reservation<'a, T> {
+    fn drop(&mut self) {
+        // The permits were forgotten by `reserve`. Every push consumed
+        // one of them so exactly the unused ones need to be returned.
+        if self.remaining > 0 {
+            self.queue.release_push_permits(self.remaining);
+        }
+    }
+}
+
+/// Poller for the items of a [`Queue`] which can be embedded in a manually
+/// implemented `Future` or `Stream` without boxing.
+///
+/// Every task polling the queue needs its own poller. While it waits for
+/// an item it is counted in `available` just like a future waiting in
+/// `pop`. No item is handed to a poller before it takes it out of the
+/// queue so it can be dropped at any time without losing an item.
+///
+/// **Note:** Futures waiting in `pop` are served first. A poller only
+/// gets an item that no waiting `pop` has been granted.
+///
+/// ```
+/// use std::future::poll_fn;
+/// use std::pin::pin;
+///
+/// use deadqueue::limited::{PollPop, Queue};
+///
+/// # #[tokio::main]
+/// # async fn main() {
+/// let queue = Queue::with_capacity(2);
+/// let mut poller = pin!(PollPop::new(&queue));
+/// queue.try_push(1).unwrap();
+/// assert_eq!(poll_fn(|cx| poller.as_mut().poll_pop(cx)).await, 1);
+/// # }
+/// ```
+pub struct PollPop<'a, T> {
+    queue: &'a Queue<T>,
+    txn: Option<TransactionSub<'a>>,
+    notified: Notified<'a>,
+}
+
+impl<'a, T> PollPop<'a, T> {
+    /// Create new poller for the items of the given queue.
+    pub fn new(queue: &'a Queue<T>) -> Self {
+        Self {
+            queue,
+            txn: None,
+            notified: queue.notify_poll_pop.notified(),
+        }
+    }
+    /// Poll for an item of the queue. If the queue is currently empty
+    /// the waker of `cx` is registered and woken once an item is pushed.
+    /// After an item has been returned the poller can be polled again for
+    /// the next one.
+    pub fn poll_pop(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<T> {
+        // SAFETY: `notified` is structurally pinned. It is never moved out
+        // of the poller which neither implements `Unpin` nor `Drop`.
+        let this = unsafe { self.get_unchecked_mut() };
+        let mut notified = unsafe { Pin::new_unchecked(&mut this.notified) };
+        let queue = this.queue;
+        loop {
+            if this.txn.is_none() {
+                this.txn = Some(queue.available.sub());
+            }
+            if let Ok(permit) = queue.pop_semaphore.try_acquire() {
+                match queue.queue.pop() {
+                    Some(item) => {
+                        this.txn.take().unwrap().commit();
+                        queue.popped();
+                        queue.permit_ledger.forget(permit);
+                        queue.release_push_permits(1);
+                        return Poll::Ready(item);
+                    }
+                    // See `Queue::pop`
+                    None => queue.permit_ledger.forget(permit),
+                }
+                continue;
+            }
+            // Every push notifies one poller. A push which happened before
+            // the poller was registered leaves a notification behind so it
+            // is not missed.
+            match notified.as_mut().poll(cx) {
+                Poll::Ready(()) => notified.set(queue.notify_poll_pop.notified()),
+                Poll::Pending => return Poll::Pending,
+            }
+        }
+    }
+}
+
+impl<'a, T> Debug for PollPop<'a, T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("PollPop")
+            .field("queue", &self.queue)
+            .field("waiting", &self.txn.is_some())
+            .finish()
+    }
+}
+
+/// View of a queue which transforms the items when popping them.
+///
+/// This object is created by the [`Queue::map`] method. Items are pushed
+/// as `T` and returned as `U` by the pop methods.
+pub struct MappedQueue<T, U, F> {
+    queue: Queue<T>,
+    f: F,
+    _marker: PhantomData<fn() -> U>,
+}
+
+impl<T, U, F: Fn(T) -> U> MappedQueue<T, U, F> {
+    /// Get an item from the queue and transform it. If the queue is
+    /// currently empty this method blocks until an item is available.
+    pub async fn pop(&self) -> U {
+        (self.f)(self.queue.pop().await)
+    }
+    /// Try to get an item from the queue and transform it. If the queue
+    /// is currently empty return None instead.
+    pub fn try_pop(&self) -> Option<U> {
+        self.queue.try_pop().map(&self.f)
+    }
+    /// Push an item into the queue
+    pub async fn push(&self, item: T) {
+        self.queue.push(item).await
+    }
+    /// Try to push an item into the queue. If the queue is full
+    /// or closed the item is returned as `Err<T>`.
+    pub fn try_push(&self, item: T) -> Result<(), T> {
+        self.queue.try_push(item)
+    }
+    /// Get current length of queue (number of items currently stored).
+    pub fn len(&self) -> usize {
+        self.queue.len()
+    }
+    /// Returns `true` if the queue is empty.
+    pub fn is_empty(&self) -> bool {
+        self.queue.is_empty()
+    }
+    /// Get a reference to the underlying queue.
+    pub fn get_ref(&self) -> &Queue<T> {
+        &self.queue
+    }
+    /// Consume the view returning the underlying queue.
+    pub fn into_inner(self) -> Queue<T> {
+        self.queue
+    }
+}
+
+impl<T, U, F> Debug for MappedQueue<T, U, F> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("MappedQueue")
+            .field("queue", &self.queue)
+            .finish()
+    }
+}
+
+/// Iterator which pops items from a [`Queue`] until it is empty.
+///
+/// This object is created by the [`Queue::drain_iter`] method.
+pub struct DrainIter<'a, T> {
+    queue: &'a Queue<T>,
+}
+
+impl<'a, T> Iterator for DrainIter<'a, T> {
+    type Item = T;
+    fn next(&mut self) -> Option<T> {
+        self.queue.try_pop()
+    }
+}
+
+impl<'a, T> Debug for DrainIter<'a, T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("DrainIter")
+            .field("queue", &self.queue)
+            .finish()
+    }
+}
+
+/// Limited queue with a capacity fixed at compile time.
+///
+/// The underlying [`Queue`] is created with a capacity of `N` and can't
+/// be created with any other capacity. A capacity of zero is rejected at
+/// compile time.
+pub struct FixedQueue<T, const N: usize> {
+    queue: Queue<T>,
+}
+
+impl<T, const N: usize> FixedQueue<T, N> {
+    /// Capacity of the queue
+    pub const CAPACITY: usize = {
+        assert!(N > 0, "capacity must be greater than zero");
+        N
+    };
+    /// Create new empty queue with a capacity of `N`.
+    pub fn new() -> Self {
+        Self {
+            queue: Queue::with_capacity(Self::CAPACITY),
+        }
+    }
+    /// Get an item from the queue. If the queue is currently empty
+    /// this method blocks until an item is available.
+    pub async fn pop(&self) -> T {
+        self.queue.pop().await
+    }
+    /// Try to get an item from the queue. If the queue is currently
+    /// empty return None instead.
+    pub fn try_pop(&self) -> Option<T> {
+        self.queue.try_pop()
+    }
+    /// Push an item into the queue
+    pub async fn push(&self, item: T) {
+        self.queue.push(item).await
+    }
+    /// Try to push an item into the queue. If the queue is full
+    /// or closed the item is returned as `Err<T>`.
+    pub fn try_push(&self, item: T) -> Result<(), T> {
+        self.queue.try_push(item)
+    }
+    /// Get capacity of the queue. This is always `N`.
+    pub const fn capacity(&self) -> usize {
+        N
+    }
+    /// Get current length of queue (number of items currently stored).
+    pub fn len(&self) -> usize {
+        self.queue.len()
+    }
+    /// Returns `true` if the queue is empty.
+    pub fn is_empty(&self) -> bool {
+        self.queue.is_empty()
+    }
+    /// Returns `true` if the queue is full.
+    pub fn is_full(&self) -> bool {
+        self.queue.is_full()
+    }
+    /// Get a reference to the underlying queue.
+    pub fn get_ref(&self) -> &Queue<T> {
+        &self.queue
+    }
+    /// Consume the fixed queue returning the underlying queue.
+    pub fn into_inner(self) -> Queue<T> {
+        self.queue
+    }
+}
+
+impl<T, const N: usize> Default for FixedQueue<T, N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T, const N: usize> Debug for FixedQueue<T, N> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("FixedQueue")
+            .field("queue", &self.queue)
+            .finish()
+    }
+}
+
+impl<T> Queue<Box<T>> {
+    /// Push a boxed item into the queue. Only the pointer is moved into
+    /// the queue which avoids copying large items around. Use this with a
+    /// [`BoxQueue`] to keep a single heap allocation per item from the
+    /// producer all the way to the consumer.
+    pub async fn push_boxed(&self, item: Box<T>) {
+        self.push(item).await
+    }
+    /// Get an item from the queue and move it out of its box. If the
+    /// queue is currently empty this method blocks until an item is
+    /// available.
+    pub async fn pop_unboxed(&self) -> T {
+        *self.pop().await
+    }
+    /// Try to get an item from the queue and move it out of its box. If
+    /// the queue is currently empty return None instead.
+    pub fn try_pop_unboxed(&self) -> Option<T> {
+        self.try_pop().map(|item| *item)
+    }
+}
+
+#[cfg(feature = "tracing-context")]
+impl<T> Queue<Spanned<T>> {
+    /// Push an item into the queue together with the current span. See
+    /// `push` for the behavior when the queue is full or closed.
+    pub async fn push_spanned(&self, item: T) {
+        self.push(Spanned::new(item)).await
+    }
+    /// Try to push an item into the queue together with the current span.
+    /// If the queue is full or closed the item is returned as `Err<T>`.
+    pub fn try_push_spanned(&self, item: T) -> Result<(), T> {
+        self.try_push(Spanned::new(item))
+            .map_err(Spanned::into_inner)
+    }
+    /// Get an item from the queue together with the span that was current
+    /// when it was pushed. If the queue is currently empty this method
+    /// blocks until an item is available.
+    pub async fn pop_with_span(&self) -> (Span, T) {
+        self.pop().await.into_parts()
+    }
+    /// Try to get an item from the queue together with the span that was
+    /// current when it was pushed. If the queue is currently empty return
+    /// None instead.
+    pub fn try_pop_with_span(&self) -> Option<(Span, T)> {
+        self.try_pop().map(Spanned::into_parts)
+    }
+}
+
+impl<T> Default for Queue<T> {
+    /// Create new empty queue with a capacity of zero.
+    ///
+    /// **Note:** The capacity of a limited queue cannot be changed so such
+    /// a queue rejects all items: `try_push` always fails and `push` blocks
+    /// forever.
+    fn default() -> Self {
+        Self::with_capacity(0)
+    }
+}
+
+impl<T> crate::Queue for Queue<T> {
+    type Item = T;
+    fn try_pop(&self) -> Option<T> {
+        self.try_pop()
+    }
+    fn try_push(&self, item: T) -> Result<(), T> {
+        self.try_push(item)
+    }
+    fn len(&self) -> usize {
+        self.len()
+    }
+    fn is_empty(&self) -> bool {
+        self.is_empty()
+    }
+    fn capacity(&self) -> Option<usize> {
+        Some(self.capacity())
+    }
+    fn available(&self) -> isize {
+        self.available()
+    }
+    fn close(&self) {
+        self.close()
+    }
+    fn is_closed(&self) -> bool {
+        self.is_closed()
+    }
+}
+
+impl<T> FromIterator<T> for Queue<T> {
+    /// Create new queue from the given iterator. The capacity of the
+    /// queue equals the number of items yielded by the iterator.
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        let items: Vec<T> = iter.into_iter().collect();
+        let size = items.len();
+        let queue = ArrayQueue::new(size.max(1));
+        for item in items {
+            queue.push(item).ok().unwrap();
+        }
+        Self {
+            queue,
+            capacity: size,
+            push_semaphore: Semaphore::new(0),
+            pop_semaphore: Semaphore::new(size),
+            available: Available::new(size.try_into().unwrap()),
+            total_pushed: AtomicU64::new(size as u64),
+            ..Self::default()
+        }
     }
 }
 
@@ -170,17 +1578,18 @@ where
     fn from(iter: I) -> Self {
         let iter = iter.into_iter();
         let size = iter.len();
-        let queue = ArrayQueue::new(size);
+        let queue = ArrayQueue::new(size.max(1));
         for obj in iter {
             queue.push(obj).ok().unwrap();
         }
-        Queue {
-            queue: ArrayQueue::new(size),
+        Self {
+            queue,
+            capacity: size,
             push_semaphore: Semaphore::new(0),
             pop_semaphore: Semaphore::new(size),
             available: Available::new(size.try_into().unwrap()),
-            notifier_full: crate::new_notifier(),
-            notifier_empty: crate::new_notifier(),
+            total_pushed: AtomicU64::new(size as u64),
+            ..Self::default()
         }
     }
 }
@@ -0,0 +1,77 @@
+//! Pool of workers processing the items of a queue
+
+use std::fmt::Debug;
+use std::future::Future;
+use std::sync::Arc;
+
+use tokio::task::JoinHandle;
+
+/// Pool of worker tasks which pop items from a queue and run a closure
+/// for each of them.
+///
+/// The pool is created by the `worker_pool` method of the queues. Every
+/// worker processes one item at a time and stops once the queue is
+/// closed and drained. Use [`WorkerPool::queue`] to push items and
+/// [`WorkerPool::join`] to shut the pool down gracefully. Dropping the
+/// pool without calling `join` detaches the workers.
+pub struct WorkerPool<Q> {
+    queue: Arc<Q>,
+    close: fn(&Q),
+    workers: Vec<JoinHandle<()>>,
+}
+
+impl<Q: Send + Sync + 'static> WorkerPool<Q> {
+    /// Spawn `workers` tasks each running the future returned by `run`.
+    pub(crate) fn spawn<F, Fut>(queue: Arc<Q>, workers: usize, close: fn(&Q), run: F) -> Self
+    where
+        F: Fn(Arc<Q>) -> Fut,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        assert!(workers > 0, "workers must be greater than zero");
+        Self {
+            workers: (0..workers)
+                .map(|_| tokio::spawn(run(queue.clone())))
+                .collect(),
+            queue,
+            close,
+        }
+    }
+    /// Get the queue the workers are popping from.
+    pub fn queue(&self) -> &Arc<Q> {
+        &self.queue
+    }
+    /// Get the number of workers.
+    pub fn workers(&self) -> usize {
+        self.workers.len()
+    }
+    /// Close the queue and wait until the workers have processed all
+    /// items which are still in the queue.
+    ///
+    /// # Panics
+    ///
+    /// If a worker panicked the panic is resumed once all other workers
+    /// have finished.
+    pub async fn join(self) {
+        (self.close)(&self.queue);
+        let mut panic = None;
+        for worker in self.workers {
+            if let Err(err) = worker.await {
+                if err.is_panic() && panic.is_none() {
+                    panic = Some(err.into_panic());
+                }
+            }
+        }
+        if let Some(panic) = panic {
+            std::panic::resume_unwind(panic);
+        }
+    }
+}
+
+impl<Q: Debug> Debug for WorkerPool<Q> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("WorkerPool")
+            .field("queue", &self.queue)
+            .field("workers", &self.workers.len())
+            .finish()
+    }
+}
@@ -2,14 +2,39 @@
 
 use std::convert::TryInto;
 use std::fmt::Debug;
+use std::future::Future;
 use std::iter::FromIterator;
-use std::sync::atomic::{AtomicUsize, Ordering};
+use std::marker::PhantomData;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+#[cfg(any(
+    feature = "tokio-mpsc",
+    feature = "std-mpsc",
+    feature = "broadcast",
+    feature = "pool"
+))]
+use std::sync::Arc;
+use std::time::Duration;
 
-use tokio::sync::{Mutex, Semaphore};
+#[cfg(feature = "tokio-mpsc")]
+use tokio::sync::mpsc;
+use tokio::sync::{oneshot, watch, Mutex, Semaphore};
+#[cfg(any(feature = "tokio-mpsc", feature = "std-mpsc"))]
+use tokio::task::JoinHandle;
+#[cfg(feature = "cancellation")]
+use tokio_util::sync::CancellationToken;
+#[cfg(feature = "tracing-context")]
+use tracing::Span;
 
-use crate::atomic::Available;
+use crate::atomic::{Available, PermitLedger};
+#[cfg(feature = "broadcast")]
+use crate::broadcast::Broadcast;
+#[cfg(feature = "pool")]
+use crate::pool::WorkerPool;
+#[cfg(feature = "tracing-context")]
+use crate::span::Spanned;
+use crate::telemetry::QueueMetrics;
 use crate::unlimited::Queue as UnlimitedQueue;
-use crate::{Notifier, Receiver};
+use crate::{Notifier, PushOutcome, QueueEvent, QueueStatsSnapshot, WaitStats};
 
 /// Queue that is limited in size and supports resizing.
 ///
@@ -19,6 +44,7 @@ use crate::{Notifier, Receiver};
 ///   - Based on `deadqueue::unlimited::Queue`
 ///   - Has limited capacity with back pressure on push
 ///   - Supports resizing
+///   - `Send` and `Sync` if `T: Send`
 ///   - Enabled via the `resizable` feature in your `Cargo.toml`
 pub struct Queue<T> {
     queue: UnlimitedQueue<T>,
@@ -28,150 +54,983 @@ pub struct Queue<T> {
     resize_mutex: Mutex<()>,
     notifier_full: Notifier,
     notifier_empty: Notifier,
+    notifier_not_full: Notifier,
+    closed: AtomicBool,
+    notifier_closed: Notifier,
+    capacity_sender: watch::Sender<usize>,
+    metrics: Option<QueueMetrics>,
+    permit_ledger: PermitLedger,
 }
 
+/// Queue of boxed items. This is useful for queueing trait objects like
+/// jobs of different types:
+///
+/// ```
+/// use deadqueue::resizable::BoxQueue;
+///
+/// let offset = 1;
+/// let queue: BoxQueue<dyn Fn() -> usize + Send> = BoxQueue::with_capacity(2);
+/// assert!(queue.try_push(Box::new(|| 1)).is_ok());
+/// assert!(queue.try_push(Box::new(move || offset + 1)).is_ok());
+/// assert_eq!(queue.try_pop().unwrap()(), 1);
+/// ```
+pub type BoxQueue<T> = Queue<Box<T>>;
+
+/// Queue of items which carry the tracing span that was current when they
+/// were pushed. Use `push_spanned` and `pop_with_span` to continue the
+/// trace of the producer in the consumer.
+///
+/// Enabled via the `tracing-context` feature in your `Cargo.toml`.
+#[cfg(feature = "tracing-context")]
+pub type SpanQueue<T> = Queue<Spanned<T>>;
+
 impl<T> Queue<T> {
     /// Create new empty queue
+    #[deprecated(note = "use `Queue::with_capacity` instead")]
     pub fn new(max_size: usize) -> Self {
+        Self::with_capacity(max_size)
+    }
+    /// Create new empty queue which can hold up to `capacity` items.
+    ///
+    /// ```
+    /// use deadqueue::resizable::Queue;
+    ///
+    /// let queue: Queue<usize> = Queue::with_capacity(2);
+    /// assert!(queue.try_push(1).is_ok());
+    /// assert!(queue.try_push(2).is_ok());
+    /// assert_eq!(queue.try_push(3), Err(3));
+    /// assert_eq!(queue.capacity(), 2);
+    /// ```
+    pub fn with_capacity(capacity: usize) -> Self {
         Self {
             queue: UnlimitedQueue::new(),
-            capacity: AtomicUsize::new(max_size),
-            push_semaphore: Semaphore::new(max_size),
+            capacity: AtomicUsize::new(capacity),
+            push_semaphore: Semaphore::new(capacity),
             available: Available::new(0),
             resize_mutex: Mutex::default(),
             notifier_full: crate::new_notifier(),
             notifier_empty: crate::new_notifier(),
+            notifier_not_full: crate::new_notifier(),
+            closed: AtomicBool::new(false),
+            notifier_closed: crate::new_notifier(),
+            capacity_sender: watch::channel(capacity).0,
+            metrics: None,
+            permit_ledger: PermitLedger::default(),
+        }
+    }
+    /// Create new queue containing the items of `iter` which can hold up
+    /// to `capacity` items.
+    ///
+    /// If `iter` yields more than `capacity` items the capacity is
+    /// clamped to the number of items. No items are dropped.
+    ///
+    /// ```
+    /// use deadqueue::resizable::Queue;
+    ///
+    /// let queue = Queue::from_iter_with_capacity(vec![1, 2, 3], 5);
+    /// assert_eq!(queue.capacity(), 5);
+    /// let queue = Queue::from_iter_with_capacity(vec![1, 2, 3], 2);
+    /// assert_eq!(queue.capacity(), 3);
+    /// ```
+    pub fn from_iter_with_capacity<I: IntoIterator<Item = T>>(iter: I, capacity: usize) -> Self {
+        let queue = Self::from_iter(iter);
+        // No one else can hold the resize lock of a queue which is being
+        // constructed.
+        queue.grow_locked(capacity.saturating_sub(queue.len()));
+        queue
+    }
+    /// Give the queue a name which is shown in its `Debug` output. This
+    /// helps telling queues apart in logs. Queues created by `new_named`
+    /// use the name passed to it.
+    pub fn with_name(mut self, name: impl Into<String>) -> Self {
+        self.queue = self.queue.with_name(name);
+        self
+    }
+    /// Get the name of the queue as given to `with_name` or `new_named`.
+    pub fn name(&self) -> Option<&str> {
+        self.queue.name()
+    }
+    /// Enable the accounting of the time spent waiting in `pop`. The
+    /// recorded times can be retrieved via `wait_stats`.
+    ///
+    /// This is disabled by default as it requires reading the clock
+    /// twice for every call to `pop`.
+    pub fn record_waits(mut self) -> Self {
+        self.queue = self.queue.record_waits();
+        self
+    }
+    /// Get the wait times recorded so far. All values are zero unless
+    /// `record_waits` was enabled.
+    pub fn wait_stats(&self) -> WaitStats {
+        self.queue.wait_stats()
+    }
+    /// Get the time since the last successful pop or since the creation
+    /// of the queue if no item has been popped yet. A growing value while
+    /// the queue is not empty indicates stuck consumers.
+    pub fn idle_duration(&self) -> Duration {
+        self.queue.idle_duration()
+    }
+    /// Get the total number of items pushed into the queue since it was
+    /// created. Together with `total_popped` this can be used to compute
+    /// the throughput of the queue. While no operation is in progress
+    /// `total_pushed() == total_popped() + len()` holds.
+    pub fn total_pushed(&self) -> u64 {
+        self.queue.total_pushed()
+    }
+    /// Get the total number of items popped from the queue since it was
+    /// created. Items dropped by shrinking the queue are counted, too.
+    pub fn total_popped(&self) -> u64 {
+        self.queue.total_popped()
+    }
+    /// Create new empty queue which can hold up to `capacity` items and
+    /// exports its statistics to the `metrics` crate labeled with the
+    /// given name. See the crate documentation for the list of exported
+    /// metrics.
+    #[cfg(feature = "metrics")]
+    pub fn new_named(capacity: usize, name: &str) -> Self {
+        let queue = UnlimitedQueue::new_named(name);
+        // Share the metric handles with the inner queue instead of
+        // registering them a second time.
+        let metrics = queue.metrics().cloned();
+        if let Some(metrics) = &metrics {
+            metrics.set_capacity(capacity);
+        }
+        Self {
+            queue,
+            metrics,
+            ..Self::with_capacity(capacity)
+        }
+    }
+    /// Notify the subscribers and update the capacity gauge after the
+    /// capacity has been changed.
+    fn capacity_changed(&self) {
+        // Must only be called after the new capacity has been stored. The
+        // watch channel synchronizes sender and receivers so a subscriber
+        // woken by this notification reads the new value from `capacity`.
+        self.capacity_sender.send_replace(self.capacity());
+        self.queue.notify_changed();
+        if let Some(metrics) = &self.metrics {
+            metrics.set_capacity(self.capacity());
         }
     }
     /// Get an item from the queue. If the queue is currently empty
     /// this method blocks until an item is available.
+    ///
+    /// **Note:** Closing the queue does not wake tasks waiting in this
+    /// method as there is no item to return. Use `pop_or_closed` if the
+    /// consumer needs to observe the closure of the queue.
+    ///
+    /// **Cancel safety:** Dropping the future before it completes never
+    /// removes an item from the queue, even if an item was handed to it
+    /// while it was waiting. The item stays available to other consumers.
+    ///
+    /// **Fairness:** This method takes part in the cooperative scheduling
+    /// budget of tokio. A task draining a full queue yields to the runtime
+    /// every now and then even though items are available right away.
+    /// This costs a little throughput but keeps other tasks on the same
+    /// thread responsive. `try_pop` never yields.
     pub async fn pop(&self) -> T {
-        let (txn, new_len) = self.available.sub();
+        let txn = self.available.sub();
         let item = self.queue.pop().await;
         txn.commit();
-        if new_len <= 0 {
+        // The permit of the item was forgotten by the inner queue.
+        self.permit_ledger.forgotten(1);
+        if self.queue.is_empty() {
             self.notify_empty();
         }
-        self.push_semaphore.add_permits(1);
+        self.release_push_permits(1);
         item
     }
+    /// Get an item from the queue like `pop` but return `None` once
+    /// `token` is cancelled. If the token is already cancelled no item is
+    /// taken from the queue.
+    ///
+    /// This relies on the cancel safety of `pop`: an item is never lost
+    /// and `available()` is restored when the wait is cancelled.
+    ///
+    /// Enabled via the `cancellation` feature in your `Cargo.toml`.
+    #[cfg(feature = "cancellation")]
+    pub async fn pop_cancellable(&self, token: &CancellationToken) -> Option<T> {
+        tokio::select! {
+            biased;
+            _ = token.cancelled() => None,
+            item = self.pop() => Some(item),
+        }
+    }
     /// Try to get an item from the queue. If the queue is currently
     /// empty return None instead.
     pub fn try_pop(&self) -> Option<T> {
-        let (txn, new_len) = self.available.sub();
+        let txn = self.available.sub();
         let item = self.queue.try_pop();
         if item.is_some() {
             txn.commit();
-            if new_len <= 0 {
+            self.permit_ledger.forgotten(1);
+            if self.queue.is_empty() {
                 self.notify_empty();
             }
-            self.push_semaphore.add_permits(1);
+            self.release_push_permits(1);
         }
         item
     }
+    /// Try to get the first item matching `pred` from the queue. Items
+    /// in front of it are popped and dropped. If no item matches the
+    /// queue is drained completely and `None` is returned.
+    ///
+    /// Every dropped item is popped like with `try_pop` so the counters
+    /// of the queue stay in sync.
+    pub fn try_pop_find<F: Fn(&T) -> bool>(&self, pred: F) -> Option<T> {
+        while let Some(item) = self.try_pop() {
+            if pred(&item) {
+                return Some(item);
+            }
+        }
+        None
+    }
+    /// Try to get an item from the queue like `try_pop` and return it
+    /// together with the number of futures waiting in `pop` right after
+    /// the item was taken. See `pending_pops`.
+    ///
+    /// **Note:** Futures waiting in `pop` are handed new items before
+    /// `try_pop` can take them. Thus the count is only non-zero if other
+    /// consumers started waiting concurrently, e.g. because this call
+    /// took the last item. A non-zero count means the queue is running
+    /// dry under contention.
+    pub fn try_pop_with_waiters(&self) -> Option<(T, usize)> {
+        let item = self.try_pop()?;
+        Some((item, self.pending_pops()))
+    }
+    /// Try to get an item from the queue like `try_pop` and return it
+    /// together with the length of the queue right before and right
+    /// after the item was taken as `(item, len_before, len_after)`.
+    ///
+    /// **Note:** The lengths are read right around the pop. Without
+    /// concurrent pushes and pops `len_before == len_after + 1` holds.
+    /// Otherwise they may differ by more as other tasks can modify the
+    /// queue between the reads.
+    pub fn try_pop_delta(&self) -> Option<(T, usize, usize)> {
+        let len_before = self.len();
+        let item = self.try_pop()?;
+        Some((item, len_before, self.len()))
+    }
+    /// Try to get an item from the queue calling `try_pop` up to
+    /// `max_spins` times in a busy loop. Returns `None` if the queue is
+    /// still empty after that.
+    ///
+    /// **Note:** This burns CPU while spinning and never yields to the
+    /// runtime. It is only meant for latency critical consumers running
+    /// on a dedicated core. Use `pop` in all other cases.
+    pub fn spin_pop(&self, max_spins: usize) -> Option<T> {
+        for _ in 0..max_spins {
+            if let Some(item) = self.try_pop() {
+                return Some(item);
+            }
+            std::hint::spin_loop();
+        }
+        None
+    }
+    /// Get an iterator which pops items from the queue without blocking.
+    /// Every call to `next` is a `try_pop` so the iterator ends as soon
+    /// as the queue is empty at the moment `next` is called.
+    pub fn drain_iter(&self) -> DrainIter<'_, T> {
+        DrainIter { queue: self }
+    }
+    /// Fill `out` with items popped from the queue without blocking and
+    /// return the number of items written. The slots are filled from the
+    /// start of the slice until it is full or the queue is empty. Slots
+    /// after the returned count are left untouched.
+    pub fn try_pop_slice(&self, out: &mut [Option<T>]) -> usize {
+        // `zip` polls `out` first so no item is popped once it is full.
+        out.iter_mut()
+            .zip(self.drain_iter())
+            .map(|(slot, item)| *slot = Some(item))
+            .count()
+    }
+    /// Get up to `max` items from the queue waiting at most `timeout` for
+    /// them. This method returns as soon as `max` items have been
+    /// collected or the timeout has elapsed, whichever comes first. The
+    /// timeout is not reset when an item is received. The returned vector
+    /// contains the items collected so far and may be empty.
+    pub async fn pop_n_timeout(&self, max: usize, timeout: Duration) -> Vec<T> {
+        let mut items = Vec::new();
+        let deadline = tokio::time::sleep(timeout);
+        tokio::pin!(deadline);
+        while items.len() < max {
+            tokio::select! {
+                biased;
+                item = self.pop() => items.push(item),
+                _ = &mut deadline => break,
+            }
+        }
+        items
+    }
+    /// Pop the items currently in the queue until it is empty or `timeout`
+    /// has elapsed, whichever comes first. The popped items are returned
+    /// in order and the items which could not be popped in time are left
+    /// in the queue. Unlike `pop_n_timeout` this method never waits for
+    /// new items to be pushed.
+    ///
+    /// **Note:** If other consumers pop the last item concurrently this
+    /// method waits for a new item until the timeout has elapsed. It
+    /// requires a `tokio` runtime with the time driver enabled.
+    pub async fn drain_for(&self, timeout: Duration) -> Vec<T> {
+        let mut items = Vec::new();
+        let deadline = tokio::time::Instant::now() + timeout;
+        // The clock is checked before every item as popping an item which
+        // is already in the queue completes without giving the timer a
+        // chance to fire.
+        while !self.is_empty() && tokio::time::Instant::now() < deadline {
+            match tokio::time::timeout_at(deadline, self.pop()).await {
+                Ok(item) => items.push(item),
+                Err(_) => break,
+            }
+        }
+        items
+    }
+    /// Get an item from the queue blocking the current thread until an
+    /// item is available.
+    ///
+    /// This method is meant for synchronous code which is not running
+    /// inside an async runtime. The calling thread is parked while waiting
+    /// so no runtime is required. **Never** call this method from within an
+    /// async context as it blocks the executor thread.
+    pub fn pop_blocking(&self) -> T {
+        crate::blocking::block_on(self.pop())
+    }
     /// Push an item into the queue
+    ///
+    /// **Note:** If the queue is closed the item is dropped. Use
+    /// `try_push` if you need to get the item back. Dropping the returned
+    /// future before it completes drops the item as well. Use
+    /// `push_cancellable` if the push might be cancelled.
     pub async fn push(&self, item: T) {
-        let permit = self.push_semaphore.acquire().await.unwrap();
-        let new_len = self.available.add();
-        self.queue.push(item);
-        if new_len >= self.capacity().try_into().unwrap() {
-            self.notify_full();
+        self.push_reporting(item).await;
+    }
+    /// Push an item into the queue like `push` but give the item back
+    /// if it could not be pushed.
+    ///
+    /// If the returned future is dropped before the item has been pushed,
+    /// e.g. because it lost a `select!` or hit a timeout, or if the queue
+    /// is closed the item is sent through the returned receiver. Once the
+    /// item is pushed the sender is dropped without sending anything.
+    pub fn push_cancellable(
+        &self,
+        item: T,
+    ) -> (impl Future<Output = ()> + '_, oneshot::Receiver<T>) {
+        let (sender, receiver) = oneshot::channel();
+        let mut item = crate::ReturnOnDrop(Some((item, sender)));
+        let future = async move {
+            if self.is_closed() {
+                return;
+            }
+            let permit = crate::trace::acquire(
+                &self.push_semaphore,
+                "push",
+                || self.len(),
+                self.metrics.as_ref(),
+            )
+            .await;
+            if self.is_closed() {
+                return;
+            }
+            self.permit_ledger.forget(permit);
+            self.push_permitted(item.take());
+        };
+        (future, receiver)
+    }
+    /// Push an item into the queue and report whether it filled the
+    /// queue. This can be used by producers to throttle proactively.
+    ///
+    /// **Note:** If the queue is closed the item is dropped and the
+    /// returned outcome describes the unchanged queue.
+    pub async fn push_reporting(&self, item: T) -> PushOutcome {
+        if self.is_closed() {
+            return self.unchanged_outcome();
         }
-        permit.forget();
+        let permit = crate::trace::acquire(
+            &self.push_semaphore,
+            "push",
+            || self.len(),
+            self.metrics.as_ref(),
+        )
+        .await;
+        if self.is_closed() {
+            return self.unchanged_outcome();
+        }
+        self.permit_ledger.forget(permit);
+        self.push_permitted(item)
+    }
+    fn unchanged_outcome(&self) -> PushOutcome {
+        PushOutcome {
+            became_full: false,
+            len_after: self.len(),
+        }
+    }
+    /// Push an item into the queue blocking the current thread until
+    /// there is room for the item.
+    ///
+    /// This method is meant for synchronous code which is not running
+    /// inside an async runtime. The calling thread is parked while waiting
+    /// so no runtime is required. **Never** call this method from within an
+    /// async context as it blocks the executor thread.
+    pub fn push_blocking(&self, item: T) {
+        crate::blocking::block_on(self.push(item))
     }
     /// Try to push an item to the queue. If the queue is currently
-    /// full return the object as `Err<T>`.
+    /// full or closed return the object as `Err<T>`.
     pub fn try_push(&self, item: T) -> Result<(), T> {
+        if self.is_closed() {
+            return Err(item);
+        }
         match self.push_semaphore.try_acquire() {
             Ok(permit) => {
-                let new_len = self.available.add();
-                self.queue.push(item);
-                if new_len >= self.capacity().try_into().unwrap() {
-                    self.notify_full();
-                }
-                permit.forget();
+                self.permit_ledger.forget(permit);
+                self.push_permitted(item);
                 Ok(())
             }
             Err(_) => Err(item),
         }
     }
+    /// Reserve room for `n` items. The returned [`Reservation`] can be used
+    /// to push up to `n` items without blocking. Reserved room which is
+    /// not used is released when the reservation is dropped. Returns
+    /// `None` if the queue is closed before the room could be reserved.
+    ///
+    /// **Note:** This method blocks forever if `n` exceeds the capacity
+    /// of the queue and the queue is not resized.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `n` exceeds `u32::MAX`.
+    pub async fn reserve(&self, n: usize) -> Option<Reservation<'_, T>> {
+        let permit = tokio::select! {
+            biased;
+            _ = self.closed() => return None,
+            permit = self.push_semaphore.acquire_many(n.try_into().unwrap()) => permit.unwrap(),
+        };
+        self.permit_ledger.forget(permit);
+        Some(Reservation {
+            queue: self,
+            remaining: n,
+        })
+    }
+    /// Try to reserve room for `n` items without blocking. Either all `n`
+    /// slots are reserved at once or none at all and `None` is returned.
+    /// Reserved room which is not used is released when the returned
+    /// [`Reservation`] is dropped.
+    pub fn try_reserve_slots(&self, n: usize) -> Option<Reservation<'_, T>> {
+        let permit = self
+            .push_semaphore
+            .try_acquire_many(n.try_into().ok()?)
+            .ok()?;
+        self.permit_ledger.forget(permit);
+        Some(Reservation {
+            queue: self,
+            remaining: n,
+        })
+    }
+    /// Try to push as many of the given items into the queue as there is
+    /// room for without blocking. The items which did not fit into the
+    /// queue are returned in their original order.
+    pub fn try_push_n(&self, items: Vec<T>) -> Vec<T> {
+        let mut items = items.into_iter();
+        while let Some(item) = items.next() {
+            if let Err(item) = self.try_push(item) {
+                return std::iter::once(item).chain(items).collect();
+            }
+        }
+        Vec::new()
+    }
+    /// Push all of the given items into the queue in their original order
+    /// waiting for room as needed. If the queue is closed the items which
+    /// have not been pushed yet are returned as `Err`.
+    ///
+    /// **Note:** Dropping the returned future before it completes drops
+    /// the items which have not been pushed yet. Use
+    /// `push_all_cancellable` if the push might be cancelled.
+    pub async fn push_all(&self, items: Vec<T>) -> Result<(), Vec<T>> {
+        let mut items = items.into_iter();
+        while let Some(item) = items.next() {
+            if self.is_closed() {
+                return Err(std::iter::once(item).chain(items).collect());
+            }
+            let permit = crate::trace::acquire(
+                &self.push_semaphore,
+                "push",
+                || self.len(),
+                self.metrics.as_ref(),
+            )
+            .await;
+            if self.is_closed() {
+                return Err(std::iter::once(item).chain(items).collect());
+            }
+            self.permit_ledger.forget(permit);
+            self.push_permitted(item);
+        }
+        Ok(())
+    }
+    /// Push all of the given items into the queue like `push_all` until
+    /// `token` is cancelled. The items which have not been pushed because
+    /// the token was cancelled or the queue was closed are returned in
+    /// their original order. An empty `Vec` means all items were pushed.
+    ///
+    /// Enabled via the `cancellation` feature in your `Cargo.toml`.
+    #[cfg(feature = "cancellation")]
+    pub async fn push_all_cancellable(&self, items: Vec<T>, token: &CancellationToken) -> Vec<T> {
+        let mut items = items.into_iter();
+        while let Some(item) = items.next() {
+            if self.is_closed() {
+                return std::iter::once(item).chain(items).collect();
+            }
+            let permit = tokio::select! {
+                biased;
+                _ = token.cancelled() => None,
+                permit = crate::trace::acquire(
+                    &self.push_semaphore,
+                    "push",
+                    || self.len(),
+                    self.metrics.as_ref(),
+                ) => Some(permit),
+            };
+            match permit {
+                Some(permit) if !self.is_closed() => {
+                    self.permit_ledger.forget(permit);
+                    self.push_permitted(item);
+                }
+                _ => return std::iter::once(item).chain(items).collect(),
+            }
+        }
+        Vec::new()
+    }
+    /// Push an item into the queue for which a permit of the
+    /// `push_semaphore` has already been acquired and forgotten.
+    fn push_permitted(&self, item: T) -> PushOutcome {
+        let new_len = self.available.add();
+        self.queue.push(item);
+        // The inner queue added a permit for the item.
+        self.permit_ledger.added(1);
+        self.permit_ledger.check();
+        let became_full = new_len >= self.capacity().try_into().unwrap();
+        if became_full {
+            self.notify_full();
+        }
+        PushOutcome {
+            became_full,
+            len_after: self.len(),
+        }
+    }
     /// Get capacity of the queue (maximum number of items queue can store).
     pub fn capacity(&self) -> usize {
         self.capacity.load(Ordering::Relaxed)
     }
+    /// Get a `watch::Receiver` which is updated whenever the capacity of
+    /// the queue changes. The initial value is the current capacity.
+    ///
+    /// A notification is only sent once the new capacity has been stored.
+    /// Thus `capacity()` called right after a notification returns the
+    /// notified value unless the capacity has been changed again since.
+    /// While a resize waits for room the intermediate capacities are not
+    /// notified, only the capacity reached in the end.
+    pub fn subscribe_capacity(&self) -> watch::Receiver<usize> {
+        self.capacity_sender.subscribe()
+    }
+    /// Get a `watch::Receiver` which is updated with the length of the
+    /// queue whenever it changes. Updates are coalesced so a slow receiver
+    /// only observes the latest length.
+    pub fn subscribe_len(&self) -> watch::Receiver<usize> {
+        self.queue.subscribe_len()
+    }
     /// Get current length of queue (number of items currently stored).
     pub fn len(&self) -> usize {
         self.queue.len()
     }
-    /// Returns `true` if the queue is empty.
+    /// Returns `true` if the queue is empty. This is `len() == 0` so
+    /// both methods always agree.
     pub fn is_empty(&self) -> bool {
-        self.queue.is_empty()
+        self.len() == 0
+    }
+    /// Get the `SegQueue` storing the items of the queue.
+    ///
+    /// # Safety
+    ///
+    /// The caller must not push items to or pop items from the returned
+    /// queue. Doing so bypasses the bookkeeping of this queue which can
+    /// make its methods panic, block forever or lose items. Read-only
+    /// methods like `len` and `is_empty` are fine but bypass the lock
+    /// taken by `contains` and related methods so they may observe the
+    /// queue while its items are taken out for a scan.
+    pub unsafe fn inner(&self) -> &crossbeam_queue::SegQueue<T> {
+        // The inner queue of a resizable queue always uses FIFO order.
+        self.queue.inner().unwrap()
     }
     /// Returns `true` if the queue is full.
-    /// **Note:** this can give an incorrect result if a simultaneous push/pop
-    /// and resize ocurr while this function is executing. try_push() is the
+    ///
+    /// **Note:** The length and the capacity are read one after the
+    /// other. A concurrent push or pop can change the length in between
+    /// and a resize in progress lowers the capacity one slot at a time
+    /// while it drops items, so the result is only a snapshot. Use
+    /// `try_is_full` to rule out a concurrent resize. try_push() is the
     /// reccomended and safer mechanism in most circumstances. This method
     /// is provided as a convenience API.
     pub fn is_full(&self) -> bool {
         self.len() >= self.capacity()
     }
+    /// Returns `Some(true)` if the queue is full or `None` if a resize
+    /// operation is in progress.
+    ///
+    /// Unlike `is_full` this method never observes the capacity of a
+    /// queue in the middle of a resize. Concurrent pushes and pops can
+    /// still change the length right after the check.
+    pub fn try_is_full(&self) -> Option<bool> {
+        let _guard = self.resize_mutex.try_lock().ok()?;
+        Some(self.is_full())
+    }
+    /// Get the number of items which can be pushed right now without
+    /// blocking.
+    ///
+    /// This number excludes slots held by a `Reservation` and unlike
+    /// `capacity() - len()` it stays consistent while a resize is in
+    /// progress.
+    pub fn available_permits(&self) -> usize {
+        self.push_semaphore.available_permits()
+    }
     /// The number of available items in the queue. If there are no
     /// items in the queue this number can become negative and stores the
     /// number of futures waiting for an item.
     pub fn available(&self) -> isize {
-        self.queue.available()
+        self.available.get()
+    }
+    /// Check the internal invariants of the queue and return `true` if
+    /// they hold:
+    ///
+    ///   - `len() == max(available(), 0)`
+    ///   - Every item in the queue can be popped without waiting
+    ///   - `len() <= capacity()` and the room left in the queue does not
+    ///     exceed `capacity() - len()`
+    ///
+    /// The invariants only hold while no other operation is in progress.
+    /// This method is meant for tests and only available with the
+    /// `testing` feature enabled.
+    #[cfg(feature = "testing")]
+    pub fn check_invariants(&self) -> bool {
+        let len = self.len();
+        let capacity = self.capacity();
+        self.queue.check_invariants()
+            && self.available.matches_len(len)
+            && len <= capacity
+            && self.push_semaphore.available_permits() <= capacity - len
+    }
+    /// Get the balance of the permits forgotten and added back by the
+    /// operations of the queue. Every push and pop forgets a permit and
+    /// adds one back afterwards. A negative balance means permits have
+    /// leaked which permanently shrinks the usable capacity of the queue.
+    /// In debug builds every push and pop asserts that the balance is
+    /// never positive.
+    ///
+    /// While no operation is in progress and no [`Reservation`] is held
+    /// the balance is zero. This method is meant for tests and only
+    /// available with the `testing` feature enabled.
+    #[cfg(feature = "testing")]
+    pub fn debug_permit_balance(&self) -> isize {
+        self.permit_ledger.balance()
+    }
+    /// Get the number of items in the queue and the number of futures
+    /// waiting for an item as `(len, waiters)`.
+    ///
+    /// This is a more explicit form of `available` which always equals
+    /// `len - waiters`. As the queue can be modified concurrently the
+    /// returned values are only a snapshot.
+    pub fn occupancy(&self) -> (usize, usize) {
+        let len = self.len();
+        let waiters = (len as isize - self.available()).max(0) as usize;
+        (len, waiters)
+    }
+    /// Get the number of items which can be popped right now without
+    /// waiting. This is `available()` saturated at zero.
+    pub fn items_ready(&self) -> usize {
+        self.available().max(0) as usize
+    }
+    /// Get the number of futures currently waiting for an item. This is
+    /// the negated `available()` saturated at zero.
+    pub fn pending_pops(&self) -> usize {
+        self.available().min(0).unsigned_abs()
+    }
+    /// Get a snapshot of the state of the queue.
+    pub fn stats_snapshot(&self) -> QueueStatsSnapshot {
+        QueueStatsSnapshot {
+            len: self.len(),
+            capacity: Some(self.capacity()),
+            available: self.available(),
+            closed: self.is_closed(),
+        }
+    }
+    /// Wait until `predicate` returns `true` for the state of the queue.
+    /// The predicate is checked right away and again after every push,
+    /// pop, resize or close of the queue.
+    ///
+    /// **Note:** The predicate is checked when the waiting task is polled
+    /// and not at the time of the change. States which only exist for a
+    /// short moment can therefore be missed.
+    pub async fn wait_until<F: Fn(&QueueStatsSnapshot) -> bool>(&self, predicate: F) {
+        self.queue
+            .wait_changed_until(|| predicate(&self.stats_snapshot()))
+            .await
     }
-    /// Check if the queue is full and notify any waiters
     fn notify_full(&self) {
-        self.notifier_full.send_replace(());
+        crate::notify(&self.notifier_full);
     }
     /// Await until the queue is full.
     pub async fn wait_full(&self) {
-        if self.len() == self.capacity() {
-            return;
-        }
-        self.subscribe_full().changed().await.unwrap();
+        self.wait_until(|stats| Some(stats.len) == stats.capacity)
+            .await
     }
-    /// Get a `Receiver` object that can repeatedly be awaited for
+    /// Get a [`QueueEvent`] receiver that can repeatedly be awaited for
     /// queue-full notifications.
-    pub fn subscribe_full(&self) -> Receiver {
-        self.notifier_full.subscribe()
+    pub fn subscribe_full(&self) -> QueueEvent {
+        QueueEvent(self.notifier_full.subscribe())
     }
     /// Check if the queue is empty and notify any waiters
     fn notify_empty(&self) {
-        self.notifier_empty.send_replace(());
+        crate::notify(&self.notifier_empty);
+    }
+    /// Await until the queue contains at least `n` items. This can be
+    /// used by consumers to wait for a batch of items.
+    pub async fn wait_len(&self, n: usize) {
+        self.wait_until(|stats| stats.len >= n).await
+    }
+    /// Await until the queue contains at least one item.
+    pub async fn wait_not_empty(&self) {
+        self.wait_len(1).await
     }
     /// Await until the queue is empty.
     pub async fn wait_empty(&self) {
-        if self.is_empty() {
-            return;
-        }
-        self.subscribe_empty().changed().await.unwrap();
+        self.wait_until(|stats| stats.len == 0).await
     }
-    /// Get a `Receiver` object that can repeatedly be awaited for
+    /// Get a [`QueueEvent`] receiver that can repeatedly be awaited for
     /// queue-empty notifications.
-    pub fn subscribe_empty(&self) -> Receiver {
-        self.notifier_empty.subscribe()
+    pub fn subscribe_empty(&self) -> QueueEvent {
+        QueueEvent(self.notifier_empty.subscribe())
+    }
+    /// Return permits to the `push_semaphore` and notify any waiters
+    fn release_push_permits(&self, n: usize) {
+        self.push_semaphore.add_permits(n);
+        self.permit_ledger.added(n);
+        self.permit_ledger.check();
+        self.notify_not_full();
+        self.queue.notify_changed();
+    }
+    /// Notify any callers awaiting room in the queue
+    fn notify_not_full(&self) {
+        crate::notify(&self.notifier_not_full);
+    }
+    /// Await until there is room for at least one more item in the queue.
+    ///
+    /// **Note:** The room is not reserved. A concurrent push can use it up
+    /// before the caller gets to push an item. Use `try_push` afterwards and
+    /// wait again if that fails or use `reserve` instead.
+    pub async fn wait_not_full(&self) {
+        self.wait_until(|_| self.push_semaphore.available_permits() > 0)
+            .await
+    }
+    /// Get a [`QueueEvent`] receiver that can repeatedly be awaited for
+    /// notifications about room becoming available in the queue.
+    pub fn subscribe_not_full(&self) -> QueueEvent {
+        QueueEvent(self.notifier_not_full.subscribe())
+    }
+    /// Turn the queue into a [`MappedQueue`] which applies `f` to every
+    /// item that is popped. Items are still pushed as `T`.
+    pub fn map<U, F: Fn(T) -> U>(self, f: F) -> MappedQueue<T, U, F> {
+        MappedQueue {
+            queue: self,
+            f,
+            _marker: PhantomData,
+        }
+    }
+    /// Close the queue. Once closed no new items are accepted: `try_push`
+    /// returns the item as `Err<T>` and `push` drops it. Items which
+    /// are already in the queue can still be popped.
+    ///
+    /// Tasks waiting in `pop_or_closed` are woken and get `None` once the
+    /// queue is empty. Tasks waiting in `pop` are **not** woken and keep
+    /// waiting for an item.
+    pub fn close(&self) {
+        self.closed.store(true, Ordering::Relaxed);
+        crate::notify(&self.notifier_closed);
+        self.queue.notify_changed();
+    }
+    /// Returns `true` if the queue has been closed.
+    pub fn is_closed(&self) -> bool {
+        self.closed.load(Ordering::Relaxed)
+    }
+    /// Wait until the queue is closed. Resolves immediately if the queue
+    /// has already been closed.
+    pub async fn closed(&self) {
+        self.wait_until(|stats| stats.closed).await
+    }
+    /// Get an item from the queue. If the queue is currently empty this
+    /// method blocks until an item is available or the queue is closed.
+    /// Returns `None` once the queue is closed and empty.
+    ///
+    /// Calling `close` wakes all tasks waiting in this method right away.
+    /// The items which are still in the queue are returned first so no
+    /// item is lost. Waiting tasks don't hold on to any item once woken
+    /// so `available()` is restored accordingly.
+    pub async fn pop_or_closed(&self) -> Option<T> {
+        let mut closed = self.notifier_closed.subscribe();
+        loop {
+            if let Some(item) = self.try_pop() {
+                return Some(item);
+            }
+            if self.is_closed() {
+                return None;
+            }
+            tokio::select! {
+                item = self.pop() => return Some(item),
+                _ = closed.changed() => {}
+            }
+        }
+    }
+    /// Pop items from the queue and call `f` for each of them, running at
+    /// most `concurrency` of the returned futures at the same time. This
+    /// method returns once the queue is closed and all items have been
+    /// processed.
+    ///
+    /// The futures are polled by the task calling this method. Spawn a
+    /// task inside of `f` if the work needs to run in parallel.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `concurrency` is zero.
+    pub async fn for_each_concurrent<F, Fut>(&self, concurrency: usize, f: F)
+    where
+        F: Fn(T) -> Fut,
+        Fut: Future<Output = ()>,
+    {
+        crate::consumer::for_each_concurrent(|| self.pop_or_closed(), concurrency, f).await
+    }
+    /// Close the queue and wait up to `drain_timeout` for the consumers
+    /// to drain it. If the queue could not be drained in time the number
+    /// of remaining items is returned as `Err<usize>`.
+    ///
+    /// **Note:** This method requires a `tokio` runtime with the time
+    /// driver enabled.
+    pub async fn shutdown(&self, drain_timeout: Duration) -> Result<(), usize> {
+        self.close();
+        match tokio::time::timeout(drain_timeout, self.wait_empty()).await {
+            Ok(()) => Ok(()),
+            Err(_) => Err(self.len()),
+        }
     }
     /// Resize queue. This increases or decreases the queue
     /// capacity accordingly.
     ///
     /// **Note:** Increasing the capacity of a queue happens without
     /// blocking unless a resize operation is already in progress.
-    /// Decreasing the capacity can block if there are futures waiting to
-    /// push items to the queue.
+    /// Shrinking never drops items which are already queued. The free
+    /// slots are removed first so no new items can be pushed into them.
+    /// If the queue contains more items than the new capacity this method
+    /// blocks until consumers have popped the excess items. This is
+    /// `resize_with(target_capacity, ShrinkPolicy::Block)`. Use
+    /// `resize_returning` to remove the oldest items instead of waiting.
+    ///
+    /// Resizing to zero pauses the producers: `try_push` fails and `push`
+    /// waits until the capacity is increased again. Futures waiting in
+    /// `pop` are not affected and stay parked until items arrive.
     pub async fn resize(&self, target_capacity: usize) {
+        self.resize_with(target_capacity, ShrinkPolicy::Block).await;
+    }
+    /// Resize queue like `resize` but remove the oldest items instead of
+    /// waiting for consumers if the queue contains more items than the
+    /// new capacity. The removed items are returned, oldest first. This
+    /// is `resize_with(target_capacity, ShrinkPolicy::DropOldest)`.
+    pub async fn resize_returning(&self, target_capacity: usize) -> Vec<T> {
+        self.resize_with(target_capacity, ShrinkPolicy::DropOldest)
+            .await
+    }
+    /// Resize queue using the given policy when decreasing the capacity.
+    /// The items removed from the queue are returned.
+    ///
+    /// Free slots of the queue are always used up first. The policy
+    /// decides what happens if that is not enough to reach the target
+    /// capacity. Increasing the capacity works exactly like `resize`.
+    pub async fn resize_with(&self, target_capacity: usize, policy: ShrinkPolicy) -> Vec<T> {
         let _guard = self.resize_mutex.lock().await;
-        match target_capacity.cmp(&self.capacity()) {
+        let (dropped, _) = self
+            .resize_locked(target_capacity, policy, std::future::pending())
+            .await;
+        dropped
+    }
+    /// Resize queue to the target capacity like
+    /// `resize_with(target_capacity, ShrinkPolicy::Block)` but stop
+    /// shrinking the queue once `cancel` is cancelled.
+    ///
+    /// A cancelled resize leaves the queue at the capacity reached so
+    /// far which is reported by the returned [`ResizeResult`]. The queue
+    /// is consistent at every step of a shrink so it can be used as if it
+    /// had been resized to that capacity.
+    ///
+    /// Enabled via the `cancellation` feature in your `Cargo.toml`.
+    #[cfg(feature = "cancellation")]
+    pub async fn resize_cancellable(
+        &self,
+        target_capacity: usize,
+        cancel: CancellationToken,
+    ) -> ResizeResult {
+        let _guard = tokio::select! {
+            biased;
+            guard = self.resize_mutex.lock() => guard,
+            _ = cancel.cancelled() => {
+                return ResizeResult {
+                    capacity: self.capacity(),
+                    cancelled: true,
+                };
+            }
+        };
+        let (_, cancelled) = self
+            .resize_locked(target_capacity, ShrinkPolicy::Block, cancel.cancelled())
+            .await;
+        ResizeResult {
+            capacity: self.capacity(),
+            cancelled,
+        }
+    }
+    /// Resize the queue until the target capacity is reached or `cancel`
+    /// resolves. Returns the dropped items and whether the resize was
+    /// cancelled. The caller must hold the `resize_mutex`.
+    async fn resize_locked(
+        &self,
+        target_capacity: usize,
+        policy: ShrinkPolicy,
+        cancel: impl Future<Output = ()>,
+    ) -> (Vec<T>, bool) {
+        tokio::pin!(cancel);
+        let mut cancelled = false;
+        let mut dropped = Vec::new();
+        let capacity = self.capacity();
+        match target_capacity.cmp(&capacity) {
             std::cmp::Ordering::Greater => {
-                let diff = target_capacity - self.capacity();
-                self.capacity.fetch_add(diff, Ordering::Relaxed);
-                self.push_semaphore.add_permits(diff);
+                self.grow_locked(target_capacity - capacity);
             }
             std::cmp::Ordering::Less => {
+                let mut excess = capacity - target_capacity;
+                // Free slots are removed in one go. If the queue holds no
+                // more items than the target capacity this is all that is
+                // needed and the loop below is skipped entirely.
+                excess -= self.shrink_free_slots(excess);
+                if policy == ShrinkPolicy::DropNewest {
+                    // The newest items are removed from the back of the
+                    // queue without going through `pop` and `push` so the
+                    // remaining items keep their place.
+                    let newest = self.queue.take_newest(excess);
+                    for _ in &newest {
+                        self.available.sub().commit();
+                    }
+                    self.capacity.fetch_sub(newest.len(), Ordering::Relaxed);
+                    excess -= newest.len();
+                    dropped = newest;
+                }
                 // Shrinking the queue is a bit more involved
                 // as there are two cases that need to be covered.
-                for _ in target_capacity..self.capacity() {
+                for _ in 0..excess {
                     tokio::select! {
                         biased;
                         // If there are push permits available consume
@@ -187,43 +1046,511 @@ impl<T> Queue<T> {
                         // `self.pop` as the former would add permits to
                         // the `push_semaphore` which we don't want to
                         // happen since the queue is being shrunk.
-                        _ = self.queue.pop() => {}
+                        item = self.queue.pop(), if policy != ShrinkPolicy::Block => {
+                            self.available.sub().commit();
+                            dropped.push(item);
+                        }
+                        // The capacity is lowered one slot at a time so
+                        // the queue stays consistent when cancelled.
+                        _ = &mut cancel => {
+                            cancelled = true;
+                            break;
+                        }
                     };
                     self.capacity.fetch_sub(1, Ordering::Relaxed);
                 }
 
+                self.capacity_changed();
+                if !dropped.is_empty() && self.queue.is_empty() {
+                    self.notify_empty();
+                }
                 if self.is_full() {
                     self.notify_full();
                 }
             }
             _ => {}
         }
+        (dropped, cancelled)
+    }
+    /// Increase the capacity of the queue by `additional` without
+    /// blocking.
+    ///
+    /// Returns `Err(ResizeWouldBlock)` if another resize operation is
+    /// currently in progress.
+    pub fn grow(&self, additional: usize) -> Result<(), ResizeWouldBlock> {
+        let _guard = self.resize_mutex.try_lock().map_err(|_| ResizeWouldBlock)?;
+        self.grow_locked(additional);
+        Ok(())
+    }
+    /// Set the capacity of the queue without blocking.
+    ///
+    /// Increasing the capacity always succeeds. Decreasing the capacity
+    /// only succeeds if the queue has enough free slots which are not
+    /// reserved by a pending push. Returns `Err(ResizeWouldBlock)` if the
+    /// capacity can't be changed without blocking or if another resize
+    /// operation is currently in progress. The capacity is left untouched
+    /// in that case.
+    pub fn try_set_capacity(&self, target_capacity: usize) -> Result<(), ResizeWouldBlock> {
+        let _guard = self.resize_mutex.try_lock().map_err(|_| ResizeWouldBlock)?;
+        let capacity = self.capacity();
+        match target_capacity.cmp(&capacity) {
+            std::cmp::Ordering::Greater => {
+                self.grow_locked(target_capacity - capacity);
+            }
+            std::cmp::Ordering::Less => {
+                let diff = (capacity - target_capacity)
+                    .try_into()
+                    .map_err(|_| ResizeWouldBlock)?;
+                self.push_semaphore
+                    .try_acquire_many(diff)
+                    .map_err(|_| ResizeWouldBlock)?
+                    .forget();
+                self.capacity.fetch_sub(diff as usize, Ordering::Relaxed);
+                self.capacity_changed();
+                if self.is_full() {
+                    self.notify_full();
+                }
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+    /// Change the capacity of the queue as far as possible without
+    /// blocking and return the capacity reached.
+    ///
+    /// Increasing the capacity always succeeds. When decreasing the
+    /// capacity only free slots of the queue are removed. Items are never
+    /// dropped so the capacity never goes below the number of items in the
+    /// queue. Returns `Err(ResizeWouldBlock)` if another resize operation
+    /// is currently in progress.
+    pub fn try_resize(&self, target_capacity: usize) -> Result<usize, ResizeWouldBlock> {
+        let _guard = self.resize_mutex.try_lock().map_err(|_| ResizeWouldBlock)?;
+        let capacity = self.capacity();
+        match target_capacity.cmp(&capacity) {
+            std::cmp::Ordering::Greater => {
+                self.grow_locked(target_capacity - capacity);
+            }
+            std::cmp::Ordering::Less => {
+                self.shrink_free_slots(capacity - target_capacity);
+                self.capacity_changed();
+                if self.is_full() {
+                    self.notify_full();
+                }
+            }
+            _ => {}
+        }
+        Ok(self.capacity())
+    }
+    /// Decrease the capacity by up to `n` free slots without blocking and
+    /// return the number of slots removed. The caller must hold the
+    /// `resize_mutex`.
+    fn shrink_free_slots(&self, n: usize) -> usize {
+        let batch: u32 = n
+            .min(self.push_semaphore.available_permits())
+            .try_into()
+            .unwrap_or(u32::MAX);
+        let mut removed = 0;
+        if let Ok(permits) = self.push_semaphore.try_acquire_many(batch) {
+            permits.forget();
+            self.capacity.fetch_sub(batch as usize, Ordering::Relaxed);
+            removed = batch as usize;
+        }
+        // Permits taken by other tasks meanwhile make the batch fail.
+        // Fall back to removing the remaining slots one by one.
+        while removed < n {
+            match self.push_semaphore.try_acquire() {
+                Ok(permit) => permit.forget(),
+                Err(_) => break,
+            }
+            self.capacity.fetch_sub(1, Ordering::Relaxed);
+            removed += 1;
+        }
+        removed
+    }
+    /// Increase the capacity. The caller must hold the `resize_mutex`.
+    fn grow_locked(&self, additional: usize) {
+        self.capacity.fetch_add(additional, Ordering::Relaxed);
+        // The new permits come with the new capacity and are not recorded
+        // in the permit ledger.
+        self.push_semaphore.add_permits(additional);
+        self.notify_not_full();
+        self.capacity_changed();
+    }
+}
+
+#[cfg(feature = "tokio-mpsc")]
+impl<T: Send + 'static> Queue<T> {
+    /// Spawn a task which receives items from `rx` and pushes them into
+    /// the queue waiting for the queue to have room for every item. The
+    /// task finishes once all senders of the channel have been dropped and
+    /// the channel is drained.
+    pub fn forward_from(self: Arc<Self>, mut rx: mpsc::Receiver<T>) -> JoinHandle<()> {
+        tokio::spawn(async move {
+            while let Some(item) = rx.recv().await {
+                self.push(item).await;
+            }
+        })
+    }
+    /// Spawn a task which pops items from the queue and sends them into
+    /// `tx` waiting for the channel to have room for every item. The task
+    /// finishes once the queue is closed and drained or the receiver of
+    /// the channel has been dropped. In the latter case the item which
+    /// could not be sent is dropped.
+    pub fn forward_to(self: Arc<Self>, tx: mpsc::Sender<T>) -> JoinHandle<()> {
+        tokio::spawn(async move {
+            while let Some(item) = self.pop_or_closed().await {
+                if tx.send(item).await.is_err() {
+                    break;
+                }
+            }
+        })
+    }
+}
+
+#[cfg(feature = "std-mpsc")]
+impl<T: Send + 'static> Queue<T> {
+    /// Spawn a blocking task which receives items from the standard
+    /// library channel `rx` and pushes them into the queue blocking the
+    /// thread while the queue is full. The task finishes once all senders
+    /// of the channel have been dropped and the channel is drained.
+    ///
+    /// The task runs on the blocking thread pool of the runtime via
+    /// `tokio::task::spawn_blocking`.
+    pub fn pump_from_std(self: Arc<Self>, rx: std::sync::mpsc::Receiver<T>) -> JoinHandle<()> {
+        tokio::task::spawn_blocking(move || {
+            for item in rx {
+                self.push_blocking(item);
+            }
+        })
+    }
+}
+
+#[cfg(feature = "broadcast")]
+impl<T: Clone + Send + 'static> Queue<T> {
+    /// Turn the queue into a [`Broadcast`] adapter which delivers every
+    /// popped item to all subscribers. `capacity` is the number of items
+    /// a subscriber can fall behind before it starts skipping items.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `capacity` is zero or larger than `usize::MAX / 2`.
+    pub fn broadcast(self: Arc<Self>, capacity: usize) -> Broadcast<T> {
+        Broadcast::spawn(capacity, |sender| async move {
+            while let Some(item) = self.pop_or_closed().await {
+                // Sending only fails if there are no subscribers in
+                // which case the item is dropped.
+                let _ = sender.send(item);
+            }
+        })
+    }
+}
+
+#[cfg(feature = "pool")]
+impl<T: Send + 'static> Queue<T> {
+    /// Spawn a [`WorkerPool`] of `workers` tasks which pop items from the
+    /// queue and call `f` for each of them. Every worker awaits the
+    /// future returned by `f` before popping the next item.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `workers` is zero.
+    pub fn worker_pool<F, Fut>(self: Arc<Self>, workers: usize, f: F) -> WorkerPool<Self>
+    where
+        F: Fn(T) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        let f = Arc::new(f);
+        WorkerPool::spawn(self, workers, Self::close, |queue| {
+            let f = f.clone();
+            async move {
+                while let Some(item) = queue.pop_or_closed().await {
+                    f(item).await;
+                }
+            }
+        })
+    }
+}
+
+/// Policy deciding how [`Queue::resize_with`] makes room when the queue
+/// contains more items than the new capacity.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum ShrinkPolicy {
+    /// Never drop any items. Wait for items to be popped from the queue
+    /// until the new capacity is reached.
+    ///
+    /// Shrinking with this policy first removes the free slots of the
+    /// queue so no new items can be pushed into them. If the queue holds
+    /// no more items than the new capacity the resize returns right away.
+    /// Otherwise it waits until consumers have popped the excess items.
+    /// A slot freed by a pop goes to the waiting resize and not to a new
+    /// push. Slots held by a [`Reservation`] or promised to a `push`
+    /// which was already waiting count as occupied. This is what `resize`
+    /// does.
+    Block,
+    /// Drop the oldest items of the queue. This is what
+    /// `resize_returning` does.
+    DropOldest,
+    /// Drop the newest items of the queue. The queue is locked while the
+    /// items are removed so concurrent calls to `push` and `pop` have to
+    /// wait for it.
+    DropNewest,
+}
+
+/// Result of [`Queue::resize_cancellable`]
+#[cfg(feature = "cancellation")]
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct ResizeResult {
+    /// Capacity of the queue after the resize
+    pub capacity: usize,
+    /// `true` if the resize was cancelled before the target capacity
+    /// was reached
+    pub cancelled: bool,
+}
+
+/// Error returned by [`Queue::grow`] and [`Queue::try_set_capacity`] if
+/// the capacity can't be changed without blocking.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct ResizeWouldBlock;
+
+impl std::fmt::Display for ResizeWouldBlock {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("resize would block")
+    }
+}
+
+impl std::error::Error for ResizeWouldBlock {}
+
+/// Room reserved in a queue for pushing items without blocking.
+///
+/// This object is created by the [`Queue::reserve`] method. Room which has
+/// not been used when it is dropped is released again.
+pub struct Reservation<'a, T> {
+    queue: &'a Queue<T>,
+    remaining: usize,
+}
+
+impl<'a, T> Reservation<'a, T> {
+    /// Push an item into the queue using up one of the reserved slots.
+    /// If all reserved slots have been used up or the queue is closed the
+    /// item is returned as `Err<T>`.
+    pub fn push(&mut self, item: T) -> Result<(), T> {
+        if self.remaining == 0 || self.queue.is_closed() {
+            return Err(item);
+        }
+        self.remaining -= 1;
+        self.queue.push_permitted(item);
+        Ok(())
+    }
+    /// Get the number of reserved slots which have not been used, yet.
+    pub fn remaining(&self) -> usize {
+        self.remaining
+    }
+}
+
+impl<'a, T> Drop for Reservation<'a, T> {
+    fn drop(&mut self) {
+        // The permits were forgotten by `reserve`. Every push consumed
+        // one of them so exactly the unused ones need to be returned.
+        if self.remaining > 0 {
+            self.queue.release_push_permits(self.remaining);
+        }
+    }
+}
+
+/// View of a queue which transforms the items when popping them.
+///
+/// This object is created by the [`Queue::map`] method. Items are pushed
+/// as `T` and returned as `U` by the pop methods.
+pub struct MappedQueue<T, U, F> {
+    queue: Queue<T>,
+    f: F,
+    _marker: PhantomData<fn() -> U>,
+}
+
+impl<T, U, F: Fn(T) -> U> MappedQueue<T, U, F> {
+    /// Get an item from the queue and transform it. If the queue is
+    /// currently empty this method blocks until an item is available.
+    pub async fn pop(&self) -> U {
+        (self.f)(self.queue.pop().await)
+    }
+    /// Try to get an item from the queue and transform it. If the queue
+    /// is currently empty return None instead.
+    pub fn try_pop(&self) -> Option<U> {
+        self.queue.try_pop().map(&self.f)
+    }
+    /// Push an item into the queue
+    pub async fn push(&self, item: T) {
+        self.queue.push(item).await
+    }
+    /// Try to push an item into the queue. If the queue is full
+    /// or closed the item is returned as `Err<T>`.
+    pub fn try_push(&self, item: T) -> Result<(), T> {
+        self.queue.try_push(item)
+    }
+    /// Get current length of queue (number of items currently stored).
+    pub fn len(&self) -> usize {
+        self.queue.len()
+    }
+    /// Returns `true` if the queue is empty.
+    pub fn is_empty(&self) -> bool {
+        self.queue.is_empty()
+    }
+    /// Get a reference to the underlying queue.
+    pub fn get_ref(&self) -> &Queue<T> {
+        &self.queue
+    }
+    /// Consume the view returning the underlying queue.
+    pub fn into_inner(self) -> Queue<T> {
+        self.queue
+    }
+}
+
+impl<T, U, F> Debug for MappedQueue<T, U, F> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("MappedQueue")
+            .field("queue", &self.queue)
+            .finish()
+    }
+}
+
+/// Iterator which pops items from a [`Queue`] until it is empty.
+///
+/// This object is created by the [`Queue::drain_iter`] method.
+pub struct DrainIter<'a, T> {
+    queue: &'a Queue<T>,
+}
+
+impl<'a, T> Iterator for DrainIter<'a, T> {
+    type Item = T;
+    fn next(&mut self) -> Option<T> {
+        self.queue.try_pop()
+    }
+}
+
+impl<'a, T> Debug for DrainIter<'a, T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("DrainIter")
+            .field("queue", &self.queue)
+            .finish()
     }
 }
 
 impl<T> Debug for Queue<T> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         f.debug_struct("Queue")
-            .field("queue", &self.queue)
-            .field("capacity", &self.capacity)
-            .field("push_semaphore", &self.push_semaphore)
-            .field("resize_mutex", &self.resize_mutex)
+            .field("name", &self.name())
+            .field("len", &self.len())
+            .field("capacity", &self.capacity())
+            .field("available", &self.available())
+            .field("closed", &self.is_closed())
+            .field("total_pushed", &self.total_pushed())
+            .field("total_popped", &self.total_popped())
             .finish()
     }
 }
 
+impl<T> Queue<Box<T>> {
+    /// Push a boxed item into the queue. Only the pointer is moved into
+    /// the queue which avoids copying large items around. Use this with a
+    /// [`BoxQueue`] to keep a single heap allocation per item from the
+    /// producer all the way to the consumer.
+    pub async fn push_boxed(&self, item: Box<T>) {
+        self.push(item).await
+    }
+    /// Get an item from the queue and move it out of its box. If the
+    /// queue is currently empty this method blocks until an item is
+    /// available.
+    pub async fn pop_unboxed(&self) -> T {
+        *self.pop().await
+    }
+    /// Try to get an item from the queue and move it out of its box. If
+    /// the queue is currently empty return None instead.
+    pub fn try_pop_unboxed(&self) -> Option<T> {
+        self.try_pop().map(|item| *item)
+    }
+}
+
+#[cfg(feature = "tracing-context")]
+impl<T> Queue<Spanned<T>> {
+    /// Push an item into the queue together with the current span. See
+    /// `push` for the behavior when the queue is full or closed.
+    pub async fn push_spanned(&self, item: T) {
+        self.push(Spanned::new(item)).await
+    }
+    /// Try to push an item into the queue together with the current span.
+    /// If the queue is full or closed the item is returned as `Err<T>`.
+    pub fn try_push_spanned(&self, item: T) -> Result<(), T> {
+        self.try_push(Spanned::new(item))
+            .map_err(Spanned::into_inner)
+    }
+    /// Get an item from the queue together with the span that was current
+    /// when it was pushed. If the queue is currently empty this method
+    /// blocks until an item is available.
+    pub async fn pop_with_span(&self) -> (Span, T) {
+        self.pop().await.into_parts()
+    }
+    /// Try to get an item from the queue together with the span that was
+    /// current when it was pushed. If the queue is currently empty return
+    /// None instead.
+    pub fn try_pop_with_span(&self) -> Option<(Span, T)> {
+        self.try_pop().map(Spanned::into_parts)
+    }
+}
+
+impl<T> Default for Queue<T> {
+    /// Create new empty queue with a capacity of zero. Use `resize` to
+    /// make room for items.
+    fn default() -> Self {
+        Self::with_capacity(0)
+    }
+}
+
+impl<T> crate::Queue for Queue<T> {
+    type Item = T;
+    fn try_pop(&self) -> Option<T> {
+        self.try_pop()
+    }
+    fn try_push(&self, item: T) -> Result<(), T> {
+        self.try_push(item)
+    }
+    fn len(&self) -> usize {
+        self.len()
+    }
+    fn is_empty(&self) -> bool {
+        self.is_empty()
+    }
+    fn capacity(&self) -> Option<usize> {
+        Some(self.capacity())
+    }
+    fn available(&self) -> isize {
+        self.available()
+    }
+    fn close(&self) {
+        self.close()
+    }
+    fn is_closed(&self) -> bool {
+        self.is_closed()
+    }
+}
+
 impl<T> FromIterator<T> for Queue<T> {
     fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
         let queue = UnlimitedQueue::from_iter(iter);
         let len = queue.len();
         Self {
             queue,
-            capacity: len.try_into().unwrap(),
+            capacity: len.into(),
             push_semaphore: Semaphore::new(0),
-            available: Available::new(0),
+            available: Available::new(len.try_into().unwrap()),
             resize_mutex: Mutex::default(),
             notifier_full: crate::new_notifier(),
             notifier_empty: crate::new_notifier(),
+            notifier_not_full: crate::new_notifier(),
+            closed: AtomicBool::new(false),
+            notifier_closed: crate::new_notifier(),
+            capacity_sender: watch::channel(len).0,
+            metrics: None,
+            permit_ledger: PermitLedger::default(),
         }
     }
 }
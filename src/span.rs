@@ -0,0 +1,48 @@
+//! Propagation of the tracing span from `push` to `pop`
+
+use std::fmt::Debug;
+
+use tracing::Span;
+
+/// Item together with the [`Span`] that was current when it was created.
+///
+/// Queues of `Spanned` items provide `push_spanned` which captures the
+/// current span of the producer and `pop_with_span` which hands it to the
+/// consumer again. Entering the returned span or using it as the parent
+/// of new spans continues the trace across the queue.
+pub struct Spanned<T> {
+    span: Span,
+    item: T,
+}
+
+impl<T> Spanned<T> {
+    /// Wrap the item together with the current span.
+    pub fn new(item: T) -> Self {
+        Self::with_span(item, Span::current())
+    }
+    /// Wrap the item together with the given span.
+    pub fn with_span(item: T, span: Span) -> Self {
+        Self { span, item }
+    }
+    /// Get the span captured for the item.
+    pub fn span(&self) -> &Span {
+        &self.span
+    }
+    /// Get the item dropping the span.
+    pub fn into_inner(self) -> T {
+        self.item
+    }
+    /// Get the span and the item.
+    pub fn into_parts(self) -> (Span, T) {
+        (self.span, self.item)
+    }
+}
+
+impl<T: Debug> Debug for Spanned<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Spanned")
+            .field("span", &self.span)
+            .field("item", &self.item)
+            .finish()
+    }
+}
@@ -0,0 +1,61 @@
+//! Accounting of the timing of `pop`
+
+use std::convert::TryFrom;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
+use crate::WaitStats;
+
+/// Lock-free accumulator of wait times
+#[derive(Debug, Default)]
+pub struct WaitRecorder {
+    count: AtomicU64,
+    total_nanos: AtomicU64,
+    max_nanos: AtomicU64,
+}
+
+impl WaitRecorder {
+    pub fn record(&self, wait: Duration) {
+        let nanos = u64::try_from(wait.as_nanos()).unwrap_or(u64::MAX);
+        self.count.fetch_add(1, Ordering::Relaxed);
+        self.total_nanos.fetch_add(nanos, Ordering::Relaxed);
+        self.max_nanos.fetch_max(nanos, Ordering::Relaxed);
+    }
+    pub fn stats(&self) -> WaitStats {
+        WaitStats {
+            count: self.count.load(Ordering::Relaxed),
+            total: Duration::from_nanos(self.total_nanos.load(Ordering::Relaxed)),
+            max: Duration::from_nanos(self.max_nanos.load(Ordering::Relaxed)),
+        }
+    }
+}
+
+/// Time since the last pop
+///
+/// The instant of the last pop is stored as the nanoseconds since the
+/// creation of the clock so it can be updated without a lock.
+#[derive(Debug)]
+pub struct PopClock {
+    created: Instant,
+    last_pop_nanos: AtomicU64,
+}
+
+impl PopClock {
+    pub fn record(&self) {
+        let nanos = u64::try_from(self.created.elapsed().as_nanos()).unwrap_or(u64::MAX);
+        self.last_pop_nanos.fetch_max(nanos, Ordering::Relaxed);
+    }
+    pub fn idle_duration(&self) -> Duration {
+        let last_pop = Duration::from_nanos(self.last_pop_nanos.load(Ordering::Relaxed));
+        self.created.elapsed().saturating_sub(last_pop)
+    }
+}
+
+impl Default for PopClock {
+    fn default() -> Self {
+        Self {
+            created: Instant::now(),
+            last_pop_nanos: AtomicU64::new(0),
+        }
+    }
+}
@@ -0,0 +1,83 @@
+//! Export of queue statistics to the `metrics` crate
+
+#[cfg(feature = "metrics")]
+use metrics::{Counter, Gauge};
+
+/// Metric handles of a queue created via `new_named`. The exported
+/// metrics are listed in the crate documentation.
+///
+/// The handles are registered once when the queue is created so updating
+/// them is cheap. Without the `metrics` feature this type is empty and
+/// all methods are no-ops.
+// Without the `metrics` feature the type is never constructed. The
+// `classed` and `ttl` queues only ever pass `None` for it.
+#[cfg_attr(
+    not(all(feature = "metrics", any(feature = "unlimited", feature = "limited"))),
+    allow(dead_code)
+)]
+#[derive(Clone)]
+pub struct QueueMetrics {
+    #[cfg(feature = "metrics")]
+    len: Gauge,
+    #[cfg(feature = "metrics")]
+    // The unlimited queue has no capacity.
+    #[cfg_attr(not(any(feature = "limited", feature = "resizable")), allow(dead_code))]
+    capacity: Gauge,
+    #[cfg(feature = "metrics")]
+    pushes: Counter,
+    #[cfg(feature = "metrics")]
+    pops: Counter,
+    #[cfg(feature = "metrics")]
+    blocked_push: Counter,
+    #[cfg(feature = "metrics")]
+    blocked_pop: Counter,
+}
+
+#[cfg(feature = "metrics")]
+#[cfg_attr(not(any(feature = "unlimited", feature = "limited")), allow(dead_code))]
+impl QueueMetrics {
+    pub fn new(name: &str) -> Self {
+        let name = name.to_owned();
+        Self {
+            len: metrics::gauge!("deadqueue_len", "queue" => name.clone()),
+            capacity: metrics::gauge!("deadqueue_capacity", "queue" => name.clone()),
+            pushes: metrics::counter!("deadqueue_pushes_total", "queue" => name.clone()),
+            pops: metrics::counter!("deadqueue_pops_total", "queue" => name.clone()),
+            blocked_push: metrics::counter!(
+                "deadqueue_blocked_total", "queue" => name.clone(), "op" => "push"
+            ),
+            blocked_pop: metrics::counter!(
+                "deadqueue_blocked_total", "queue" => name, "op" => "pop"
+            ),
+        }
+    }
+    pub fn pushed(&self, len: usize) {
+        self.pushes.increment(1);
+        self.len.set(len as f64);
+    }
+    pub fn popped(&self, len: usize) {
+        self.pops.increment(1);
+        self.len.set(len as f64);
+    }
+    pub fn blocked(&self, op: &str) {
+        match op {
+            "push" => self.blocked_push.increment(1),
+            _ => self.blocked_pop.increment(1),
+        }
+    }
+    #[cfg_attr(not(any(feature = "limited", feature = "resizable")), allow(dead_code))]
+    pub fn set_capacity(&self, capacity: usize) {
+        self.capacity.set(capacity as f64);
+    }
+}
+
+#[cfg(not(feature = "metrics"))]
+impl QueueMetrics {
+    #[cfg_attr(not(any(feature = "unlimited", feature = "limited")), allow(dead_code))]
+    pub fn pushed(&self, _len: usize) {}
+    #[cfg_attr(not(any(feature = "unlimited", feature = "limited")), allow(dead_code))]
+    pub fn popped(&self, _len: usize) {}
+    pub fn blocked(&self, _op: &str) {}
+    #[cfg_attr(not(feature = "resizable"), allow(dead_code))]
+    pub fn set_capacity(&self, _capacity: usize) {}
+}
@@ -0,0 +1,58 @@
+//! Instrumentation of the await points of the queues
+
+use std::future::{poll_fn, Future};
+use std::pin::pin;
+use std::task::Poll;
+
+use tokio::sync::{Semaphore, SemaphorePermit};
+
+use crate::telemetry::QueueMetrics;
+
+/// Acquire a permit from the given semaphore.
+///
+/// If no permit is immediately available or the task has to yield to the
+/// runtime the wait is counted in the given metrics. If the `tracing`
+/// feature is enabled a `deadqueue::wait` span is entered and events are
+/// emitted when the wait starts and when it resolves. The `op` and `len`
+/// fields record the operation (`"push"` or `"pop"`) and the queue length.
+pub async fn acquire<'a>(
+    semaphore: &'a Semaphore,
+    op: &'static str,
+    len: impl Fn() -> usize,
+    metrics: Option<&QueueMetrics>,
+) -> SemaphorePermit<'a> {
+    let mut acquire = pin!(semaphore.acquire());
+    // Fast path: poll the `Acquire` future once. Unlike `try_acquire` it
+    // takes part in the cooperative scheduling budget of tokio. Thus a
+    // task draining a full queue in a loop still yields to the runtime
+    // once its budget is used up instead of starving other tasks.
+    if let Poll::Ready(permit) = poll_fn(|cx| Poll::Ready(acquire.as_mut().poll(cx))).await {
+        return permit.unwrap();
+    }
+    if let Some(metrics) = metrics {
+        metrics.blocked(op);
+    }
+    #[cfg(feature = "tracing")]
+    {
+        use std::time::Instant;
+        use tracing::Instrument;
+
+        let span = tracing::debug_span!("deadqueue::wait", op);
+        tracing::debug!(parent: &span, op, len = len(), "blocked");
+        let start = Instant::now();
+        let permit = acquire.instrument(span.clone()).await.unwrap();
+        tracing::debug!(
+            parent: &span,
+            op,
+            len = len(),
+            wait_us = start.elapsed().as_micros() as u64,
+            "resolved"
+        );
+        permit
+    }
+    #[cfg(not(feature = "tracing"))]
+    {
+        let _ = len;
+        acquire.await.unwrap()
+    }
+}
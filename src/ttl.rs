@@ -0,0 +1,178 @@
+//! Queue dropping items which are older than a time to live
+
+use std::collections::VecDeque;
+use std::fmt::Debug;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use tokio::sync::Semaphore;
+use tokio::task::JoinHandle;
+use tokio::time::Instant;
+
+use crate::atomic::Available;
+
+/// Queue that is unlimited in size and drops items which have been in
+/// the queue for longer than a fixed time to live (TTL).
+///
+/// Every item is stored together with the instant it was pushed. `pop`
+/// and `try_pop` only ever return items which are not expired yet and
+/// drop the expired ones they come across. Expired items which are not
+/// popped still occupy memory and count towards `len`. Use
+/// [`Queue::spawn_sweeper`] to remove them periodically.
+///
+/// This queue implementation has the following characteristics:
+///
+///   - Based on a `VecDeque` protected by a `Mutex`
+///   - Has unlimited capacity and no back pressure on push
+///   - `Send` and `Sync` if `T: Send`
+///   - Requires a tokio runtime for the sweeper
+///   - Enabled via the `ttl` feature in your `Cargo.toml`
+pub struct Queue<T> {
+    items: Mutex<VecDeque<(Instant, T)>>,
+    ttl: Duration,
+    semaphore: Semaphore,
+    available: Available,
+}
+
+impl<T> Queue<T> {
+    /// Create new empty queue dropping items which are older than `ttl`.
+    pub fn new(ttl: Duration) -> Self {
+        Self {
+            items: Mutex::new(VecDeque::new()),
+            ttl,
+            semaphore: Semaphore::new(0),
+            available: Available::new(0),
+        }
+    }
+    /// Get the time to live of the items.
+    pub fn ttl(&self) -> Duration {
+        self.ttl
+    }
+    /// Get an item from the queue. If the queue is currently empty
+    /// this method blocks until an item is available. Expired items are
+    /// dropped and never returned.
+    pub async fn pop(&self) -> T {
+        loop {
+            let txn = self.available.sub();
+            let permit = crate::trace::acquire(&self.semaphore, "pop", || self.len(), None).await;
+            let (pushed, item) = match self.items.lock().unwrap().pop_front() {
+                Some(entry) => entry,
+                // The permit does not belong to any item which means the
+                // bookkeeping is out of sync. Discard the permit instead of
+                // restoring it as the next consumer would not find an item
+                // either and wait for the next one.
+                None => {
+                    permit.forget();
+                    continue;
+                }
+            };
+            txn.commit();
+            permit.forget();
+            if pushed.elapsed() < self.ttl {
+                return item;
+            }
+        }
+    }
+    /// Try to get an item from the queue. If the queue is currently
+    /// empty or only contains expired items return None instead.
+    pub fn try_pop(&self) -> Option<T> {
+        loop {
+            let txn = self.available.sub();
+            let permit = self.semaphore.try_acquire().ok()?;
+            let (pushed, item) = match self.items.lock().unwrap().pop_front() {
+                Some(entry) => entry,
+                // See `pop`
+                None => {
+                    permit.forget();
+                    return None;
+                }
+            };
+            txn.commit();
+            permit.forget();
+            if pushed.elapsed() < self.ttl {
+                return Some(item);
+            }
+        }
+    }
+    /// Push an item into the queue.
+    pub fn push(&self, item: T) {
+        self.items.lock().unwrap().push_back((Instant::now(), item));
+        self.semaphore.add_permits(1);
+        self.available.add();
+    }
+    /// Remove and drop the expired items from the front of the queue.
+    /// Returns the number of items removed.
+    pub fn purge_expired(&self) -> usize {
+        let mut purged = 0;
+        loop {
+            let txn = self.available.sub();
+            let permit = match self.semaphore.try_acquire() {
+                Ok(permit) => permit,
+                Err(_) => return purged,
+            };
+            let mut items = self.items.lock().unwrap();
+            match items.front() {
+                Some((pushed, _)) if pushed.elapsed() >= self.ttl => {
+                    let expired = items.pop_front();
+                    drop(items);
+                    drop(expired);
+                    txn.commit();
+                    permit.forget();
+                    purged += 1;
+                }
+                // The front item is still alive. Dropping the permit and
+                // the transaction gives the item back to the poppers.
+                _ => return purged,
+            }
+        }
+    }
+    /// Get current length of queue (number of items currently stored).
+    /// This includes expired items which have not been removed yet.
+    pub fn len(&self) -> usize {
+        self.items.lock().unwrap().len()
+    }
+    /// Returns `true` if the queue is empty.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+    /// The number of available items in the queue. If there are no
+    /// items in the queue this number can become negative and stores the
+    /// number of futures waiting for an item.
+    pub fn available(&self) -> isize {
+        self.available.get()
+    }
+}
+
+impl<T: Send + 'static> Queue<T> {
+    /// Spawn a task calling `purge_expired` every `interval`.
+    ///
+    /// The task only holds a weak reference to the queue and stops once
+    /// the queue is dropped. Aborting the returned handle stops it
+    /// earlier.
+    ///
+    /// **Note:** This method must be called from within a tokio runtime.
+    pub fn spawn_sweeper(self: Arc<Self>, interval: Duration) -> JoinHandle<()> {
+        let queue = Arc::downgrade(&self);
+        drop(self);
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(interval);
+            loop {
+                interval.tick().await;
+                match queue.upgrade() {
+                    Some(queue) => queue.purge_expired(),
+                    None => return,
+                };
+            }
+        })
+    }
+}
+
+impl<T> Debug for Queue<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Queue")
+            .field("ttl", &self.ttl)
+            .field("len", &self.len())
+            .field("available", &self.available())
+            .finish()
+    }
+}
@@ -1,14 +1,47 @@
 //! Unlimited queue implementation
 
+#[cfg(feature = "dedup")]
+use std::collections::HashSet;
 use std::convert::TryInto;
 use std::fmt::Debug;
+use std::future::Future;
+#[cfg(feature = "dedup")]
+use std::hash::Hash;
 use std::iter::FromIterator;
+use std::marker::PhantomData;
+use std::ops::{Deref, DerefMut};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+#[cfg(any(
+    feature = "tokio-mpsc",
+    feature = "std-mpsc",
+    feature = "broadcast",
+    feature = "pool"
+))]
+use std::sync::Arc;
+use std::sync::{Mutex, RwLock};
+use std::time::{Duration, Instant};
 
 use crossbeam_queue::SegQueue;
-use tokio::sync::Semaphore;
+#[cfg(feature = "tokio-mpsc")]
+use tokio::sync::mpsc;
+use tokio::sync::{watch, Semaphore};
+#[cfg(any(feature = "tokio-mpsc", feature = "std-mpsc"))]
+use tokio::task::JoinHandle;
+#[cfg(feature = "cancellation")]
+use tokio_util::sync::CancellationToken;
+#[cfg(feature = "tracing-context")]
+use tracing::Span;
 
 use crate::atomic::Available;
-use crate::{Notifier, Receiver};
+#[cfg(feature = "broadcast")]
+use crate::broadcast::Broadcast;
+#[cfg(feature = "pool")]
+use crate::pool::WorkerPool;
+#[cfg(feature = "tracing-context")]
+use crate::span::Spanned;
+use crate::stats::{PopClock, WaitRecorder};
+use crate::telemetry::QueueMetrics;
+use crate::{Notifier, QueueEvent, QueueStatsSnapshot, WaitStats};
 
 /// Queue that is unlimited in size.
 ///
@@ -16,51 +49,761 @@ use crate::{Notifier, Receiver};
 ///
 ///   - Based on `crossbeam_queue::SegQueue`
 ///   - Has unlimitied capacity and no back pressure on push
+///   - Supports FIFO and LIFO order (see [`Order`])
+///   - `Send` and `Sync` if `T: Send`
 ///   - Enabled via the `unlimited` feature in your `Cargo.toml`
 pub struct Queue<T> {
-    queue: SegQueue<T>,
+    queue: Storage<T>,
     semaphore: Semaphore,
     available: Available,
     notifier_empty: Notifier,
+    closed: AtomicBool,
+    notifier_closed: Notifier,
+    notifier_changed: Notifier,
+    len_sender: watch::Sender<usize>,
+    metrics: Option<QueueMetrics>,
+    wait_recorder: Option<WaitRecorder>,
+    pop_clock: PopClock,
+    name: Option<Box<str>>,
+    total_pushed: AtomicU64,
+    total_popped: AtomicU64,
+    warn_threshold: Option<WarnThreshold>,
+}
+
+/// Queue of boxed items. This is useful for queueing trait objects like
+/// jobs of different types:
+///
+/// ```
+/// use deadqueue::unlimited::BoxQueue;
+///
+/// let offset = 1;
+/// let queue: BoxQueue<dyn Fn() -> usize + Send> = BoxQueue::new();
+/// queue.push(Box::new(|| 1));
+/// queue.push(Box::new(move || offset + 1));
+/// assert_eq!(queue.try_pop().unwrap()(), 1);
+/// ```
+pub type BoxQueue<T> = Queue<Box<T>>;
+
+/// Queue of items which carry the tracing span that was current when they
+/// were pushed. Use `push_spanned` and `pop_with_span` to continue the
+/// trace of the producer in the consumer.
+///
+/// Enabled via the `tracing-context` feature in your `Cargo.toml`.
+#[cfg(feature = "tracing-context")]
+pub type SpanQueue<T> = Queue<Spanned<T>>;
+
+/// Order in which items are returned by the queue.
+///
+/// This only affects the order of the items. Tasks calling `pop` are
+/// always served in a first-come-first-serve fashion regardless of the
+/// order.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum Order {
+    /// First in, first out. `pop` returns the oldest item. (default)
+    #[default]
+    Fifo,
+    /// Last in, first out. `pop` returns the most recently pushed item.
+    Lifo,
+}
+
+/// Storage backing the queue. The FIFO variant is a lock-free `SegQueue`
+/// next to a lock which is only taken exclusively while all items are
+/// inspected. The LIFO variant is a mutex guarded stack.
+// The size difference is caused by the cache padding of `SegQueue` and
+// boxing it would add an indirection to the hot path.
+#[allow(clippy::large_enum_variant)]
+enum Storage<T> {
+    Fifo {
+        queue: SegQueue<T>,
+        lock: RwLock<()>,
+    },
+    Lifo(Mutex<Vec<T>>),
+}
+
+impl<T> Storage<T> {
+    fn new(order: Order) -> Self {
+        match order {
+            Order::Fifo => Self::Fifo {
+                queue: SegQueue::new(),
+                lock: RwLock::default(),
+            },
+            Order::Lifo => Self::Lifo(Mutex::default()),
+        }
+    }
+    fn push(&self, item: T) {
+        match self {
+            Self::Fifo { queue, lock } => {
+                let _guard = lock.read().unwrap();
+                queue.push(item)
+            }
+            Self::Lifo(stack) => stack.lock().unwrap().push(item),
+        }
+    }
+    fn pop(&self) -> Option<T> {
+        match self {
+            Self::Fifo { queue, lock } => {
+                let _guard = lock.read().unwrap();
+                queue.pop()
+            }
+            Self::Lifo(stack) => stack.lock().unwrap().pop(),
+        }
+    }
+    fn len(&self) -> usize {
+        match self {
+            Self::Fifo { queue, lock } => {
+                let _guard = lock.read().unwrap();
+                queue.len()
+            }
+            Self::Lifo(stack) => stack.lock().unwrap().len(),
+        }
+    }
+    fn is_empty(&self) -> bool {
+        match self {
+            Self::Fifo { queue, lock } => {
+                let _guard = lock.read().unwrap();
+                queue.is_empty()
+            }
+            Self::Lifo(stack) => stack.lock().unwrap().is_empty(),
+        }
+    }
+    fn allocated_capacity(&self) -> usize {
+        match self {
+            // `SegQueue` does not expose its allocation so the number of
+            // stored items is used as a proxy.
+            Self::Fifo { .. } => self.len(),
+            Self::Lifo(stack) => stack.lock().unwrap().capacity(),
+        }
+    }
+    /// Call `f` with all items in the order they were pushed. Pushes and
+    /// pops of other tasks wait until `f` returns.
+    fn with_pushed<R>(&self, f: impl FnOnce(&mut Vec<T>) -> R) -> R {
+        match self {
+            Self::Fifo { queue, lock } => {
+                let _guard = lock.write().unwrap();
+                let mut items = Vec::with_capacity(queue.len());
+                while let Some(item) = queue.pop() {
+                    items.push(item);
+                }
+                let result = f(&mut items);
+                for item in items {
+                    queue.push(item);
+                }
+                result
+            }
+            Self::Lifo(stack) => f(&mut stack.lock().unwrap()),
+        }
+    }
+    /// Call `f` with all items in the order they would be popped. Pushes
+    /// and pops of other tasks wait until `f` returns.
+    fn with_items<R>(&self, f: impl FnOnce(&mut Vec<T>) -> R) -> R {
+        match self {
+            Self::Fifo { .. } => self.with_pushed(f),
+            Self::Lifo(_) => self.with_pushed(|items| {
+                items.reverse();
+                let result = f(items);
+                items.reverse();
+                result
+            }),
+        }
+    }
+}
+
+impl<T> Debug for Storage<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Fifo { queue, .. } => queue.fmt(f),
+            Self::Lifo(_) => f.pad("Lifo { .. }"),
+        }
+    }
+}
+
+/// Observer which is notified when the length of the queue reaches the
+/// warn threshold. See [`Queue::with_warn_threshold`].
+///
+/// This trait is implemented for all `Fn(usize)` closures.
+pub trait WarnObserver: Send + Sync {
+    /// Called with the current length of the queue when it reaches
+    /// the warn threshold.
+    fn warn(&self, len: usize);
+}
+
+impl<F: Fn(usize) + Send + Sync> WarnObserver for F {
+    fn warn(&self, len: usize) {
+        self(len)
+    }
+}
+
+/// Soft limit of the queue length which only warns via an observer.
+struct WarnThreshold {
+    threshold: usize,
+    observer: Box<dyn WarnObserver>,
+    exceeded: AtomicBool,
+}
+
+impl WarnThreshold {
+    fn pushed(&self, len: usize) {
+        if len >= self.threshold && !self.exceeded.swap(true, Ordering::Relaxed) {
+            self.observer.warn(len);
+        }
+    }
+    fn popped(&self, len: usize) {
+        if len < self.threshold {
+            self.exceeded.store(false, Ordering::Relaxed);
+        }
+    }
 }
 
 impl<T> Queue<T> {
     /// Create new empty queue
+    ///
+    /// **Note:** This is not a `const fn` as the notification channels
+    /// of the queue can't be created in a const context. A queue can
+    /// still be stored in a `static` by initializing it lazily:
+    ///
+    /// ```
+    /// use std::sync::OnceLock;
+    ///
+    /// use deadqueue::unlimited::Queue;
+    ///
+    /// fn queue() -> &'static Queue<usize> {
+    ///     static QUEUE: OnceLock<Queue<usize>> = OnceLock::new();
+    ///     QUEUE.get_or_init(Queue::new)
+    /// }
+    ///
+    /// queue().push(42);
+    /// assert_eq!(queue().try_pop(), Some(42));
+    /// ```
     pub fn new() -> Self {
         Self::default()
     }
 
+    /// Create new empty queue exporting its statistics to the `metrics`
+    /// crate labeled with the given name. See the crate documentation for
+    /// the list of exported metrics.
+    #[cfg(feature = "metrics")]
+    pub fn new_named(name: &str) -> Self {
+        Self {
+            metrics: Some(QueueMetrics::new(name)),
+            name: Some(name.into()),
+            ..Self::default()
+        }
+    }
+
+    /// Get the metric handles of a queue created via `new_named`.
+    #[cfg(all(feature = "metrics", feature = "resizable"))]
+    pub(crate) fn metrics(&self) -> Option<&QueueMetrics> {
+        self.metrics.as_ref()
+    }
+
+    /// Create new empty queue which is expected to hold about `capacity`
+    /// items at once.
+    ///
+    /// **Note:** `crossbeam_queue::SegQueue` does not support
+    /// preallocating its segments so the hint is currently ignored. It is
+    /// accepted so callers with known bursts can pass it today and
+    /// benefit once the underlying queue supports it.
+    pub fn with_capacity_hint(capacity: usize) -> Self {
+        let _ = capacity;
+        Self::default()
+    }
+
+    /// Create new empty queue returning the items in the given order.
+    ///
+    /// **Note:** The `Lifo` order uses a mutex guarded stack internally
+    /// which is slower under contention than the `Fifo` order. The latter
+    /// only takes a shared lock which is never contended unless one of
+    /// the methods scanning the whole queue like `contains` is used.
+    pub fn with_order(order: Order) -> Self {
+        Self {
+            queue: Storage::new(order),
+            ..Self::default()
+        }
+    }
+
+    /// Create new empty queue with a soft limit. Whenever the length of
+    /// the queue reaches `threshold` the given observer is called. It is
+    /// called again only after the length dropped below the threshold
+    /// and reached it once more.
+    ///
+    /// This does not limit the queue in any way. It merely provides an
+    /// early warning e.g. when consumers are stuck and the queue keeps
+    /// growing.
+    pub fn with_warn_threshold<O>(threshold: usize, observer: O) -> Self
+    where
+        O: WarnObserver + 'static,
+    {
+        Self {
+            warn_threshold: Some(WarnThreshold {
+                threshold,
+                observer: Box::new(observer),
+                exceeded: AtomicBool::new(false),
+            }),
+            ..Self::default()
+        }
+    }
+
+    /// Give the queue a name which is shown in its `Debug` output. This
+    /// helps telling queues apart in logs. Queues created by `new_named`
+    /// use the name passed to it.
+    pub fn with_name(mut self, name: impl Into<String>) -> Self {
+        self.name = Some(name.into().into_boxed_str());
+        self
+    }
+    /// Get the name of the queue as given to `with_name` or `new_named`.
+    pub fn name(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
+    /// Enable the accounting of the time spent waiting in `pop`. The
+    /// recorded times can be retrieved via `wait_stats`.
+    ///
+    /// This is disabled by default as it requires reading the clock
+    /// twice for every call to `pop`.
+    pub fn record_waits(mut self) -> Self {
+        self.wait_recorder = Some(WaitRecorder::default());
+        self
+    }
+    /// Get the wait times recorded so far. All values are zero unless
+    /// `record_waits` was enabled.
+    pub fn wait_stats(&self) -> WaitStats {
+        self.wait_recorder
+            .as_ref()
+            .map(WaitRecorder::stats)
+            .unwrap_or_default()
+    }
+    /// Get the time since the last successful pop or since the creation
+    /// of the queue if no item has been popped yet. A growing value while
+    /// the queue is not empty indicates stuck consumers.
+    pub fn idle_duration(&self) -> Duration {
+        self.pop_clock.idle_duration()
+    }
+    /// Get the total number of items pushed into the queue since it was
+    /// created. Together with `total_popped` this can be used to compute
+    /// the throughput of the queue. While no operation is in progress
+    /// `total_pushed() == total_popped() + len()` holds.
+    pub fn total_pushed(&self) -> u64 {
+        self.total_pushed.load(Ordering::Relaxed)
+    }
+    /// Get the total number of items popped from the queue since it was
+    /// created.
+    pub fn total_popped(&self) -> u64 {
+        self.total_popped.load(Ordering::Relaxed)
+    }
     /// Get an item from the queue. If the queue is currently empty
     /// this method blocks until an item is available.
+    ///
+    /// **Note:** Closing the queue does not wake tasks waiting in this
+    /// method as there is no item to return. Use `pop_or_closed` if the
+    /// consumer needs to observe the closure of the queue.
+    ///
+    /// **Cancel safety:** Dropping the future before it completes never
+    /// removes an item from the queue, even if an item was handed to it
+    /// while it was waiting. The item stays available to other consumers.
+    ///
+    /// **Fairness:** This method takes part in the cooperative scheduling
+    /// budget of tokio. A task draining a full queue yields to the runtime
+    /// every now and then even though items are available right away.
+    /// This costs a little throughput but keeps other tasks on the same
+    /// thread responsive. `try_pop` never yields.
     pub async fn pop(&self) -> T {
-        let (txn, new_len) = self.available.sub();
-        let permit = self.semaphore.acquire().await.unwrap();
-        let item = self.queue.pop().unwrap();
+        let start = self.wait_recorder.as_ref().map(|_| Instant::now());
+        let (txn, permit, item) = loop {
+            let txn = self.available.sub();
+            let permit =
+                crate::trace::acquire(&self.semaphore, "pop", || self.len(), self.metrics.as_ref())
+                    .await;
+            match self.queue.pop() {
+                Some(item) => break (txn, permit, item),
+                // The permit does not belong to any item which means the
+                // bookkeeping is out of sync. Discard the permit instead of
+                // restoring it as the next consumer would not find an item
+                // either and wait for the next one.
+                None => permit.forget(),
+            }
+        };
         txn.commit();
-        if new_len <= 0 {
+        self.total_popped.fetch_add(1, Ordering::Relaxed);
+        self.pop_clock.record();
+        if let (Some(recorder), Some(start)) = (&self.wait_recorder, start) {
+            recorder.record(start.elapsed());
+        }
+        if self.queue.is_empty() {
             self.notify_empty();
         }
+        if let Some(warn_threshold) = &self.warn_threshold {
+            warn_threshold.popped(self.queue.len());
+        }
+        self.notify_changed();
+        if let Some(metrics) = &self.metrics {
+            metrics.popped(self.queue.len());
+        }
         permit.forget();
         item
     }
+    /// Get an item from the queue like `pop` but return `None` once
+    /// `token` is cancelled. If the token is already cancelled no item is
+    /// taken from the queue.
+    ///
+    /// This relies on the cancel safety of `pop`: an item is never lost
+    /// and `available()` is restored when the wait is cancelled.
+    ///
+    /// Enabled via the `cancellation` feature in your `Cargo.toml`.
+    #[cfg(feature = "cancellation")]
+    pub async fn pop_cancellable(&self, token: &CancellationToken) -> Option<T> {
+        tokio::select! {
+            biased;
+            _ = token.cancelled() => None,
+            item = self.pop() => Some(item),
+        }
+    }
+    /// Get an item from the queue wrapped in a [`PopGuard`]. If the guard
+    /// is dropped without calling [`PopGuard::commit`], e.g. because the
+    /// code processing the item panicked, the item is put back to the
+    /// front of the queue. This provides at-least-once processing.
+    ///
+    /// **Note:** Putting the item back shares the O(n) cost and the
+    /// locking of `contains`.
+    pub async fn pop_guard(&self) -> PopGuard<'_, T> {
+        PopGuard {
+            queue: self,
+            item: Some(self.pop().await),
+        }
+    }
     /// Try to get an item from the queue. If the queue is currently
     /// empty return None instead.
     pub fn try_pop(&self) -> Option<T> {
-        let (txn, new_len) = self.available.sub();
+        let txn = self.available.sub();
         let permit = self.semaphore.try_acquire().ok()?;
-        let item = self.queue.pop().unwrap();
+        let item = match self.queue.pop() {
+            Some(item) => item,
+            // See `pop`
+            None => {
+                permit.forget();
+                return None;
+            }
+        };
         txn.commit();
-        if new_len <= 0 {
+        self.total_popped.fetch_add(1, Ordering::Relaxed);
+        self.pop_clock.record();
+        if self.queue.is_empty() {
             self.notify_empty();
         }
+        if let Some(warn_threshold) = &self.warn_threshold {
+            warn_threshold.popped(self.queue.len());
+        }
+        self.notify_changed();
+        if let Some(metrics) = &self.metrics {
+            metrics.popped(self.queue.len());
+        }
         permit.forget();
         Some(item)
     }
+    /// Try to get the first item matching `pred` from the queue. Items
+    /// in front of it are popped and dropped. If no item matches the
+    /// queue is drained completely and `None` is returned.
+    ///
+    /// Every dropped item is popped like with `try_pop` so the counters
+    /// of the queue stay in sync.
+    pub fn try_pop_find<F: Fn(&T) -> bool>(&self, pred: F) -> Option<T> {
+        while let Some(item) = self.try_pop() {
+            if pred(&item) {
+                return Some(item);
+            }
+        }
+        None
+    }
+    /// Try to get an item from the queue like `try_pop` and return it
+    /// together with the number of futures waiting in `pop` right after
+    /// the item was taken. See `pending_pops`.
+    ///
+    /// **Note:** Futures waiting in `pop` are handed new items before
+    /// `try_pop` can take them. Thus the count is only non-zero if other
+    /// consumers started waiting concurrently, e.g. because this call
+    /// took the last item. A non-zero count means the queue is running
+    /// dry under contention.
+    pub fn try_pop_with_waiters(&self) -> Option<(T, usize)> {
+        let item = self.try_pop()?;
+        Some((item, self.pending_pops()))
+    }
+    /// Try to get an item from the queue like `try_pop` and return it
+    /// together with the length of the queue right before and right
+    /// after the item was taken as `(item, len_before, len_after)`.
+    ///
+    /// **Note:** The lengths are read right around the pop. Without
+    /// concurrent pushes and pops `len_before == len_after + 1` holds.
+    /// Otherwise they may differ by more as other tasks can modify the
+    /// queue between the reads.
+    pub fn try_pop_delta(&self) -> Option<(T, usize, usize)> {
+        let len_before = self.len();
+        let item = self.try_pop()?;
+        Some((item, len_before, self.len()))
+    }
+    /// Get a copy of the item which would be returned by the next call
+    /// to `pop` without removing it from the queue. Returns `None` if the
+    /// queue is empty.
+    ///
+    /// **Note:** This shares the O(n) cost and the locking of `contains`.
+    /// The returned item is only guaranteed to be the next one popped if
+    /// no other task pushes or pops concurrently.
+    pub fn peek_copy(&self) -> Option<T>
+    where
+        T: Copy,
+    {
+        self.queue.with_items(|items| items.first().copied())
+    }
+    /// Get clones of up to `n` items which would be returned by the next
+    /// calls to `pop` without removing them from the queue. The items are
+    /// returned in the order they would be popped.
+    ///
+    /// **Note:** This shares the O(n) cost and the locking of `contains`.
+    /// The whole queue is scanned even if `n` is small and the returned
+    /// items may already have been popped by the time this returns.
+    pub fn peek_n(&self, n: usize) -> Vec<T>
+    where
+        T: Clone,
+    {
+        self.queue
+            .with_items(|items| items.iter().take(n).cloned().collect())
+    }
+    /// Try to get an item from the queue calling `try_pop` up to
+    /// `max_spins` times in a busy loop. Returns `None` if the queue is
+    /// still empty after that.
+    ///
+    /// **Note:** This burns CPU while spinning and never yields to the
+    /// runtime. It is only meant for latency critical consumers running
+    /// on a dedicated core. Use `pop` in all other cases.
+    pub fn spin_pop(&self, max_spins: usize) -> Option<T> {
+        for _ in 0..max_spins {
+            if let Some(item) = self.try_pop() {
+                return Some(item);
+            }
+            std::hint::spin_loop();
+        }
+        None
+    }
+    /// Get an iterator which pops items from the queue without blocking.
+    /// Every call to `next` is a `try_pop` so the iterator ends as soon
+    /// as the queue is empty at the moment `next` is called.
+    pub fn drain_iter(&self) -> DrainIter<'_, T> {
+        DrainIter { queue: self }
+    }
+    /// Fill `out` with items popped from the queue without blocking and
+    /// return the number of items written. The slots are filled from the
+    /// start of the slice until it is full or the queue is empty. Slots
+    /// after the returned count are left untouched.
+    pub fn try_pop_slice(&self, out: &mut [Option<T>]) -> usize {
+        // `zip` polls `out` first so no item is popped once it is full.
+        out.iter_mut()
+            .zip(self.drain_iter())
+            .map(|(slot, item)| *slot = Some(item))
+            .count()
+    }
+    /// Move the items of the queue into the limited queue `dst` until this
+    /// queue is empty waiting for `dst` to have room for every item. The
+    /// items keep their order. Returns the number of items moved.
+    ///
+    /// Room in `dst` is reserved before an item is popped so no item is
+    /// lost if the returned future is dropped. Moving stops early if
+    /// `dst` is closed, even while waiting for room, and the remaining
+    /// items stay in this queue. It also stops without waiting for room
+    /// once this queue is empty.
+    ///
+    /// Enabled via the `limited` feature in your `Cargo.toml`.
+    #[cfg(feature = "limited")]
+    pub async fn drain_into(&self, dst: &crate::limited::Queue<T>) -> usize {
+        let mut moved = 0;
+        while !dst.is_closed() {
+            // Waiting for room in `dst` does not end when this queue runs
+            // empty.
+            let mut reservation = tokio::select! {
+                biased;
+                reservation = dst.reserve(1) => match reservation {
+                    Some(reservation) => reservation,
+                    None => break,
+                },
+                _ = self.wait_empty() => break,
+            };
+            let item = match self.try_pop() {
+                Some(item) => item,
+                None => break,
+            };
+            if let Err(item) = reservation.push(item) {
+                // `dst` has been closed while waiting for room.
+                self.push_front(item);
+                break;
+            }
+            moved += 1;
+        }
+        moved
+    }
+    /// Convert the queue into a limited queue with the given capacity.
+    /// The items are moved over in the order they would have been
+    /// popped. A closed queue results in a closed limited queue.
+    ///
+    /// If the queue contains more than `capacity` items it is returned
+    /// unchanged as `Err<Self>`. Use `drain_iter` to move a part of the
+    /// items instead.
+    ///
+    /// ```
+    /// use deadqueue::unlimited::Queue;
+    ///
+    /// let queue: Queue<usize> = (1..=3).collect();
+    /// let queue = queue.into_limited(5).unwrap();
+    /// assert_eq!(queue.capacity(), 5);
+    /// assert_eq!(queue.len(), 3);
+    /// ```
+    ///
+    /// Enabled via the `limited` feature in your `Cargo.toml`.
+    #[cfg(feature = "limited")]
+    #[allow(clippy::result_large_err)]
+    pub fn into_limited(self, capacity: usize) -> Result<crate::limited::Queue<T>, Self> {
+        if self.len() > capacity {
+            return Err(self);
+        }
+        let queue = crate::limited::Queue::with_capacity(capacity);
+        for item in self.drain_iter() {
+            // The capacity has been checked above and no one else has
+            // access to the new queue.
+            queue.try_push(item).ok().unwrap();
+        }
+        if self.is_closed() {
+            queue.close();
+        }
+        Ok(queue)
+    }
+    /// Convert the queue into a resizable queue with the given capacity.
+    /// The items are moved over in the order they would have been
+    /// popped. A closed queue results in a closed resizable queue.
+    ///
+    /// If the queue contains more than `capacity` items it is returned
+    /// unchanged as `Err<Self>`. Use `drain_iter` to move a part of the
+    /// items instead.
+    ///
+    /// Enabled via the `resizable` feature in your `Cargo.toml`.
+    #[cfg(feature = "resizable")]
+    #[allow(clippy::result_large_err)]
+    pub fn into_resizable(self, capacity: usize) -> Result<crate::resizable::Queue<T>, Self> {
+        if self.len() > capacity {
+            return Err(self);
+        }
+        let queue = crate::resizable::Queue::with_capacity(capacity);
+        for item in self.drain_iter() {
+            // The capacity has been checked above and no one else has
+            // access to the new queue.
+            queue.try_push(item).ok().unwrap();
+        }
+        if self.is_closed() {
+            queue.close();
+        }
+        Ok(queue)
+    }
+    /// Get up to `max` items from the queue waiting at most `timeout` for
+    /// them. This method returns as soon as `max` items have been
+    /// collected or the timeout has elapsed, whichever comes first. The
+    /// timeout is not reset when an item is received. The returned vector
+    /// contains the items collected so far and may be empty.
+    pub async fn pop_n_timeout(&self, max: usize, timeout: Duration) -> Vec<T> {
+        let mut items = Vec::new();
+        let deadline = tokio::time::sleep(timeout);
+        tokio::pin!(deadline);
+        while items.len() < max {
+            tokio::select! {
+                biased;
+                item = self.pop() => items.push(item),
+                _ = &mut deadline => break,
+            }
+        }
+        items
+    }
+    /// Pop the items currently in the queue until it is empty or `timeout`
+    /// has elapsed, whichever comes first. The popped items are returned
+    /// in order and the items which could not be popped in time are left
+    /// in the queue. Unlike `pop_n_timeout` this method never waits for
+    /// new items to be pushed.
+    ///
+    /// **Note:** If other consumers pop the last item concurrently this
+    /// method waits for a new item until the timeout has elapsed. It
+    /// requires a `tokio` runtime with the time driver enabled.
+    pub async fn drain_for(&self, timeout: Duration) -> Vec<T> {
+        let mut items = Vec::new();
+        let deadline = tokio::time::Instant::now() + timeout;
+        // The clock is checked before every item as popping an item which
+        // is already in the queue completes without giving the timer a
+        // chance to fire.
+        while !self.is_empty() && tokio::time::Instant::now() < deadline {
+            match tokio::time::timeout_at(deadline, self.pop()).await {
+                Ok(item) => items.push(item),
+                Err(_) => break,
+            }
+        }
+        items
+    }
+    /// Get an item from the queue blocking the current thread until an
+    /// item is available.
+    ///
+    /// This method is meant for synchronous code which is not running
+    /// inside an async runtime. The calling thread is parked while waiting
+    /// so no runtime is required. **Never** call this method from within an
+    /// async context as it blocks the executor thread.
+    pub fn pop_blocking(&self) -> T {
+        crate::blocking::block_on(self.pop())
+    }
     /// Push an item into the queue
+    ///
+    /// **Note:** If the queue is closed the item is dropped.
     pub fn push(&self, item: T) {
+        if self.is_closed() {
+            return;
+        }
         self.queue.push(item);
+        self.pushed();
+    }
+    /// Put an item back to the front of the queue so it is returned by
+    /// the next call to `pop`. Unlike `push` this also works for closed
+    /// queues as the item was part of the queue before.
+    fn push_front(&self, item: T) {
+        self.queue.with_items(|items| items.insert(0, item));
+        self.pushed();
+    }
+    /// Update the bookkeeping after an item has been added to the queue.
+    fn pushed(&self) {
+        self.total_pushed.fetch_add(1, Ordering::Relaxed);
         self.semaphore.add_permits(1);
         self.available.add();
+        if let Some(warn_threshold) = &self.warn_threshold {
+            warn_threshold.pushed(self.queue.len());
+        }
+        self.notify_changed();
+        if let Some(metrics) = &self.metrics {
+            metrics.pushed(self.queue.len());
+        }
+    }
+    /// Push an item into the queue unless an equal item is already
+    /// queued. Returns `true` if the item was pushed.
+    ///
+    /// **Note:** This uses `contains` and shares its O(n) cost. Two
+    /// concurrent calls pushing equal items can both succeed. Use
+    /// `DedupQueue` (`dedup` feature) for constant time and race free
+    /// deduplication.
+    pub fn push_unique(&self, item: T) -> bool
+    where
+        T: PartialEq,
+    {
+        if self.is_closed() || self.contains(&item) {
+            return false;
+        }
+        self.push(item);
+        true
+    }
+    /// Get a `watch::Receiver` which is updated with the length of the
+    /// queue whenever it changes. Updates are coalesced so a slow receiver
+    /// only observes the latest length.
+    pub fn subscribe_len(&self) -> watch::Receiver<usize> {
+        // The length is only sent while there are receivers so it might
+        // be outdated.
+        let mut receiver = self.len_sender.subscribe();
+        self.update_len();
+        receiver.borrow_and_update();
+        receiver
     }
     /// Get current length of queue (number of items currently stored).
     pub fn len(&self) -> usize {
@@ -70,55 +813,700 @@ impl<T> Queue<T> {
     pub fn is_empty(&self) -> bool {
         self.queue.is_empty()
     }
+    /// Get the `SegQueue` storing the items of the queue. Returns `None`
+    /// if the queue was created with `Order::Lifo` as that order is not
+    /// backed by a `SegQueue`.
+    ///
+    /// # Safety
+    ///
+    /// The caller must not push items to or pop items from the returned
+    /// queue. Doing so bypasses the bookkeeping of this queue which can
+    /// make its methods panic, block forever or lose items. Read-only
+    /// methods like `len` and `is_empty` are fine but bypass the lock
+    /// taken by `contains` and related methods so they may observe the
+    /// queue while its items are taken out for a scan.
+    pub unsafe fn inner(&self) -> Option<&SegQueue<T>> {
+        match &self.queue {
+            Storage::Fifo { queue, .. } => Some(queue),
+            Storage::Lifo(_) => None,
+        }
+    }
+    /// Get the approximate number of item slots currently allocated by
+    /// the queue. This is meant as a diagnostic aid for tuning memory
+    /// sensitive deployments.
+    ///
+    /// **Note:** The FIFO queue is based on `crossbeam_queue::SegQueue`
+    /// which does not expose its allocation. The returned value is only a
+    /// rough estimate: it is the number of items currently stored and does
+    /// not account for the slots which are allocated ahead of time. For
+    /// the LIFO queue this is the exact capacity of the underlying stack.
+    pub fn allocated_capacity(&self) -> usize {
+        self.queue.allocated_capacity()
+    }
+    /// Returns `true` if the queue contains an item equal to `item`.
+    ///
+    /// **Note:** This scans the whole queue and therefore takes O(n)
+    /// time. The queue is locked during the scan so concurrent pushes and
+    /// pops wait until it is finished.
+    pub fn contains(&self, item: &T) -> bool
+    where
+        T: PartialEq,
+    {
+        self.queue.with_items(|items| items.contains(item))
+    }
+    /// Move the first item matching the predicate to the front of the
+    /// queue so it is returned by the next call to `pop`. Returns `true`
+    /// if a matching item was found.
+    ///
+    /// **Note:** This shares the O(n) cost and the locking of `contains`.
+    pub fn bump_to_front<F: Fn(&T) -> bool>(&self, predicate: F) -> bool {
+        self.queue
+            .with_items(|items| match items.iter().position(predicate) {
+                Some(index) => {
+                    let item = items.remove(index);
+                    items.insert(0, item);
+                    true
+                }
+                None => false,
+            })
+    }
+    /// Remove up to `n` items from the back of the queue and return them
+    /// in the order they were pushed. The removed items count as popped
+    /// but the remaining items are left in place. Fewer than `n` items
+    /// are only removed if the queue is shorter or the permits of the
+    /// other items are already granted to waiting tasks.
+    #[cfg(feature = "resizable")]
+    pub(crate) fn take_newest(&self, n: usize) -> Vec<T> {
+        let newest = self.queue.with_pushed(|items| {
+            // Only items whose permit is not granted to a waiting task can
+            // be removed. Those tasks wait for the lock and find the
+            // remaining items once it is released.
+            let mut permits = 0;
+            while permits < n.min(items.len()) {
+                match self.semaphore.try_acquire() {
+                    Ok(permit) => permit.forget(),
+                    Err(_) => break,
+                }
+                permits += 1;
+            }
+            let at = items.len() - permits;
+            items.split_off(at)
+        });
+        for _ in &newest {
+            self.available.sub().commit();
+            self.total_popped.fetch_add(1, Ordering::Relaxed);
+            if let Some(metrics) = &self.metrics {
+                metrics.popped(self.queue.len());
+            }
+        }
+        if !newest.is_empty() {
+            if let Some(warn_threshold) = &self.warn_threshold {
+                warn_threshold.popped(self.queue.len());
+            }
+            if self.queue.is_empty() {
+                self.notify_empty();
+            }
+            self.notify_changed();
+        }
+        newest
+    }
     /// Get available count. This is the difference between the current
     /// queue length and the number of tasks waiting for an item of the
     /// queue.
     pub fn available(&self) -> isize {
         self.available.get()
     }
+    /// Check the internal invariants of the queue and return `true` if
+    /// they hold:
+    ///
+    ///   - `len() == max(available(), 0)`
+    ///   - Every item in the queue can be popped without waiting
+    ///
+    /// The invariants only hold while no other operation is in progress.
+    /// This method is meant for tests and only available with the
+    /// `testing` feature enabled.
+    #[cfg(feature = "testing")]
+    pub fn check_invariants(&self) -> bool {
+        let len = self.len();
+        self.available.matches_len(len) && self.semaphore.available_permits() == len
+    }
+    /// Get the number of items in the queue and the number of futures
+    /// waiting for an item as `(len, waiters)`.
+    ///
+    /// This is a more explicit form of `available` which always equals
+    /// `len - waiters`. As the queue can be modified concurrently the
+    /// returned values are only a snapshot.
+    pub fn occupancy(&self) -> (usize, usize) {
+        let len = self.len();
+        let waiters = (len as isize - self.available()).max(0) as usize;
+        (len, waiters)
+    }
+    /// Get the number of items which can be popped right now without
+    /// waiting. This is `available()` saturated at zero.
+    pub fn items_ready(&self) -> usize {
+        self.available().max(0) as usize
+    }
+    /// Get the number of futures currently waiting for an item. This is
+    /// the negated `available()` saturated at zero.
+    pub fn pending_pops(&self) -> usize {
+        self.available().min(0).unsigned_abs()
+    }
+    /// Get a snapshot of the state of the queue.
+    pub fn stats_snapshot(&self) -> QueueStatsSnapshot {
+        QueueStatsSnapshot {
+            len: self.len(),
+            capacity: None,
+            available: self.available(),
+            closed: self.is_closed(),
+        }
+    }
+    /// Wait until `predicate` returns `true` for the state of the queue.
+    /// The predicate is checked right away and again after every push,
+    /// pop or close of the queue.
+    ///
+    /// **Note:** The predicate is checked when the waiting task is polled
+    /// and not at the time of the change. States which only exist for a
+    /// short moment can therefore be missed.
+    pub async fn wait_until<F: Fn(&QueueStatsSnapshot) -> bool>(&self, predicate: F) {
+        self.wait_changed_until(|| predicate(&self.stats_snapshot()))
+            .await
+    }
+    /// Wait until `done` returns `true` checking it again after every
+    /// change of the queue.
+    pub(crate) async fn wait_changed_until(&self, done: impl Fn() -> bool) {
+        crate::wait_until(&self.notifier_changed, done).await
+    }
+    /// Notify any callers awaiting a change of the queue
+    pub(crate) fn notify_changed(&self) {
+        crate::notify(&self.notifier_changed);
+        if self.len_sender.receiver_count() > 0 {
+            self.update_len();
+        }
+    }
+    /// Send the current length to the `subscribe_len` receivers. The
+    /// length is read while holding the lock of the channel so the last
+    /// update always carries the latest length.
+    fn update_len(&self) {
+        self.len_sender.send_if_modified(|old| {
+            let len = self.len();
+            std::mem::replace(old, len) != len
+        });
+    }
     /// Notify any callers awaiting empty()
     fn notify_empty(&self) {
-        self.notifier_empty.send_replace(());
+        crate::notify(&self.notifier_empty);
+    }
+    /// Await until the queue contains at least `n` items. This can be
+    /// used by consumers to wait for a batch of items.
+    pub async fn wait_len(&self, n: usize) {
+        self.wait_until(|stats| stats.len >= n).await
+    }
+    /// Await until the queue contains at least one item.
+    pub async fn wait_not_empty(&self) {
+        self.wait_len(1).await
     }
     /// Await until the queue is empty.
     pub async fn wait_empty(&self) {
-        if self.is_empty() {
-            return;
-        }
-        self.subscribe_empty().changed().await.unwrap();
+        self.wait_until(|stats| stats.len == 0).await
     }
-    /// Get a `Receiver` object that can repeatedly be awaited for
+    /// Get a [`QueueEvent`] receiver that can repeatedly be awaited for
     /// queue-empty notifications.
-    pub fn subscribe_empty(&self) -> Receiver {
-        self.notifier_empty.subscribe()
+    pub fn subscribe_empty(&self) -> QueueEvent {
+        QueueEvent(self.notifier_empty.subscribe())
+    }
+    /// Turn the queue into a [`MappedQueue`] which applies `f` to every
+    /// item that is popped. Items are still pushed as `T`.
+    pub fn map<U, F: Fn(T) -> U>(self, f: F) -> MappedQueue<T, U, F> {
+        MappedQueue {
+            queue: self,
+            f,
+            _marker: PhantomData,
+        }
+    }
+    /// Close the queue. Once closed no new items are accepted and `push`
+    /// drops the given item. Items which are already in the queue can
+    /// still be popped.
+    ///
+    /// Tasks waiting in `pop_or_closed` are woken and get `None` once the
+    /// queue is empty. Tasks waiting in `pop` are **not** woken and keep
+    /// waiting for an item.
+    pub fn close(&self) {
+        self.closed.store(true, Ordering::Relaxed);
+        crate::notify(&self.notifier_closed);
+        self.notify_changed();
+    }
+    /// Returns `true` if the queue has been closed.
+    pub fn is_closed(&self) -> bool {
+        self.closed.load(Ordering::Relaxed)
+    }
+    /// Wait until the queue is closed. Resolves immediately if the queue
+    /// has already been closed.
+    pub async fn closed(&self) {
+        self.wait_until(|stats| stats.closed).await
+    }
+    /// Get an item from the queue. If the queue is currently empty this
+    /// method blocks until an item is available or the queue is closed.
+    /// Returns `None` once the queue is closed and empty.
+    ///
+    /// Calling `close` wakes all tasks waiting in this method right away.
+    /// The items which are still in the queue are returned first so no
+    /// item is lost. Waiting tasks don't hold on to any item once woken
+    /// so `available()` is restored accordingly.
+    pub async fn pop_or_closed(&self) -> Option<T> {
+        let mut closed = self.notifier_closed.subscribe();
+        loop {
+            if let Some(item) = self.try_pop() {
+                return Some(item);
+            }
+            if self.is_closed() {
+                return None;
+            }
+            tokio::select! {
+                item = self.pop() => return Some(item),
+                _ = closed.changed() => {}
+            }
+        }
+    }
+    /// Pop items from the queue and call `f` for each of them, running at
+    /// most `concurrency` of the returned futures at the same time. This
+    /// method returns once the queue is closed and all items have been
+    /// processed.
+    ///
+    /// The futures are polled by the task calling this method. Spawn a
+    /// task inside of `f` if the work needs to run in parallel.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `concurrency` is zero.
+    pub async fn for_each_concurrent<F, Fut>(&self, concurrency: usize, f: F)
+    where
+        F: Fn(T) -> Fut,
+        Fut: Future<Output = ()>,
+    {
+        crate::consumer::for_each_concurrent(|| self.pop_or_closed(), concurrency, f).await
+    }
+    /// Close the queue and wait up to `drain_timeout` for the consumers
+    /// to drain it. If the queue could not be drained in time the number
+    /// of remaining items is returned as `Err<usize>`.
+    ///
+    /// **Note:** This method requires a `tokio` runtime with the time
+    /// driver enabled.
+    pub async fn shutdown(&self, drain_timeout: Duration) -> Result<(), usize> {
+        self.close();
+        match tokio::time::timeout(drain_timeout, self.wait_empty()).await {
+            Ok(()) => Ok(()),
+            Err(_) => Err(self.len()),
+        }
+    }
+}
+
+#[cfg(feature = "tokio-mpsc")]
+impl<T: Send + 'static> Queue<T> {
+    /// Spawn a task which receives items from `rx` and pushes them into
+    /// the queue. The task finishes once all senders of the
+    /// channel have been dropped and the channel is drained.
+    pub fn forward_from(self: Arc<Self>, mut rx: mpsc::Receiver<T>) -> JoinHandle<()> {
+        tokio::spawn(async move {
+            while let Some(item) = rx.recv().await {
+                self.push(item);
+            }
+        })
+    }
+    /// Spawn a task which pops items from the queue and sends them into
+    /// `tx` waiting for the channel to have room for every item. The task
+    /// finishes once the queue is closed and drained or the receiver of
+    /// the channel has been dropped. In the latter case the item which
+    /// could not be sent is dropped.
+    pub fn forward_to(self: Arc<Self>, tx: mpsc::Sender<T>) -> JoinHandle<()> {
+        tokio::spawn(async move {
+            while let Some(item) = self.pop_or_closed().await {
+                if tx.send(item).await.is_err() {
+                    break;
+                }
+            }
+        })
+    }
+}
+
+#[cfg(feature = "std-mpsc")]
+impl<T: Send + 'static> Queue<T> {
+    /// Spawn a blocking task which receives items from the standard
+    /// library channel `rx` and pushes them into the queue. The task
+    /// finishes once all senders of the channel have been dropped and the
+    /// channel is drained.
+    ///
+    /// The task runs on the blocking thread pool of the runtime via
+    /// `tokio::task::spawn_blocking`.
+    pub fn pump_from_std(self: Arc<Self>, rx: std::sync::mpsc::Receiver<T>) -> JoinHandle<()> {
+        tokio::task::spawn_blocking(move || {
+            for item in rx {
+                self.push(item);
+            }
+        })
+    }
+}
+
+#[cfg(feature = "broadcast")]
+impl<T: Clone + Send + 'static> Queue<T> {
+    /// Turn the queue into a [`Broadcast`] adapter which delivers every
+    /// popped item to all subscribers. `capacity` is the number of items
+    /// a subscriber can fall behind before it starts skipping items.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `capacity` is zero or larger than `usize::MAX / 2`.
+    pub fn broadcast(self: Arc<Self>, capacity: usize) -> Broadcast<T> {
+        Broadcast::spawn(capacity, |sender| async move {
+            while let Some(item) = self.pop_or_closed().await {
+                // Sending only fails if there are no subscribers in
+                // which case the item is dropped.
+                let _ = sender.send(item);
+            }
+        })
+    }
+}
+
+#[cfg(feature = "pool")]
+impl<T: Send + 'static> Queue<T> {
+    /// Spawn a [`WorkerPool`] of `workers` tasks which pop items from the
+    /// queue and call `f` for each of them. Every worker awaits the
+    /// future returned by `f` before popping the next item.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `workers` is zero.
+    pub fn worker_pool<F, Fut>(self: Arc<Self>, workers: usize, f: F) -> WorkerPool<Self>
+    where
+        F: Fn(T) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        let f = Arc::new(f);
+        WorkerPool::spawn(self, workers, Self::close, |queue| {
+            let f = f.clone();
+            async move {
+                while let Some(item) = queue.pop_or_closed().await {
+                    f(item).await;
+                }
+            }
+        })
+    }
+}
+
+/// View of a queue which transforms the items when popping them.
+///
+/// This object is created by the [`Queue::map`] method. Items are pushed
+/// as `T` and returned as `U` by the pop methods.
+pub struct MappedQueue<T, U, F> {
+    queue: Queue<T>,
+    f: F,
+    _marker: PhantomData<fn() -> U>,
+}
+
+impl<T, U, F: Fn(T) -> U> MappedQueue<T, U, F> {
+    /// Get an item from the queue and transform it. If the queue is
+    /// currently empty this method blocks until an item is available.
+    pub async fn pop(&self) -> U {
+        (self.f)(self.queue.pop().await)
+    }
+    /// Try to get an item from the queue and transform it. If the queue
+    /// is currently empty return None instead.
+    pub fn try_pop(&self) -> Option<U> {
+        self.queue.try_pop().map(&self.f)
+    }
+    /// Push an item into the queue
+    pub fn push(&self, item: T) {
+        self.queue.push(item)
+    }
+    /// Get current length of queue (number of items currently stored).
+    pub fn len(&self) -> usize {
+        self.queue.len()
+    }
+    /// Returns `true` if the queue is empty.
+    pub fn is_empty(&self) -> bool {
+        self.queue.is_empty()
+    }
+    /// Get a reference to the underlying queue.
+    pub fn get_ref(&self) -> &Queue<T> {
+        &self.queue
+    }
+    /// Consume the view returning the underlying queue.
+    pub fn into_inner(self) -> Queue<T> {
+        self.queue
+    }
+}
+
+impl<T, U, F> Debug for MappedQueue<T, U, F> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("MappedQueue")
+            .field("queue", &self.queue)
+            .finish()
+    }
+}
+
+/// Iterator which pops items from a [`Queue`] until it is empty.
+///
+/// This object is created by the [`Queue::drain_iter`] method.
+pub struct DrainIter<'a, T> {
+    queue: &'a Queue<T>,
+}
+
+impl<'a, T> Iterator for DrainIter<'a, T> {
+    type Item = T;
+    fn next(&mut self) -> Option<T> {
+        self.queue.try_pop()
+    }
+}
+
+impl<'a, T> Debug for DrainIter<'a, T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("DrainIter")
+            .field("queue", &self.queue)
+            .finish()
+    }
+}
+
+/// Item popped from a [`Queue`] which is put back to the front of the
+/// queue unless it is committed.
+///
+/// This object is created by the [`Queue::pop_guard`] method. As it hands
+/// out references to the item it is only `Sync` if `T: Sync`.
+pub struct PopGuard<'a, T> {
+    queue: &'a Queue<T>,
+    item: Option<T>,
+}
+
+impl<'a, T> PopGuard<'a, T> {
+    /// Mark the item as processed and take it out of the guard.
+    pub fn commit(mut self) -> T {
+        self.item.take().unwrap()
+    }
+}
+
+impl<'a, T> Deref for PopGuard<'a, T> {
+    type Target = T;
+    fn deref(&self) -> &T {
+        self.item.as_ref().unwrap()
+    }
+}
+
+impl<'a, T> DerefMut for PopGuard<'a, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        self.item.as_mut().unwrap()
+    }
+}
+
+impl<'a, T> Drop for PopGuard<'a, T> {
+    fn drop(&mut self) {
+        if let Some(item) = self.item.take() {
+            self.queue.push_front(item);
+        }
+    }
+}
+
+impl<'a, T: Debug> Debug for PopGuard<'a, T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("PopGuard")
+            .field("item", &self.item)
+            .finish()
+    }
+}
+
+/// Unlimited queue which never contains the same item twice.
+///
+/// The queued items are tracked in a `HashSet` which makes `push_unique`
+/// and `contains` constant time operations. The set stores a clone of
+/// every queued item.
+///
+/// Enabled via the `dedup` feature in your `Cargo.toml`.
+#[cfg(feature = "dedup")]
+pub struct DedupQueue<T> {
+    queue: Queue<T>,
+    index: Mutex<HashSet<T>>,
+}
+
+#[cfg(feature = "dedup")]
+impl<T: Clone + Eq + Hash> DedupQueue<T> {
+    /// Create new empty queue
+    pub fn new() -> Self {
+        Self::default()
+    }
+    /// Get an item from the queue. If the queue is currently empty
+    /// this method blocks until an item is available.
+    pub async fn pop(&self) -> T {
+        let item = self.queue.pop().await;
+        self.index.lock().unwrap().remove(&item);
+        item
+    }
+    /// Try to get an item from the queue. If the queue is currently
+    /// empty return None instead.
+    pub fn try_pop(&self) -> Option<T> {
+        let item = self.queue.try_pop()?;
+        self.index.lock().unwrap().remove(&item);
+        Some(item)
+    }
+    /// Push an item into the queue unless an equal item is already
+    /// queued. Returns `true` if the item was pushed.
+    pub fn push_unique(&self, item: T) -> bool {
+        let mut index = self.index.lock().unwrap();
+        if self.queue.is_closed() || !index.insert(item.clone()) {
+            return false;
+        }
+        self.queue.push(item);
+        true
+    }
+    /// Returns `true` if the queue contains an item equal to `item`.
+    pub fn contains(&self, item: &T) -> bool {
+        self.index.lock().unwrap().contains(item)
+    }
+    /// Get current length of queue (number of items currently stored).
+    pub fn len(&self) -> usize {
+        self.queue.len()
+    }
+    /// Returns `true` if the queue is empty.
+    pub fn is_empty(&self) -> bool {
+        self.queue.is_empty()
+    }
+    /// Get a reference to the underlying queue.
+    pub fn get_ref(&self) -> &Queue<T> {
+        &self.queue
+    }
+}
+
+#[cfg(feature = "dedup")]
+impl<T> Default for DedupQueue<T> {
+    fn default() -> Self {
+        Self {
+            queue: Queue::default(),
+            index: Mutex::new(HashSet::new()),
+        }
+    }
+}
+
+#[cfg(feature = "dedup")]
+impl<T> Debug for DedupQueue<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("DedupQueue")
+            .field("queue", &self.queue)
+            .finish()
     }
 }
 
 impl<T> Debug for Queue<T> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         f.debug_struct("Queue")
-            .field("queue", &self.queue)
-            .field("semaphore", &self.semaphore)
-            .field("available", &self.available)
-            .field("empty", &self.notifier_empty)
+            .field("name", &self.name())
+            .field("len", &self.len())
+            .field("available", &self.available())
+            .field("closed", &self.is_closed())
+            .field("total_pushed", &self.total_pushed())
+            .field("total_popped", &self.total_popped())
+            .field(
+                "warn_threshold",
+                &self.warn_threshold.as_ref().map(|w| w.threshold),
+            )
             .finish()
     }
 }
 
+impl<T> Queue<Box<T>> {
+    /// Push a boxed item into the queue. Only the pointer is moved into
+    /// the queue which avoids copying large items around. Use this with a
+    /// [`BoxQueue`] to keep a single heap allocation per item from the
+    /// producer all the way to the consumer.
+    pub fn push_boxed(&self, item: Box<T>) {
+        self.push(item)
+    }
+    /// Get an item from the queue and move it out of its box. If the
+    /// queue is currently empty this method blocks until an item is
+    /// available.
+    pub async fn pop_unboxed(&self) -> T {
+        *self.pop().await
+    }
+    /// Try to get an item from the queue and move it out of its box. If
+    /// the queue is currently empty return None instead.
+    pub fn try_pop_unboxed(&self) -> Option<T> {
+        self.try_pop().map(|item| *item)
+    }
+}
+
+#[cfg(feature = "tracing-context")]
+impl<T> Queue<Spanned<T>> {
+    /// Push an item into the queue together with the current span.
+    pub fn push_spanned(&self, item: T) {
+        self.push(Spanned::new(item))
+    }
+    /// Get an item from the queue together with the span that was current
+    /// when it was pushed. If the queue is currently empty this method
+    /// blocks until an item is available.
+    pub async fn pop_with_span(&self) -> (Span, T) {
+        self.pop().await.into_parts()
+    }
+    /// Try to get an item from the queue together with the span that was
+    /// current when it was pushed. If the queue is currently empty return
+    /// None instead.
+    pub fn try_pop_with_span(&self) -> Option<(Span, T)> {
+        self.try_pop().map(Spanned::into_parts)
+    }
+}
+
 impl<T> Default for Queue<T> {
     fn default() -> Self {
         Self {
-            queue: SegQueue::new(),
+            queue: Storage::new(Order::Fifo),
             semaphore: Semaphore::new(0),
             available: Available::new(0),
             notifier_empty: crate::new_notifier(),
+            closed: AtomicBool::new(false),
+            notifier_closed: crate::new_notifier(),
+            notifier_changed: crate::new_notifier(),
+            len_sender: watch::channel(0).0,
+            metrics: None,
+            wait_recorder: None,
+            pop_clock: PopClock::default(),
+            name: None,
+            total_pushed: AtomicU64::new(0),
+            total_popped: AtomicU64::new(0),
+            warn_threshold: None,
+        }
+    }
+}
+
+impl<T> crate::Queue for Queue<T> {
+    type Item = T;
+    fn try_pop(&self) -> Option<T> {
+        self.try_pop()
+    }
+    fn try_push(&self, item: T) -> Result<(), T> {
+        // The item is dropped by `push` if the queue is closed.
+        if self.is_closed() {
+            return Err(item);
         }
+        self.push(item);
+        Ok(())
+    }
+    fn len(&self) -> usize {
+        self.len()
+    }
+    fn is_empty(&self) -> bool {
+        self.is_empty()
+    }
+    fn capacity(&self) -> Option<usize> {
+        None
+    }
+    fn available(&self) -> isize {
+        self.available()
+    }
+    fn close(&self) {
+        self.close()
+    }
+    fn is_closed(&self) -> bool {
+        self.is_closed()
     }
 }
 
 impl<T> FromIterator<T> for Queue<T> {
     fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
-        let queue = SegQueue::new();
+        let queue = Storage::new(Order::Fifo);
         for item in iter {
             queue.push(item);
         }
@@ -127,6 +1515,7 @@ impl<T> FromIterator<T> for Queue<T> {
             queue,
             semaphore: Semaphore::new(size),
             available: Available::new(size.try_into().unwrap()),
+            total_pushed: AtomicU64::new(size as u64),
             ..Self::default()
         }
     }
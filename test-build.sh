@@ -7,7 +7,23 @@ cargo build
 cargo build --no-default-features --features unlimited
 cargo build --no-default-features --features resizable
 cargo build --no-default-features --features limited
+cargo build --no-default-features --features classed
+cargo build --no-default-features --features ttl
 
 cargo test --no-default-features --features unlimited
 cargo test --no-default-features --features resizable
 cargo test --no-default-features --features limited
+
+cargo test --features tracing
+cargo test --features tracing-context
+cargo test --no-default-features --features dedup
+cargo test --features metrics
+cargo test --release --features testing
+cargo test --features tokio-mpsc
+cargo test --features std-mpsc
+cargo test --features broadcast
+cargo test --features cancellation
+cargo test --features pool
+cargo test --release --features loom --test loom
+cargo test --features classed
+cargo test --features ttl
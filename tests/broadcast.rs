@@ -0,0 +1,45 @@
+//! Tests for the adapter enabled by the `broadcast` feature.
+#[cfg(all(feature = "broadcast", feature = "unlimited"))]
+mod tests {
+
+    use std::sync::Arc;
+
+    use deadqueue::unlimited::Queue;
+    use tokio_stream::StreamExt;
+
+    #[tokio::test]
+    async fn test_broadcast_to_all_subscribers() {
+        let queue: Arc<Queue<usize>> = Arc::new(Queue::new());
+        let broadcast = queue.clone().broadcast(16);
+        let first = broadcast.subscribe_stream();
+        let second = broadcast.subscribe_stream();
+        for i in 0..10 {
+            queue.push(i);
+        }
+        queue.close();
+        let first: Vec<usize> = first.collect().await;
+        let second: Vec<usize> = second.collect().await;
+        assert_eq!(first, (0..10).collect::<Vec<_>>());
+        assert_eq!(second, (0..10).collect::<Vec<_>>());
+        assert!(queue.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_broadcast_subscribe_after_close() {
+        let queue: Arc<Queue<usize>> = Arc::new(Queue::new());
+        let broadcast = queue.clone().broadcast(16);
+        let stream = broadcast.subscribe_stream();
+        queue.close();
+        // The stream ends once the popper task has finished.
+        assert_eq!(stream.collect::<Vec<_>>().await, Vec::<usize>::new());
+        let mut stream = Box::pin(broadcast.subscribe_stream());
+        assert_eq!(stream.next().await, None);
+    }
+
+    #[tokio::test]
+    #[should_panic]
+    async fn test_broadcast_zero_capacity() {
+        let queue: Arc<Queue<usize>> = Arc::new(Queue::new());
+        let _broadcast = queue.broadcast(0);
+    }
+}
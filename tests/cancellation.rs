@@ -0,0 +1,184 @@
+//! Tests for the cancellable methods enabled by the `cancellation` feature.
+#[cfg(feature = "cancellation")]
+mod tests {
+
+    use std::sync::Arc;
+
+    use deadqueue::resizable::{Queue, ResizeResult};
+    use tokio_util::sync::CancellationToken;
+
+    #[tokio::test]
+    async fn test_resize_cancelled_midway() {
+        let queue: Arc<Queue<usize>> = Arc::new(Queue::with_capacity(4));
+        for i in 0..4 {
+            queue.try_push(i).unwrap();
+        }
+        let cancel = CancellationToken::new();
+        let resize = {
+            let queue = queue.clone();
+            let cancel = cancel.clone();
+            tokio::spawn(async move { queue.resize_cancellable(0, cancel).await })
+        };
+        assert_eq!(queue.pop().await, 0);
+        while queue.capacity() > 3 {
+            tokio::task::yield_now().await;
+        }
+        cancel.cancel();
+        let result = resize.await.unwrap();
+        assert_eq!(
+            result,
+            ResizeResult {
+                capacity: 3,
+                cancelled: true
+            }
+        );
+        assert_eq!(queue.capacity(), 3);
+        assert_eq!(queue.len(), 3);
+        assert!(queue.is_full());
+        assert!(queue.try_push(4).is_err());
+        assert_eq!(queue.pop().await, 1);
+        queue.try_push(4).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_resize_not_cancelled() {
+        let queue: Queue<usize> = Queue::with_capacity(4);
+        queue.try_push(1).unwrap();
+        let result = queue.resize_cancellable(2, CancellationToken::new()).await;
+        assert_eq!(
+            result,
+            ResizeResult {
+                capacity: 2,
+                cancelled: false
+            }
+        );
+        assert_eq!(queue.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_pop_cancellable_resizable() {
+        let queue: Arc<Queue<usize>> = Arc::new(Queue::with_capacity(2));
+        let token = CancellationToken::new();
+        let pop = {
+            let queue = queue.clone();
+            let token = token.clone();
+            tokio::spawn(async move { queue.pop_cancellable(&token).await })
+        };
+        while queue.available() > -1 {
+            tokio::task::yield_now().await;
+        }
+        token.cancel();
+        assert_eq!(pop.await.unwrap(), None);
+        assert_eq!(queue.available(), 0);
+        queue.push(1).await;
+        assert_eq!(queue.available(), 1);
+        assert_eq!(
+            queue.pop_cancellable(&CancellationToken::new()).await,
+            Some(1)
+        );
+        assert_eq!(queue.available_permits(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_pop_cancellable_already_cancelled() {
+        let queue: Queue<usize> = Queue::with_capacity(2);
+        queue.try_push(1).unwrap();
+        let token = CancellationToken::new();
+        token.cancel();
+        assert_eq!(queue.pop_cancellable(&token).await, None);
+        assert_eq!(queue.len(), 1);
+        assert_eq!(queue.available(), 1);
+    }
+
+    #[cfg(feature = "limited")]
+    #[tokio::test]
+    async fn test_pop_cancellable_limited() {
+        let queue: Arc<deadqueue::limited::Queue<usize>> =
+            Arc::new(deadqueue::limited::Queue::with_capacity(2));
+        let token = CancellationToken::new();
+        let pop = {
+            let queue = queue.clone();
+            let token = token.clone();
+            tokio::spawn(async move { queue.pop_cancellable(&token).await })
+        };
+        while queue.available() > -1 {
+            tokio::task::yield_now().await;
+        }
+        token.cancel();
+        assert_eq!(pop.await.unwrap(), None);
+        assert_eq!(queue.available(), 0);
+        queue.push(1).await;
+        assert_eq!(queue.try_pop(), Some(1));
+        assert_eq!(queue.available_permits(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_pop_cancellable_unlimited() {
+        let queue: Arc<deadqueue::unlimited::Queue<usize>> =
+            Arc::new(deadqueue::unlimited::Queue::new());
+        let token = CancellationToken::new();
+        let pop = {
+            let queue = queue.clone();
+            let token = token.clone();
+            tokio::spawn(async move { queue.pop_cancellable(&token).await })
+        };
+        while queue.available() > -1 {
+            tokio::task::yield_now().await;
+        }
+        token.cancel();
+        assert_eq!(pop.await.unwrap(), None);
+        assert_eq!(queue.available(), 0);
+        queue.push(1);
+        assert_eq!(queue.try_pop(), Some(1));
+    }
+
+    #[tokio::test]
+    async fn test_push_all_cancellable_returns_leftovers() {
+        let queue: Arc<Queue<usize>> = Arc::new(Queue::with_capacity(3));
+        let token = CancellationToken::new();
+        let push = {
+            let queue = queue.clone();
+            let token = token.clone();
+            tokio::spawn(async move { queue.push_all_cancellable((0..10).collect(), &token).await })
+        };
+        assert_eq!(queue.pop().await, 0);
+        assert_eq!(queue.pop().await, 1);
+        while queue.total_pushed() < 5 {
+            tokio::task::yield_now().await;
+        }
+        token.cancel();
+        assert_eq!(push.await.unwrap(), (5..10).collect::<Vec<_>>());
+        assert_eq!(queue.len(), 3);
+        assert_eq!(queue.try_pop(), Some(2));
+        assert_eq!(queue.available_permits(), 1);
+    }
+
+    #[cfg(feature = "limited")]
+    #[tokio::test]
+    async fn test_push_all_cancellable_limited() {
+        let queue: Arc<deadqueue::limited::Queue<usize>> =
+            Arc::new(deadqueue::limited::Queue::with_capacity(3));
+        let token = CancellationToken::new();
+        let push = {
+            let queue = queue.clone();
+            let token = token.clone();
+            tokio::spawn(async move { queue.push_all_cancellable((0..10).collect(), &token).await })
+        };
+        assert_eq!(queue.pop().await, 0);
+        while queue.total_pushed() < 4 {
+            tokio::task::yield_now().await;
+        }
+        token.cancel();
+        assert_eq!(push.await.unwrap(), (4..10).collect::<Vec<_>>());
+        assert_eq!(queue.len(), 3);
+        assert_eq!(queue.push_all_cancellable(vec![10], &token).await, vec![10]);
+        queue.close();
+        assert_eq!(queue.try_pop(), Some(1));
+        assert_eq!(
+            queue
+                .push_all_cancellable(vec![10], &CancellationToken::new())
+                .await,
+            vec![10]
+        );
+    }
+}
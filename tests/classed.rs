@@ -0,0 +1,71 @@
+#[cfg(feature = "classed")]
+mod tests {
+
+    use std::sync::Arc;
+
+    use deadqueue::classed::Queue;
+
+    #[tokio::test]
+    async fn test_weighted_round_robin() {
+        let queue: Queue<u8> = Queue::new(&[3, 1]);
+        for _ in 0..100 {
+            queue.push(0, 0);
+            queue.push(1, 1);
+        }
+        let order: Vec<u8> = (0..8).map(|_| queue.try_pop().unwrap()).collect();
+        assert_eq!(order, vec![0, 0, 0, 1, 0, 0, 0, 1]);
+        let mut counts = [0usize; 2];
+        for _ in 0..92 {
+            counts[usize::from(queue.pop().await)] += 1;
+        }
+        // 100 pops in total while both classes are non-empty
+        assert_eq!(counts[0] + 6, 75);
+        assert_eq!(counts[1] + 2, 25);
+        assert_eq!(queue.class_len(0), 25);
+        assert_eq!(queue.class_len(1), 75);
+    }
+
+    #[tokio::test]
+    async fn test_empty_class_does_not_block() {
+        let queue: Queue<u8> = Queue::new(&[3, 1]);
+        for _ in 0..5 {
+            queue.push(1, 1);
+        }
+        let items: Vec<u8> = (0..5).map(|_| queue.try_pop().unwrap()).collect();
+        assert_eq!(items, vec![1; 5]);
+        assert_eq!(queue.try_pop(), None);
+        assert_eq!(queue.available(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_pop_waits_for_push() {
+        let queue: Arc<Queue<u8>> = Arc::new(Queue::new(&[1, 1]));
+        let popper = {
+            let queue = queue.clone();
+            tokio::spawn(async move { queue.pop().await })
+        };
+        while queue.available() > -1 {
+            tokio::task::yield_now().await;
+        }
+        queue.push(7, 1);
+        assert_eq!(popper.await.unwrap(), 7);
+        assert!(queue.is_empty());
+    }
+
+    #[test]
+    #[should_panic(expected = "unknown class")]
+    fn test_unknown_class() {
+        let queue: Queue<u8> = Queue::new(&[1]);
+        queue.push(0, 1);
+    }
+
+    #[test]
+    fn test_debug() {
+        let queue: Queue<u8> = Queue::new(&[3, 1]);
+        queue.push(0, 1);
+        assert_eq!(
+            format!("{:?}", queue),
+            "Queue { weights: [3, 1], len: 1, available: 1 }"
+        );
+    }
+}
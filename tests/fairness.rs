@@ -0,0 +1,102 @@
+//! Tests for the fair scheduling of tasks calling `pop`.
+//!
+//! The semaphores of the queues are fair. Tasks calling `pop` are assigned
+//! an item in the order they started waiting. The item is taken out of the
+//! queue once the task is polled again. The tests use the single threaded
+//! runtime which polls woken tasks in the order they were woken up, thus
+//! every task receives the item matching its arrival rank.
+#[cfg(feature = "unlimited")]
+mod unlimited {
+
+    use std::sync::Arc;
+
+    use deadqueue::unlimited::Queue;
+
+    const N: usize = 10;
+
+    #[tokio::test]
+    async fn test_fairness_unlimited() {
+        let queue = Arc::new(Queue::new());
+        let mut futures = Vec::new();
+        for rank in 0..N {
+            let future_queue = queue.clone();
+            futures.push(tokio::spawn(
+                async move { (rank, future_queue.pop().await) },
+            ));
+            while queue.available() > -(rank as isize + 1) {
+                tokio::task::yield_now().await;
+            }
+        }
+        for i in 0..N {
+            queue.push(i);
+        }
+        for future in futures {
+            let (rank, item) = future.await.unwrap();
+            assert_eq!(rank, item);
+        }
+    }
+}
+
+#[cfg(feature = "resizable")]
+mod resizable {
+
+    use std::sync::Arc;
+
+    use deadqueue::resizable::Queue;
+
+    const N: usize = 10;
+
+    #[tokio::test]
+    async fn test_fairness_resizable() {
+        let queue = Arc::new(Queue::with_capacity(N));
+        let mut futures = Vec::new();
+        for rank in 0..N {
+            let future_queue = queue.clone();
+            futures.push(tokio::spawn(
+                async move { (rank, future_queue.pop().await) },
+            ));
+            while queue.available() > -(rank as isize + 1) {
+                tokio::task::yield_now().await;
+            }
+        }
+        for i in 0..N {
+            queue.try_push(i).unwrap();
+        }
+        for future in futures {
+            let (rank, item) = future.await.unwrap();
+            assert_eq!(rank, item);
+        }
+    }
+}
+
+#[cfg(feature = "limited")]
+mod limited {
+
+    use std::sync::Arc;
+
+    use deadqueue::limited::Queue;
+
+    const N: usize = 10;
+
+    #[tokio::test]
+    async fn test_fairness_limited() {
+        let queue = Arc::new(Queue::with_capacity(N));
+        let mut futures = Vec::new();
+        for rank in 0..N {
+            let future_queue = queue.clone();
+            futures.push(tokio::spawn(
+                async move { (rank, future_queue.pop().await) },
+            ));
+            while queue.available() > -(rank as isize + 1) {
+                tokio::task::yield_now().await;
+            }
+        }
+        for i in 0..N {
+            queue.try_push(i).unwrap();
+        }
+        for future in futures {
+            let (rank, item) = future.await.unwrap();
+            assert_eq!(rank, item);
+        }
+    }
+}
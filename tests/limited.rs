@@ -1,13 +1,18 @@
 #[cfg(feature = "limited")]
 mod tests {
 
+    use std::future::Future;
+    use std::pin::Pin;
+    use std::sync::atomic::{AtomicUsize, Ordering};
     use std::sync::Arc;
+    use std::task::{Context, Poll};
+    use std::time::Duration;
 
-    use deadqueue::limited::Queue;
+    use deadqueue::limited::{CapacityError, FixedQueue, PollPop, Queue};
 
     #[tokio::test]
     async fn test_basics() {
-        let queue: Queue<usize> = Queue::new(2);
+        let queue: Queue<usize> = Queue::with_capacity(2);
         assert_eq!(queue.len(), 0);
         assert!(queue.try_push(1).is_ok());
         assert_eq!(queue.len(), 1);
@@ -23,7 +28,7 @@ mod tests {
 
     #[tokio::test]
     async fn test_available() {
-        let queue: Queue<usize> = Queue::new(2);
+        let queue: Queue<usize> = Queue::with_capacity(2);
         assert_eq!(queue.len(), 0);
         assert!(queue.try_push(1).is_ok());
         assert_eq!(queue.len(), 1);
@@ -35,7 +40,7 @@ mod tests {
 
     #[tokio::test]
     async fn test_parallel() {
-        let queue: Arc<Queue<usize>> = Arc::new(Queue::new(100));
+        let queue: Arc<Queue<usize>> = Arc::new(Queue::with_capacity(100));
         let mut futures = Vec::new();
         for _ in 0..100usize {
             let queue = queue.clone();
@@ -57,7 +62,7 @@ mod tests {
     #[tokio::test]
     async fn test_parallel_available() {
         const N: usize = 2;
-        let queue: Arc<Queue<usize>> = Arc::new(Queue::new(N));
+        let queue: Arc<Queue<usize>> = Arc::new(Queue::with_capacity(N));
         let barrier = Arc::new(tokio::sync::Barrier::new(N + 1));
         let mut futures = Vec::new();
         for _ in 0..N {
@@ -83,7 +88,7 @@ mod tests {
 
     #[tokio::test]
     async fn test_full() {
-        let queue: Arc<Queue<usize>> = Arc::new(Queue::new(100));
+        let queue: Arc<Queue<usize>> = Arc::new(Queue::with_capacity(100));
         let barrier = Arc::new(tokio::sync::Barrier::new(2));
         let future_queue = queue.clone();
         let future_barrier = barrier.clone();
@@ -102,7 +107,7 @@ mod tests {
 
     #[tokio::test]
     async fn test_empty() {
-        let queue: Arc<Queue<usize>> = Arc::new(Queue::new(100));
+        let queue: Arc<Queue<usize>> = Arc::new(Queue::with_capacity(100));
         for i in 0..100 {
             queue.push(i).await;
         }
@@ -124,7 +129,7 @@ mod tests {
 
     #[tokio::test]
     async fn test_full_deadlock() {
-        let queue: Arc<Queue<()>> = Arc::new(Queue::new(2));
+        let queue: Arc<Queue<()>> = Arc::new(Queue::with_capacity(2));
         let barrier = Arc::new(tokio::sync::Barrier::new(2));
         let future_queue = queue.clone();
         let future_barrier = barrier.clone();
@@ -145,7 +150,7 @@ mod tests {
 
     #[tokio::test]
     async fn test_empty_deadlock() {
-        let queue: Arc<Queue<()>> = Arc::new(Queue::new(3));
+        let queue: Arc<Queue<()>> = Arc::new(Queue::with_capacity(3));
         for _ in 0..2 {
             queue.push(()).await;
         }
@@ -169,7 +174,1244 @@ mod tests {
     #[test]
     fn test_debug() {
         struct NoDebug {}
-        let queue: Queue<NoDebug> = Queue::new(1);
-        format!("{:?}", queue);
+        let queue: Queue<NoDebug> = Queue::with_capacity(1);
+        let debug = format!("{:?}", queue);
+        assert!(debug.contains("len: 0"));
+        assert!(debug.contains("capacity: 1"));
+    }
+
+    #[test]
+    fn test_blocking() {
+        let queue: Arc<Queue<usize>> = Arc::new(Queue::with_capacity(2));
+        let producer_queue = queue.clone();
+        let producer = std::thread::spawn(move || {
+            for i in 0..100 {
+                producer_queue.push_blocking(i);
+            }
+        });
+        for i in 0..100 {
+            assert_eq!(queue.pop_blocking(), i);
+        }
+        producer.join().unwrap();
+        assert_eq!(queue.len(), 0);
+        assert_eq!(queue.available(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_close() {
+        let queue: Queue<usize> = Queue::with_capacity(2);
+        queue.try_push(1).unwrap();
+        assert!(!queue.is_closed());
+        queue.close();
+        assert!(queue.is_closed());
+        assert_eq!(queue.try_push(2), Err(2));
+        queue.push(3).await;
+        assert_eq!(queue.len(), 1);
+        assert_eq!(queue.pop().await, 1);
+    }
+
+    #[tokio::test]
+    async fn test_shutdown() {
+        let queue: Arc<Queue<usize>> = Arc::new(Queue::with_capacity(10));
+        for i in 0..10 {
+            queue.try_push(i).unwrap();
+        }
+        let consumer_queue = queue.clone();
+        tokio::spawn(async move {
+            loop {
+                consumer_queue.pop().await;
+            }
+        });
+        assert_eq!(queue.shutdown(Duration::from_secs(1)).await, Ok(()));
+        assert!(queue.is_closed());
+    }
+
+    #[tokio::test]
+    async fn test_shutdown_timeout() {
+        let queue: Arc<Queue<usize>> = Arc::new(Queue::with_capacity(10));
+        for i in 0..10 {
+            queue.try_push(i).unwrap();
+        }
+        let consumer_queue = queue.clone();
+        tokio::spawn(async move {
+            loop {
+                consumer_queue.pop().await;
+                tokio::time::sleep(Duration::from_millis(50)).await;
+            }
+        });
+        let remaining = queue
+            .shutdown(Duration::from_millis(120))
+            .await
+            .unwrap_err();
+        assert!(remaining > 0 && remaining < 10);
+        assert!(queue.try_push(42).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_available_dropped_pops() {
+        const N: usize = 1000;
+        let queue: Arc<Queue<usize>> = Arc::new(Queue::with_capacity(N));
+        let mut futures = Vec::new();
+        for _ in 0..2 * N {
+            let queue = queue.clone();
+            futures.push(tokio::spawn(async move { queue.pop().await }));
+        }
+        // Wait for all pop futures to be parked
+        while queue.available() > -(2 * N as isize) {
+            tokio::task::yield_now().await;
+        }
+        // Drop every second pop future. Each dropped future must roll
+        // back its pending decrement of the available counter.
+        let (dropped, futures): (Vec<_>, Vec<_>) = futures
+            .into_iter()
+            .enumerate()
+            .partition(|(i, _)| i % 2 == 0);
+        for (_, future) in dropped {
+            future.abort();
+            assert!(future.await.unwrap_err().is_cancelled());
+        }
+        assert_eq!(queue.available(), -(N as isize));
+        for i in 0..N {
+            queue.push(i).await;
+        }
+        for (_, future) in futures {
+            future.await.unwrap();
+        }
+        assert_eq!(queue.len(), 0);
+        assert_eq!(queue.available(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_dropped_pop_empty_notification() {
+        let queue: Arc<Queue<usize>> = Arc::new(Queue::with_capacity(2));
+        let pop_a = tokio::spawn({
+            let queue = queue.clone();
+            async move { queue.pop().await }
+        });
+        let pop_b = tokio::spawn({
+            let queue = queue.clone();
+            async move { queue.pop().await }
+        });
+        while queue.available() > -2 {
+            tokio::task::yield_now().await;
+        }
+        let empty = queue.subscribe_empty();
+        queue.try_push(1).unwrap();
+        queue.try_push(2).unwrap();
+        // Drop the second pop future after it has been assigned an item
+        // but before it could take it out of the queue.
+        pop_b.abort();
+        assert!(pop_b.await.unwrap_err().is_cancelled());
+        assert_eq!(pop_a.await.unwrap(), 1);
+        assert_eq!(queue.len(), 1);
+        assert_eq!(queue.available(), 1);
+        assert!(!empty.has_changed().unwrap());
+        assert_eq!(queue.pop().await, 2);
+        assert!(empty.has_changed().unwrap());
+        assert_eq!(queue.available(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_try_push_n() {
+        let queue: Queue<usize> = Queue::with_capacity(5);
+        queue.try_push(0).unwrap();
+        queue.try_push(1).unwrap();
+        let rest = queue.try_push_n((2..12).collect());
+        assert_eq!(rest, (5..12).collect::<Vec<_>>());
+        assert_eq!(queue.len(), 5);
+        assert_eq!(queue.try_push_n(vec![42]), vec![42]);
+        for i in 0..5 {
+            assert_eq!(queue.pop().await, i);
+        }
+        assert_eq!(queue.try_push_n(vec![1, 2]), vec![]);
+    }
+
+    #[tokio::test]
+    async fn test_reserve() {
+        let queue: Queue<usize> = Queue::with_capacity(3);
+        let mut reservation = queue.reserve(2).await.unwrap();
+        assert_eq!(reservation.remaining(), 2);
+        assert!(queue.try_push(1).is_ok());
+        assert_eq!(queue.try_push(2), Err(2));
+        assert!(reservation.push(3).is_ok());
+        assert_eq!(reservation.remaining(), 1);
+        drop(reservation);
+        assert!(queue.try_push(4).is_ok());
+        assert_eq!(queue.try_push(5), Err(5));
+        assert_eq!(queue.len(), 3);
+        assert_eq!(queue.available(), 3);
+        for item in [1, 3, 4] {
+            assert_eq!(queue.pop().await, item);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_wait_not_full() {
+        let queue: Arc<Queue<usize>> = Arc::new(Queue::with_capacity(2));
+        queue.try_push(1).unwrap();
+        queue.try_push(2).unwrap();
+        let barrier = Arc::new(tokio::sync::Barrier::new(2));
+        let future_queue = queue.clone();
+        let future_barrier = barrier.clone();
+        let future = tokio::spawn(async move {
+            future_barrier.wait().await;
+            assert_eq!(future_queue.try_push(3), Err(3));
+            future_queue.wait_not_full().await;
+            future_queue.try_push(3).unwrap();
+        });
+        barrier.wait().await;
+        assert_eq!(queue.pop().await, 1);
+        future.await.unwrap();
+        assert_eq!(queue.len(), 2);
+        assert_eq!(queue.pop().await, 2);
+        assert_eq!(queue.pop().await, 3);
+    }
+
+    #[tokio::test]
+    async fn test_map() {
+        let queue: Queue<usize> = Queue::with_capacity(2);
+        let queue = queue.map(|item| format!("item {}", item));
+        queue.push(1).await;
+        queue.try_push(2).unwrap();
+        assert_eq!(queue.len(), 2);
+        assert_eq!(queue.pop().await, "item 1");
+        assert_eq!(queue.try_pop(), Some("item 2".to_string()));
+        assert_eq!(queue.try_pop(), None);
+        assert!(queue.into_inner().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_from() {
+        let queue: Queue<usize> = Queue::from(vec![1, 2, 3]);
+        assert_eq!(queue.capacity(), 3);
+        assert!(queue.is_full());
+        assert_eq!(queue.total_pushed(), 3);
+        assert_eq!(queue.pop().await, 1);
+        assert_eq!(queue.try_pop(), Some(2));
+        assert_eq!(queue.pop().await, 3);
+        assert_eq!(queue.try_pop(), None);
+        assert!(queue.try_push(4).is_ok());
+        let queue: Queue<usize> = Queue::from(Vec::new());
+        assert_eq!(queue.capacity(), 0);
+        assert_eq!(queue.try_push(1), Err(1));
+    }
+
+    #[test]
+    fn test_default() {
+        let queue: Queue<usize> = Default::default();
+        assert_eq!(queue.capacity(), 0);
+        assert_eq!(queue.try_push(1), Err(1));
+    }
+
+    #[tokio::test]
+    async fn test_from_iter() {
+        let queue: Queue<usize> = (0..10).filter(|i| i % 2 == 0).collect();
+        assert_eq!(queue.capacity(), 5);
+        assert_eq!(queue.len(), 5);
+        assert_eq!(queue.available(), 5);
+        assert!(queue.is_full());
+        assert_eq!(queue.try_push(42), Err(42));
+        for i in (0..10).step_by(2) {
+            assert_eq!(queue.pop().await, i);
+        }
+        assert_eq!(queue.try_pop(), None);
+        assert!(queue.try_push(42).is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_for_each_concurrent() {
+        const N: usize = 100;
+        const CONCURRENCY: usize = 4;
+        let queue: Arc<Queue<usize>> = Arc::new(Queue::with_capacity(N));
+        for i in 0..N {
+            queue.try_push(i).unwrap();
+        }
+        queue.close();
+        let running = AtomicUsize::new(0);
+        let max_running = AtomicUsize::new(0);
+        let sum = AtomicUsize::new(0);
+        queue
+            .for_each_concurrent(CONCURRENCY, |item| {
+                let running = &running;
+                let max_running = &max_running;
+                let sum = &sum;
+                async move {
+                    let now = running.fetch_add(1, Ordering::Relaxed) + 1;
+                    max_running.fetch_max(now, Ordering::Relaxed);
+                    tokio::task::yield_now().await;
+                    sum.fetch_add(item, Ordering::Relaxed);
+                    running.fetch_sub(1, Ordering::Relaxed);
+                }
+            })
+            .await;
+        assert_eq!(sum.load(Ordering::Relaxed), N * (N - 1) / 2);
+        assert_eq!(max_running.load(Ordering::Relaxed), CONCURRENCY);
+        assert!(queue.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_for_each_concurrent_close() {
+        let queue: Arc<Queue<usize>> = Arc::new(Queue::with_capacity(1));
+        let handled = Arc::new(AtomicUsize::new(0));
+        let consumer = {
+            let queue = queue.clone();
+            let handled = handled.clone();
+            tokio::spawn(async move {
+                queue
+                    .for_each_concurrent(2, |_| async {
+                        handled.fetch_add(1, Ordering::Relaxed);
+                    })
+                    .await
+            })
+        };
+        tokio::task::yield_now().await;
+        queue.try_push(1).unwrap();
+        queue.close();
+        tokio::time::timeout(Duration::from_secs(1), consumer)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(handled.load(Ordering::Relaxed), 1);
+    }
+
+    #[tokio::test]
+    async fn test_occupancy() {
+        let queue: Arc<Queue<usize>> = Arc::new(Queue::with_capacity(2));
+        assert_eq!(queue.occupancy(), (0, 0));
+        let poppers: Vec<_> = (0..3)
+            .map(|_| {
+                let queue = queue.clone();
+                tokio::spawn(async move { queue.pop().await })
+            })
+            .collect();
+        while queue.available() > -3 {
+            tokio::task::yield_now().await;
+        }
+        assert_eq!(queue.occupancy(), (0, 3));
+        queue.try_push(1).unwrap();
+        queue.try_push(2).unwrap();
+        for popper in poppers.into_iter().take(2) {
+            popper.await.unwrap();
+        }
+        assert_eq!(queue.occupancy(), (0, 1));
+        assert_eq!(queue.available(), -1);
+    }
+
+    #[tokio::test]
+    async fn test_zero_capacity() {
+        let queue: Queue<usize> = Queue::with_capacity(0);
+        assert_eq!(queue.capacity(), 0);
+        assert!(queue.is_full());
+        assert_eq!(queue.try_push(1), Err(1));
+        assert!(
+            tokio::time::timeout(Duration::from_millis(10), queue.push(1))
+                .await
+                .is_err()
+        );
+        assert!(queue.is_empty());
+    }
+
+    #[test]
+    fn test_try_with_capacity() {
+        assert_eq!(
+            Queue::<usize>::try_with_capacity(0).err(),
+            Some(CapacityError::Zero)
+        );
+        let queue: Queue<usize> = Queue::try_with_capacity(1).unwrap();
+        assert_eq!(queue.capacity(), 1);
+        assert!(queue.try_push(1).is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_pop_n_timeout() {
+        let queue: Arc<Queue<usize>> = Arc::new(Queue::with_capacity(10));
+        let producer_queue = queue.clone();
+        let producer = tokio::spawn(async move {
+            for i in 0..10 {
+                producer_queue.push(i).await;
+                tokio::time::sleep(Duration::from_millis(20)).await;
+            }
+        });
+        // The cap is reached before the deadline
+        let items = queue.pop_n_timeout(2, Duration::from_secs(10)).await;
+        assert_eq!(items, vec![0, 1]);
+        // The deadline is reached before the cap
+        let start = std::time::Instant::now();
+        let items = queue.pop_n_timeout(100, Duration::from_millis(70)).await;
+        let elapsed = start.elapsed();
+        assert!(!items.is_empty() && items.len() < 8);
+        assert!(elapsed >= Duration::from_millis(70));
+        assert!(elapsed < Duration::from_secs(1));
+        producer.await.unwrap();
+        let items = queue.pop_n_timeout(100, Duration::from_millis(0)).await;
+        assert!(items.iter().all(|&item| item >= 3));
+        assert!(queue
+            .pop_n_timeout(1, Duration::from_millis(10))
+            .await
+            .is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_push_reporting() {
+        let queue: Queue<usize> = Queue::with_capacity(2);
+        let outcome = queue.push_reporting(1).await;
+        assert!(!outcome.became_full);
+        assert_eq!(outcome.len_after, 1);
+        let outcome = queue.push_reporting(2).await;
+        assert!(outcome.became_full);
+        assert_eq!(outcome.len_after, 2);
+        assert!(queue.is_full());
+    }
+
+    #[tokio::test]
+    async fn test_poll_pop() {
+        struct PopFuture<'a, 'q>(Pin<&'a mut PollPop<'q, usize>>);
+        impl Future for PopFuture<'_, '_> {
+            type Output = usize;
+            fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<usize> {
+                self.0.as_mut().poll_pop(cx)
+            }
+        }
+        let queue: Arc<Queue<usize>> = Arc::new(Queue::with_capacity(2));
+        let mut poller = Box::pin(PollPop::new(&queue));
+        queue.try_push(1).unwrap();
+        assert_eq!(PopFuture(poller.as_mut()).await, 1);
+        let producer_queue = queue.clone();
+        let producer = tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+            producer_queue.push(2).await;
+        });
+        assert_eq!(PopFuture(poller.as_mut()).await, 2);
+        producer.await.unwrap();
+        assert_eq!(queue.available(), 0);
+        assert!(queue.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_poll_pop_cancel() {
+        let queue: Queue<usize> = Queue::with_capacity(2);
+        let mut poller = Box::pin(PollPop::new(&queue));
+        assert!(tokio::time::timeout(
+            Duration::from_millis(10),
+            std::future::poll_fn(|cx| poller.as_mut().poll_pop(cx))
+        )
+        .await
+        .is_err());
+        // The pending wait is counted like a waiting `pop`
+        assert_eq!(queue.available(), -1);
+        drop(poller);
+        assert_eq!(queue.available(), 0);
+        // No item is reserved for the abandoned wait
+        queue.try_push(1).unwrap();
+        assert_eq!(queue.try_pop(), Some(1));
+        assert_eq!(queue.available(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_poll_pop_concurrent() {
+        let queue: Arc<Queue<usize>> = Arc::new(Queue::with_capacity(2));
+        let pollers: Vec<_> = (0..2)
+            .map(|_| {
+                let queue = queue.clone();
+                tokio::spawn(async move {
+                    let mut poller = Box::pin(PollPop::new(&queue));
+                    std::future::poll_fn(|cx| poller.as_mut().poll_pop(cx)).await
+                })
+            })
+            .collect();
+        while queue.available() > -2 {
+            tokio::task::yield_now().await;
+        }
+        queue.try_push(1).unwrap();
+        queue.try_push(2).unwrap();
+        let mut items = Vec::new();
+        for poller in pollers {
+            items.push(
+                tokio::time::timeout(Duration::from_secs(1), poller)
+                    .await
+                    .unwrap()
+                    .unwrap(),
+            );
+        }
+        items.sort_unstable();
+        assert_eq!(items, vec![1, 2]);
+        assert_eq!(queue.available(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_poll_pop_fairness() {
+        let queue: Arc<Queue<usize>> = Arc::new(Queue::with_capacity(2));
+        let poller = {
+            let queue = queue.clone();
+            tokio::spawn(async move {
+                let mut poller = Box::pin(PollPop::new(&queue));
+                std::future::poll_fn(|cx| poller.as_mut().poll_pop(cx)).await
+            })
+        };
+        while queue.available() > -1 {
+            tokio::task::yield_now().await;
+        }
+        let popper = {
+            let queue = queue.clone();
+            tokio::spawn(async move { queue.pop().await })
+        };
+        while queue.available() > -2 {
+            tokio::task::yield_now().await;
+        }
+        // Waiting calls to `pop` are served before pollers
+        queue.try_push(1).unwrap();
+        assert_eq!(popper.await.unwrap(), 1);
+        queue.try_push(2).unwrap();
+        assert_eq!(poller.await.unwrap(), 2);
+        assert!(queue.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_late_subscriber() {
+        let queue: Arc<Queue<usize>> = Arc::new(Queue::with_capacity(1));
+        // Cross the full/empty boundary without anybody listening
+        for i in 0..10 {
+            queue.try_push(i).unwrap();
+            queue.try_pop().unwrap();
+        }
+        let mut full = queue.subscribe_full();
+        let mut empty = queue.subscribe_empty();
+        let producer_queue = queue.clone();
+        tokio::spawn(async move {
+            producer_queue.push(1).await;
+        });
+        tokio::time::timeout(Duration::from_secs(1), full.changed())
+            .await
+            .unwrap()
+            .unwrap();
+        queue.pop().await;
+        tokio::time::timeout(Duration::from_secs(1), empty.changed())
+            .await
+            .unwrap()
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_wait_stats() {
+        let queue: Arc<Queue<usize>> = Arc::new(Queue::with_capacity(2).record_waits());
+        queue.try_push(1).unwrap();
+        assert_eq!(queue.pop().await, 1);
+        let producer_queue = queue.clone();
+        tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            producer_queue.try_push(2).unwrap();
+        });
+        assert_eq!(queue.pop().await, 2);
+        let stats = queue.wait_stats();
+        assert_eq!(stats.count, 2);
+        assert!(stats.max >= Duration::from_millis(50));
+        assert!(stats.total >= stats.max);
+    }
+
+    #[tokio::test]
+    async fn test_wait_stats_disabled() {
+        let queue: Queue<usize> = Queue::with_capacity(2);
+        queue.try_push(1).unwrap();
+        queue.pop().await;
+        assert_eq!(queue.wait_stats().count, 0);
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn test_reserve_stress() {
+        const CAPACITY: usize = 8;
+        let queue: Arc<Queue<usize>> = Arc::new(Queue::with_capacity(CAPACITY));
+        let tasks: Vec<_> = (0..4)
+            .map(|task| {
+                let queue = queue.clone();
+                tokio::spawn(async move {
+                    for i in 0..1000 {
+                        // Over-reserve and only use part of the reservation
+                        let reserve = 1 + (task + i) % 4;
+                        let used = (task * i) % (reserve + 1);
+                        let mut reservation = queue.reserve(reserve).await.unwrap();
+                        for item in 0..used {
+                            reservation.push(item).unwrap();
+                        }
+                        assert_eq!(reservation.remaining(), reserve - used);
+                        drop(reservation);
+                        for _ in 0..used {
+                            queue.pop().await;
+                        }
+                    }
+                })
+            })
+            .collect();
+        for task in tasks {
+            task.await.unwrap();
+        }
+        assert_eq!(queue.capacity(), CAPACITY);
+        assert!(queue.is_empty());
+        for i in 0..CAPACITY {
+            queue.try_push(i).unwrap();
+        }
+        assert_eq!(queue.try_push(CAPACITY), Err(CAPACITY));
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn test_wait_empty_race() {
+        let queue: Arc<Queue<usize>> = Arc::new(Queue::with_capacity(1));
+        for i in 0..1000 {
+            queue.try_push(i).unwrap();
+            let consumer_queue = queue.clone();
+            let consumer = tokio::spawn(async move { consumer_queue.pop().await });
+            tokio::time::timeout(Duration::from_secs(1), queue.wait_empty())
+                .await
+                .expect("wait_empty missed the notification");
+            assert_eq!(consumer.await.unwrap(), i);
+        }
+    }
+
+    #[test]
+    fn test_push_overwrite() {
+        let queue: Queue<usize> = Queue::with_capacity(3);
+        assert_eq!(queue.push_overwrite(1), None);
+        assert_eq!(queue.push_overwrite(2), None);
+        assert_eq!(queue.push_overwrite(3), None);
+        assert_eq!(queue.push_overwrite(4), Some(1));
+        assert_eq!(queue.len(), 3);
+        assert_eq!(queue.available(), 3);
+        assert!(queue.is_full());
+        assert_eq!(queue.try_pop(), Some(2));
+        assert_eq!(queue.try_pop(), Some(3));
+        assert_eq!(queue.try_pop(), Some(4));
+        assert_eq!(queue.try_pop(), None);
+        // All slots are free again
+        for i in 0..3 {
+            queue.try_push(i).unwrap();
+        }
+        assert_eq!(queue.try_push(3), Err(3));
+    }
+
+    #[test]
+    fn test_push_overwrite_zero_capacity() {
+        let queue: Queue<usize> = Queue::with_capacity(0);
+        assert_eq!(queue.push_overwrite(1), Some(1));
+        assert!(queue.is_empty());
+    }
+
+    #[cfg(feature = "testing")]
+    #[tokio::test]
+    async fn test_invariants_random_ops() {
+        let queue: Queue<usize> = Queue::with_capacity(8);
+        let mut seed: u32 = 0x2545_f491;
+        for i in 0..1000 {
+            seed ^= seed << 13;
+            seed ^= seed >> 17;
+            seed ^= seed << 5;
+            match seed % 5 {
+                0 | 1 => {
+                    queue.try_push(i).ok();
+                }
+                2 => {
+                    queue.try_pop();
+                }
+                3 => {
+                    if !queue.is_empty() {
+                        queue.pop().await;
+                    }
+                }
+                4 => {
+                    if !queue.is_full() {
+                        queue.push(i).await;
+                    }
+                }
+                _ => unreachable!(),
+            }
+            assert!(queue.check_invariants());
+        }
+    }
+
+    #[tokio::test]
+    async fn test_available_permits() {
+        let queue: Queue<usize> = Queue::with_capacity(3);
+        assert_eq!(queue.available_permits(), 3);
+        queue.try_push(1).unwrap();
+        assert_eq!(queue.available_permits(), 2);
+        queue.try_push(2).unwrap();
+        assert_eq!(queue.available_permits(), 1);
+        queue.pop().await;
+        assert_eq!(queue.available_permits(), 2);
+        queue.pop().await;
+        assert_eq!(queue.available_permits(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_items_ready_pending_pops() {
+        let queue: Arc<Queue<usize>> = Arc::new(Queue::with_capacity(8));
+        let mut poppers = Vec::new();
+        for i in 0..3 {
+            let q = queue.clone();
+            poppers.push(tokio::spawn(async move { q.pop().await }));
+            while queue.pending_pops() < i + 1 {
+                tokio::task::yield_now().await;
+            }
+            assert_eq!(queue.items_ready(), 0);
+        }
+        assert_eq!(queue.pending_pops(), 3);
+        for i in 0..5 {
+            queue.try_push(i).unwrap();
+        }
+        for popper in poppers {
+            popper.await.unwrap();
+        }
+        assert_eq!(queue.items_ready(), 2);
+        assert_eq!(queue.pending_pops(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_fixed_queue() {
+        let queue = FixedQueue::<usize, 4>::new();
+        assert_eq!(queue.capacity(), 4);
+        assert_eq!(FixedQueue::<usize, 4>::CAPACITY, 4);
+        assert_eq!(queue.get_ref().capacity(), 4);
+        for i in 0..4 {
+            queue.try_push(i).unwrap();
+        }
+        assert!(queue.is_full());
+        assert_eq!(queue.try_push(4), Err(4));
+        assert_eq!(queue.pop().await, 0);
+        assert_eq!(queue.len(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_closed() {
+        let queue: Arc<Queue<usize>> = Arc::new(Queue::with_capacity(2));
+        let consumer = {
+            let queue = queue.clone();
+            tokio::spawn(async move { queue.closed().await })
+        };
+        tokio::task::yield_now().await;
+        assert!(!consumer.is_finished());
+        let closer = queue.clone();
+        tokio::spawn(async move { closer.close() }).await.unwrap();
+        tokio::time::timeout(Duration::from_secs(1), consumer)
+            .await
+            .unwrap()
+            .unwrap();
+        // Resolves immediately once closed
+        queue.closed().await;
+    }
+
+    #[tokio::test]
+    async fn test_drain_iter() {
+        let queue: Queue<usize> = Queue::with_capacity(8);
+        for i in 0..5 {
+            queue.try_push(i).unwrap();
+        }
+        assert_eq!(queue.drain_iter().collect::<Vec<_>>(), vec![0, 1, 2, 3, 4]);
+        assert!(queue.is_empty());
+        assert_eq!(queue.available(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_push_boxed_roundtrip() {
+        let queue: Queue<Box<[u8; 4096]>> = Queue::with_capacity(2);
+        let item = Box::new([7u8; 4096]);
+        let ptr: *const [u8; 4096] = &*item;
+        queue.push_boxed(item).await;
+        let item = queue.try_pop().unwrap();
+        assert!(std::ptr::eq(ptr, &*item));
+        queue.push_boxed(item).await;
+        assert_eq!(queue.pop_unboxed().await, [7u8; 4096]);
+        assert_eq!(queue.try_pop_unboxed(), None);
+    }
+
+    #[tokio::test]
+    async fn test_wait_until_len_range() {
+        let queue: Arc<Queue<usize>> = Arc::new(Queue::with_capacity(8));
+        let waiter = {
+            let queue = queue.clone();
+            tokio::spawn(
+                async move { queue.wait_until(|stats| (3..=5).contains(&stats.len)).await },
+            )
+        };
+        for i in 0..2 {
+            queue.push(i).await;
+            tokio::task::yield_now().await;
+            assert!(!waiter.is_finished());
+        }
+        queue.push(2).await;
+        tokio::time::timeout(Duration::from_secs(1), waiter)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(queue.stats_snapshot().len, 3);
+    }
+
+    #[tokio::test]
+    async fn test_wait_len() {
+        let queue: Arc<Queue<usize>> = Arc::new(Queue::with_capacity(8));
+        let batch = {
+            let queue = queue.clone();
+            tokio::spawn(async move {
+                queue.wait_len(3).await;
+                queue.drain_iter().collect::<Vec<_>>()
+            })
+        };
+        let single = {
+            let queue = queue.clone();
+            tokio::spawn(async move { queue.wait_not_empty().await })
+        };
+        tokio::task::yield_now().await;
+        assert!(!single.is_finished());
+        queue.push(0).await;
+        single.await.unwrap();
+        queue.push(1).await;
+        tokio::task::yield_now().await;
+        assert!(!batch.is_finished());
+        queue.push(2).await;
+        let items = tokio::time::timeout(Duration::from_secs(1), batch)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(items, vec![0, 1, 2]);
+    }
+
+    #[tokio::test]
+    async fn test_try_pop_slice() {
+        let queue: Queue<usize> = Queue::with_capacity(8);
+        for i in 0..3 {
+            queue.try_push(i).unwrap();
+        }
+        let mut out = [None; 8];
+        assert_eq!(queue.try_pop_slice(&mut out), 3);
+        assert_eq!(out[..4], [Some(0), Some(1), Some(2), None]);
+        assert!(queue.is_empty());
+        assert_eq!(queue.available(), 0);
+        for i in 0..3 {
+            queue.try_push(i).unwrap();
+        }
+        let mut out = [None; 2];
+        assert_eq!(queue.try_pop_slice(&mut out), 2);
+        assert_eq!(out, [Some(0), Some(1)]);
+        assert_eq!(queue.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_close_wakes_poppers() {
+        let queue: Arc<Queue<usize>> = Arc::new(Queue::with_capacity(8));
+        let mut poppers = Vec::new();
+        for i in 0..5 {
+            let q = queue.clone();
+            poppers.push(tokio::spawn(async move { q.pop_or_closed().await }));
+            while queue.pending_pops() < i + 1 {
+                tokio::task::yield_now().await;
+            }
+        }
+        queue.close();
+        for popper in poppers {
+            let item = tokio::time::timeout(Duration::from_secs(1), popper)
+                .await
+                .unwrap()
+                .unwrap();
+            assert_eq!(item, None);
+        }
+        assert_eq!(queue.available(), 0);
+        assert_eq!(queue.pending_pops(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_subscribe_len() {
+        let queue: Queue<usize> = Queue::with_capacity(8);
+        queue.push(0).await;
+        let mut len = queue.subscribe_len();
+        assert_eq!(*len.borrow(), 1);
+        assert!(!len.has_changed().unwrap());
+        queue.push(1).await;
+        len.changed().await.unwrap();
+        assert_eq!(*len.borrow_and_update(), 2);
+        queue.pop().await;
+        queue.pop().await;
+        // Updates are coalesced
+        len.changed().await.unwrap();
+        assert_eq!(*len.borrow_and_update(), 0);
+        assert!(!len.has_changed().unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_dropped_push_loses_item() {
+        let queue: Queue<usize> = Queue::with_capacity(1);
+        queue.try_push(1).unwrap();
+        let result = tokio::time::timeout(Duration::from_millis(10), queue.push(2)).await;
+        assert!(result.is_err());
+        assert_eq!(queue.len(), 1);
+        assert_eq!(queue.try_pop(), Some(1));
+        assert_eq!(queue.try_pop(), None);
+    }
+
+    #[tokio::test]
+    async fn test_push_cancellable() {
+        let queue: Queue<usize> = Queue::with_capacity(1);
+        queue.try_push(1).unwrap();
+        let (push, returned) = queue.push_cancellable(2);
+        let result = tokio::time::timeout(Duration::from_millis(10), push).await;
+        assert!(result.is_err());
+        assert_eq!(returned.await, Ok(2));
+        assert_eq!(queue.len(), 1);
+        // A completed push does not send anything back
+        assert_eq!(queue.pop().await, 1);
+        let (push, returned) = queue.push_cancellable(3);
+        push.await;
+        assert!(returned.await.is_err());
+        assert_eq!(queue.try_pop(), Some(3));
+        // A closed queue gives the item back
+        queue.close();
+        let (push, returned) = queue.push_cancellable(4);
+        push.await;
+        assert_eq!(returned.await, Ok(4));
+    }
+
+    #[tokio::test]
+    async fn test_spin_pop() {
+        let queue: Queue<usize> = Queue::with_capacity(2);
+        queue.try_push(1).unwrap();
+        assert_eq!(queue.spin_pop(1), Some(1));
+        assert_eq!(queue.spin_pop(100), None);
+        assert_eq!(queue.spin_pop(0), None);
+        assert_eq!(queue.available(), 0);
+    }
+
+    #[cfg(feature = "testing")]
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn test_permit_balance_stress() {
+        let queue: Arc<Queue<usize>> = Arc::new(Queue::with_capacity(8));
+        let mut tasks = Vec::new();
+        for _ in 0..4 {
+            let queue = queue.clone();
+            tasks.push(tokio::spawn(async move {
+                for i in 0..500 {
+                    match i % 3 {
+                        0 => queue.push(i).await,
+                        1 => {
+                            if let Err(item) = queue.try_push(i) {
+                                queue.push(item).await;
+                            }
+                        }
+                        _ => {
+                            let mut reservation = queue.reserve(1).await.unwrap();
+                            reservation.push(i).unwrap();
+                        }
+                    }
+                }
+            }));
+        }
+        for _ in 0..4 {
+            let queue = queue.clone();
+            tasks.push(tokio::spawn(async move {
+                for i in 0..500 {
+                    if i % 2 == 0 || queue.try_pop().is_none() {
+                        queue.pop().await;
+                    }
+                }
+            }));
+        }
+        for task in tasks {
+            task.await.unwrap();
+        }
+        assert!(queue.is_empty());
+        assert_eq!(queue.debug_permit_balance(), 0);
+        assert_eq!(queue.available_permits(), queue.capacity());
+    }
+
+    #[tokio::test]
+    async fn test_idle_duration() {
+        let queue: Queue<usize> = Queue::with_capacity(2);
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        assert!(queue.idle_duration() >= Duration::from_millis(20));
+        queue.push(1).await;
+        assert_eq!(queue.pop().await, 1);
+        assert!(queue.idle_duration() < Duration::from_millis(20));
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert!(queue.idle_duration() >= Duration::from_millis(50));
+        assert_eq!(queue.try_pop(), None);
+        assert!(queue.idle_duration() >= Duration::from_millis(50));
+    }
+
+    #[tokio::test]
+    async fn test_is_full_permits() {
+        let queue: Queue<usize> = Queue::with_capacity(3);
+        for i in 0..10 {
+            let full = queue.is_full_permits();
+            assert_eq!(queue.try_push(i).is_err(), full);
+            if i % 3 == 2 {
+                queue.try_pop();
+            }
+        }
+        let queue: Queue<usize> = Queue::with_capacity(2);
+        let reservation = queue.reserve(2).await.unwrap();
+        assert!(!queue.is_full());
+        assert!(queue.is_full_permits());
+        assert_eq!(queue.try_push(1), Err(1));
+        drop(reservation);
+        assert!(!queue.is_full_permits());
+        assert_eq!(queue.try_push(1), Ok(()));
+    }
+
+    #[tokio::test]
+    async fn test_try_pop_with_waiters() {
+        let queue: Arc<Queue<usize>> = Arc::new(Queue::with_capacity(4));
+        assert_eq!(queue.try_pop_with_waiters(), None);
+        let poppers: Vec<_> = (0..2)
+            .map(|_| {
+                let queue = queue.clone();
+                tokio::spawn(async move { queue.pop().await })
+            })
+            .collect();
+        while queue.pending_pops() < 2 {
+            tokio::task::yield_now().await;
+        }
+        // The only item is handed to one of the parked poppers.
+        queue.push(0).await;
+        assert_eq!(queue.try_pop_with_waiters(), None);
+        assert_eq!(queue.pending_pops(), 1);
+        // The remaining popper is served first and the next item is
+        // free to be taken.
+        queue.push(1).await;
+        queue.push(2).await;
+        queue.push(3).await;
+        let (_, waiters) = queue.try_pop_with_waiters().unwrap();
+        assert_eq!(waiters, 0);
+        for popper in poppers {
+            popper.await.unwrap();
+        }
+        assert_eq!(
+            queue.try_pop_with_waiters().map(|(_, waiters)| waiters),
+            Some(0)
+        );
+        assert_eq!(queue.try_pop_with_waiters(), None);
+    }
+
+    #[tokio::test]
+    async fn test_subscribe_repeated_transitions() {
+        let queue: Arc<Queue<usize>> = Arc::new(Queue::with_capacity(2));
+        let mut full = queue.subscribe_full();
+        let mut empty = queue.subscribe_empty();
+        for round in 0..2 {
+            let producer_queue = queue.clone();
+            tokio::spawn(async move {
+                producer_queue.push(round).await;
+                producer_queue.push(round).await;
+            });
+            tokio::time::timeout(Duration::from_secs(1), full.changed())
+                .await
+                .unwrap()
+                .unwrap();
+            let consumer_queue = queue.clone();
+            tokio::spawn(async move {
+                consumer_queue.pop().await;
+                consumer_queue.pop().await;
+            });
+            tokio::time::timeout(Duration::from_secs(1), empty.changed())
+                .await
+                .unwrap()
+                .unwrap();
+            assert!(queue.is_empty());
+        }
+    }
+
+    #[test]
+    fn test_try_pop_delta() {
+        let queue: Queue<usize> = Queue::with_capacity(3);
+        assert_eq!(queue.try_pop_delta(), None);
+        for i in 0..3 {
+            queue.try_push(i).unwrap();
+        }
+        for i in 0..3 {
+            let (item, len_before, len_after) = queue.try_pop_delta().unwrap();
+            assert_eq!(item, i);
+            assert_eq!(len_before, len_after + 1);
+            assert_eq!(len_after, 2 - i);
+        }
+        assert_eq!(queue.try_pop_delta(), None);
+    }
+
+    #[test]
+    fn test_with_name() {
+        let queue: Queue<usize> = Queue::with_capacity(1);
+        assert_eq!(queue.name(), None);
+        let queue = queue.with_name("jobs");
+        assert_eq!(queue.name(), Some("jobs"));
+        assert!(format!("{:?}", queue).contains("name: Some(\"jobs\")"));
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn test_total_pushed_popped() {
+        let queue: Arc<Queue<usize>> = Arc::new(Queue::with_capacity(1000));
+        let mut tasks = Vec::new();
+        for t in 0..4 {
+            let queue = queue.clone();
+            tasks.push(tokio::spawn(async move {
+                for i in 0..250 {
+                    queue.push(i).await;
+                    if (t + i) % 3 != 0 {
+                        queue.try_pop();
+                    }
+                }
+            }));
+        }
+        for task in tasks {
+            task.await.unwrap();
+        }
+        assert_eq!(queue.total_pushed(), 1000);
+        assert_eq!(
+            queue.total_pushed(),
+            queue.total_popped() + queue.len() as u64
+        );
+        assert!(format!("{:?}", queue).contains("total_pushed: 1000"));
+    }
+
+    #[tokio::test]
+    async fn test_push_all() {
+        let queue: Arc<Queue<usize>> = Arc::new(Queue::with_capacity(3));
+        let push = {
+            let queue = queue.clone();
+            tokio::spawn(async move { queue.push_all((0..10).collect()).await })
+        };
+        for i in 0..10 {
+            assert_eq!(queue.pop().await, i);
+        }
+        assert_eq!(push.await.unwrap(), Ok(()));
+        queue.try_push(10).unwrap();
+        queue.close();
+        assert_eq!(queue.push_all(vec![11, 12]).await, Err(vec![11, 12]));
+        assert_eq!(queue.len(), 1);
+    }
+
+    #[test]
+    fn test_try_reserve_slots() {
+        let queue: Queue<usize> = Queue::with_capacity(3);
+        queue.try_push(1).unwrap();
+        assert!(queue.try_reserve_slots(3).is_none());
+        assert_eq!(queue.capacity(), 3);
+        assert_eq!(queue.available_permits(), 2);
+        let mut reservation = queue.try_reserve_slots(2).unwrap();
+        assert_eq!(queue.available_permits(), 0);
+        assert_eq!(queue.try_push(2), Err(2));
+        assert!(reservation.push(3).is_ok());
+        drop(reservation);
+        assert_eq!(queue.available_permits(), 1);
+        assert_eq!(queue.len(), 2);
+        assert!(queue.try_reserve_slots(0).is_some());
+    }
+
+    #[test]
+    fn test_inner() {
+        let queue: Queue<usize> = Queue::with_capacity(4);
+        queue.try_push(1).unwrap();
+        queue.try_push(2).unwrap();
+        // SAFETY: The inner queue is only read.
+        let inner = unsafe { queue.inner() };
+        assert_eq!(inner.len(), queue.len());
+        assert_eq!(inner.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_from_slice() {
+        let queue: Queue<usize> = Queue::from_slice(&[1, 2, 3]);
+        assert_eq!(queue.capacity(), 3);
+        assert_eq!(queue.len(), 3);
+        assert_eq!(queue.available(), 3);
+        assert!(queue.is_full());
+        assert_eq!(queue.try_push(4), Err(4));
+        for i in 1..=3 {
+            assert_eq!(queue.pop().await, i);
+        }
+        assert_eq!(queue.try_pop(), None);
+        assert!(queue.try_push(4).is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_from_array() {
+        let queue: Queue<String> = Queue::from_array(["a".to_string(), "b".to_string()]);
+        assert_eq!(queue.capacity(), 2);
+        assert!(queue.is_full());
+        assert_eq!(queue.pop().await, "a");
+        assert_eq!(queue.pop().await, "b");
+        let empty: Queue<usize> = Queue::from_array([]);
+        assert_eq!(empty.capacity(), 0);
+        assert_eq!(empty.try_push(1), Err(1));
+    }
+
+    #[test]
+    fn test_try_pop_find() {
+        let queue: Queue<usize> = Queue::with_capacity(4);
+        for i in 1..=4 {
+            queue.try_push(i).unwrap();
+        }
+        assert_eq!(queue.try_pop_find(|item| item % 3 == 0), Some(3));
+        assert_eq!(queue.len(), 1);
+        assert_eq!(queue.available(), 1);
+        assert_eq!(queue.available_permits(), 3);
+        assert_eq!(queue.try_pop_find(|item| *item > 10), None);
+        assert!(queue.is_empty());
+        assert_eq!(queue.available(), 0);
+        assert_eq!(queue.available_permits(), 4);
+    }
+
+    #[test]
+    fn test_try_with_capacity_too_large() {
+        assert_eq!(
+            Queue::<usize>::try_with_capacity(usize::MAX).err(),
+            Some(CapacityError::TooLarge)
+        );
+        assert_eq!(
+            Queue::<usize>::try_with_capacity(usize::MAX / 8).err(),
+            Some(CapacityError::TooLarge)
+        );
+        assert_eq!(
+            Queue::<[u8; 1 << 20]>::try_with_capacity(1 << 45).err(),
+            Some(CapacityError::TooLarge)
+        );
+        assert_eq!(
+            CapacityError::TooLarge.to_string(),
+            "queue capacity is too large"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_drain_for() {
+        let queue: Queue<usize> = Queue::with_capacity(100);
+        for i in 0..100 {
+            queue.try_push(i).unwrap();
+        }
+        assert_eq!(queue.drain_for(Duration::ZERO).await, vec![]);
+        assert!(queue.is_full());
+        let items = queue.drain_for(Duration::from_secs(1)).await;
+        assert_eq!(items, (0..100).collect::<Vec<_>>());
+        assert!(queue.is_empty());
+        assert_eq!(queue.available_permits(), 100);
+    }
+
+    #[tokio::test]
+    async fn test_idle_duration_first_query() {
+        let queue: Queue<usize> = Queue::with_capacity(2);
+        queue.push(1).await;
+        assert_eq!(queue.pop().await, 1);
+        // The pop is timed when it happens and not when this is called.
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert!(queue.idle_duration() >= Duration::from_millis(50));
+    }
+
+    #[tokio::test]
+    async fn test_reserve_closed() {
+        let queue: Arc<Queue<usize>> = Arc::new(Queue::with_capacity(1));
+        queue.try_push(1).unwrap();
+        let reserve = {
+            let queue = queue.clone();
+            tokio::spawn(async move { queue.reserve(1).await.is_none() })
+        };
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        assert!(!reserve.is_finished());
+        queue.close();
+        assert!(tokio::time::timeout(Duration::from_secs(1), reserve)
+            .await
+            .unwrap()
+            .unwrap());
+        assert!(queue.reserve(0).await.is_none());
     }
 }
@@ -0,0 +1,36 @@
+//! Model tests of the `Available` counter enabled by the `loom` feature.
+//!
+//! Run them via `cargo test --release --features loom --test loom`.
+#[cfg(all(feature = "loom", feature = "unlimited"))]
+mod tests {
+
+    use loom::sync::Arc;
+    use loom::thread;
+
+    use deadqueue::unlimited::Queue;
+
+    #[test]
+    fn test_two_pushers_one_popper() {
+        loom::model(|| {
+            let queue: Arc<Queue<usize>> = Arc::new(Queue::new());
+            let pushers: Vec<_> = (1..=2)
+                .map(|i| {
+                    let queue = queue.clone();
+                    thread::spawn(move || queue.push(i))
+                })
+                .collect();
+            let popper = {
+                let queue = queue.clone();
+                thread::spawn(move || queue.try_pop())
+            };
+            for pusher in pushers {
+                pusher.join().unwrap();
+            }
+            let mut items: Vec<usize> = popper.join().unwrap().into_iter().collect();
+            items.extend(queue.drain_iter());
+            items.sort_unstable();
+            assert_eq!(items, vec![1, 2]);
+            assert_eq!(queue.available(), 0);
+        });
+    }
+}
@@ -0,0 +1,110 @@
+//! Tests for the statistics exported when the `metrics` feature is enabled.
+#[cfg(all(feature = "metrics", feature = "limited"))]
+mod tests {
+
+    use std::collections::HashMap;
+    #[cfg(feature = "resizable")]
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::time::Duration;
+
+    use deadqueue::limited::Queue;
+    #[cfg(feature = "resizable")]
+    use metrics::{
+        Counter, Gauge, Histogram, Key, KeyName, Metadata, Recorder, SharedString, Unit,
+    };
+    use metrics_util::debugging::{DebugValue, DebuggingRecorder, Snapshotter};
+
+    /// Take a snapshot of all metrics of the queue named `jobs`. The keys
+    /// of the returned map are the metric names with the `op` label
+    /// appended if present. Taking a snapshot resets all values.
+    fn snapshot(snapshotter: &Snapshotter) -> HashMap<String, f64> {
+        snapshotter
+            .snapshot()
+            .into_vec()
+            .into_iter()
+            .filter(|(key, _, _, _)| {
+                key.key()
+                    .labels()
+                    .any(|label| label.key() == "queue" && label.value() == "jobs")
+            })
+            .map(|(key, _, _, value)| {
+                let mut name = key.key().name().to_owned();
+                if let Some(op) = key.key().labels().find(|label| label.key() == "op") {
+                    name = format!("{}:{}", name, op.value());
+                }
+                let value = match value {
+                    DebugValue::Gauge(value) => value.0,
+                    DebugValue::Counter(value) => value as f64,
+                    DebugValue::Histogram(_) => unreachable!(),
+                };
+                (name, value)
+            })
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_metrics() {
+        let recorder = DebuggingRecorder::new();
+        let snapshotter = recorder.snapshotter();
+        let queue: Queue<usize> =
+            metrics::with_local_recorder(&recorder, || Queue::new_named(2, "jobs"));
+        queue.try_push(1).unwrap();
+        queue.push(2).await;
+        assert!(
+            tokio::time::timeout(Duration::from_millis(10), queue.push(3))
+                .await
+                .is_err()
+        );
+        let metrics = snapshot(&snapshotter);
+        assert_eq!(metrics["deadqueue_capacity"], 2.0);
+        assert_eq!(metrics["deadqueue_len"], 2.0);
+        assert_eq!(metrics["deadqueue_pushes_total"], 2.0);
+        assert_eq!(metrics["deadqueue_blocked_total:push"], 1.0);
+        assert_eq!(metrics["deadqueue_blocked_total:pop"], 0.0);
+        assert_eq!(queue.pop().await, 1);
+        assert_eq!(queue.try_pop(), Some(2));
+        let metrics = snapshot(&snapshotter);
+        assert_eq!(metrics["deadqueue_len"], 0.0);
+        assert_eq!(metrics["deadqueue_pops_total"], 2.0);
+        assert_eq!(metrics["deadqueue_pushes_total"], 0.0);
+    }
+
+    /// Recorder which only counts the registered metrics
+    #[cfg(feature = "resizable")]
+    #[derive(Default)]
+    struct CountingRecorder(AtomicUsize);
+
+    #[cfg(feature = "resizable")]
+    impl Recorder for CountingRecorder {
+        fn describe_counter(&self, _: KeyName, _: Option<Unit>, _: SharedString) {}
+        fn describe_gauge(&self, _: KeyName, _: Option<Unit>, _: SharedString) {}
+        fn describe_histogram(&self, _: KeyName, _: Option<Unit>, _: SharedString) {}
+        fn register_counter(&self, _: &Key, _: &Metadata<'_>) -> Counter {
+            self.0.fetch_add(1, Ordering::Relaxed);
+            Counter::noop()
+        }
+        fn register_gauge(&self, _: &Key, _: &Metadata<'_>) -> Gauge {
+            self.0.fetch_add(1, Ordering::Relaxed);
+            Gauge::noop()
+        }
+        fn register_histogram(&self, _: &Key, _: &Metadata<'_>) -> Histogram {
+            self.0.fetch_add(1, Ordering::Relaxed);
+            Histogram::noop()
+        }
+    }
+
+    #[cfg(feature = "resizable")]
+    #[test]
+    fn test_resizable_registers_metrics_once() {
+        let limited = CountingRecorder::default();
+        metrics::with_local_recorder(&limited, || Queue::<usize>::new_named(2, "jobs"));
+        let resizable = CountingRecorder::default();
+        metrics::with_local_recorder(&resizable, || {
+            deadqueue::resizable::Queue::<usize>::new_named(2, "jobs")
+        });
+        assert_eq!(
+            resizable.0.load(Ordering::Relaxed),
+            limited.0.load(Ordering::Relaxed)
+        );
+    }
+}
@@ -0,0 +1,42 @@
+//! Tests for the adapters enabled by the `tokio-mpsc` feature.
+#[cfg(all(feature = "tokio-mpsc", feature = "limited"))]
+mod tests {
+
+    use std::sync::Arc;
+
+    use deadqueue::limited::Queue;
+    use tokio::sync::mpsc;
+
+    #[tokio::test]
+    async fn test_forward_through_queue() {
+        let queue: Arc<Queue<usize>> = Arc::new(Queue::with_capacity(2));
+        let (in_tx, in_rx) = mpsc::channel(2);
+        let (out_tx, mut out_rx) = mpsc::channel(2);
+        let forward_from = queue.clone().forward_from(in_rx);
+        let forward_to = queue.clone().forward_to(out_tx);
+        let producer = tokio::spawn(async move {
+            for i in 0..100 {
+                in_tx.send(i).await.unwrap();
+            }
+        });
+        for i in 0..100 {
+            assert_eq!(out_rx.recv().await, Some(i));
+        }
+        producer.await.unwrap();
+        forward_from.await.unwrap();
+        queue.close();
+        forward_to.await.unwrap();
+        assert_eq!(out_rx.recv().await, None);
+    }
+
+    #[tokio::test]
+    async fn test_forward_to_stops_when_receiver_dropped() {
+        let queue: Arc<Queue<usize>> = Arc::new(Queue::with_capacity(2));
+        let (tx, rx) = mpsc::channel(1);
+        drop(rx);
+        let forward_to = queue.clone().forward_to(tx);
+        queue.push(1).await;
+        forward_to.await.unwrap();
+        assert!(queue.is_empty());
+    }
+}
@@ -0,0 +1,62 @@
+//! Tests for the worker pool enabled by the `pool` feature.
+#[cfg(all(feature = "pool", feature = "limited"))]
+mod tests {
+
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::{Arc, Mutex};
+
+    use deadqueue::limited::Queue;
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn test_worker_pool_processes_every_item_once() {
+        let queue: Arc<Queue<usize>> = Arc::new(Queue::with_capacity(16));
+        let seen = Arc::new(Mutex::new(vec![0usize; 1000]));
+        let pool = {
+            let seen = seen.clone();
+            queue.worker_pool(10, move |item| {
+                let seen = seen.clone();
+                async move {
+                    seen.lock().unwrap()[item] += 1;
+                    tokio::task::yield_now().await;
+                }
+            })
+        };
+        assert_eq!(pool.workers(), 10);
+        for i in 0..1000 {
+            pool.queue().push(i).await;
+        }
+        pool.join().await;
+        assert!(seen.lock().unwrap().iter().all(|&count| count == 1));
+    }
+
+    #[tokio::test]
+    async fn test_worker_pool_join_drains_queue() {
+        let queue: Arc<Queue<usize>> = Arc::new(Queue::with_capacity(8));
+        for i in 0..8 {
+            queue.try_push(i).unwrap();
+        }
+        let processed = Arc::new(AtomicUsize::new(0));
+        let pool = {
+            let processed = processed.clone();
+            queue.clone().worker_pool(2, move |_| {
+                let processed = processed.clone();
+                async move {
+                    processed.fetch_add(1, Ordering::Relaxed);
+                }
+            })
+        };
+        pool.join().await;
+        assert_eq!(processed.load(Ordering::Relaxed), 8);
+        assert!(queue.is_closed());
+        assert!(queue.is_empty());
+    }
+
+    #[tokio::test]
+    #[should_panic(expected = "worker failed")]
+    async fn test_worker_pool_join_resumes_panic() {
+        let queue: Arc<Queue<usize>> = Arc::new(Queue::with_capacity(1));
+        let pool = queue.worker_pool(1, |_| async { panic!("worker failed") });
+        pool.queue().push(1).await;
+        pool.join().await;
+    }
+}
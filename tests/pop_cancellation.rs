@@ -0,0 +1,123 @@
+//! Tests making sure that dropping a `pop` future never loses an item.
+
+#![cfg(any(feature = "unlimited", feature = "limited", feature = "resizable"))]
+
+use std::future::Future;
+
+/// Poll the future exactly once and assert that it is still pending.
+async fn poll_once<F: Future + Unpin>(future: &mut F) {
+    tokio::select! {
+        biased;
+        _ = future => panic!("future completed unexpectedly"),
+        _ = std::future::ready(()) => {}
+    }
+}
+
+#[cfg(feature = "unlimited")]
+mod unlimited {
+
+    use deadqueue::unlimited::Queue;
+
+    use super::poll_once;
+
+    #[tokio::test]
+    async fn test_pop_dropped_while_waiting() {
+        let queue: Queue<usize> = Queue::new();
+        let mut pop = Box::pin(queue.pop());
+        poll_once(&mut pop).await;
+        assert_eq!(queue.available(), -1);
+        drop(pop);
+        assert_eq!(queue.available(), 0);
+        queue.push(1);
+        assert_eq!(queue.available(), 1);
+        assert_eq!(queue.pop().await, 1);
+        assert_eq!(queue.available(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_pop_dropped_after_item_arrived() {
+        let queue: Queue<usize> = Queue::new();
+        let mut pop = Box::pin(queue.pop());
+        poll_once(&mut pop).await;
+        queue.push(1);
+        drop(pop);
+        assert_eq!(queue.len(), 1);
+        assert_eq!(queue.available(), 1);
+        assert_eq!(queue.try_pop(), Some(1));
+        assert_eq!(queue.available(), 0);
+    }
+}
+
+#[cfg(feature = "limited")]
+mod limited {
+
+    use deadqueue::limited::Queue;
+
+    use super::poll_once;
+
+    #[tokio::test]
+    async fn test_pop_dropped_while_waiting() {
+        let queue: Queue<usize> = Queue::with_capacity(2);
+        let mut pop = Box::pin(queue.pop());
+        poll_once(&mut pop).await;
+        assert_eq!(queue.available(), -1);
+        drop(pop);
+        assert_eq!(queue.available(), 0);
+        queue.push(1).await;
+        assert_eq!(queue.available(), 1);
+        assert_eq!(queue.pop().await, 1);
+        assert_eq!(queue.available(), 0);
+        assert_eq!(queue.available_permits(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_pop_dropped_after_item_arrived() {
+        let queue: Queue<usize> = Queue::with_capacity(2);
+        let mut pop = Box::pin(queue.pop());
+        poll_once(&mut pop).await;
+        queue.push(1).await;
+        drop(pop);
+        assert_eq!(queue.len(), 1);
+        assert_eq!(queue.available(), 1);
+        assert_eq!(queue.try_pop(), Some(1));
+        assert_eq!(queue.available(), 0);
+        assert_eq!(queue.available_permits(), 2);
+    }
+}
+
+#[cfg(feature = "resizable")]
+mod resizable {
+
+    use deadqueue::resizable::Queue;
+
+    use super::poll_once;
+
+    #[tokio::test]
+    async fn test_pop_dropped_while_waiting() {
+        let queue: Queue<usize> = Queue::with_capacity(2);
+        let mut pop = Box::pin(queue.pop());
+        poll_once(&mut pop).await;
+        assert_eq!(queue.available(), -1);
+        drop(pop);
+        assert_eq!(queue.available(), 0);
+        queue.push(1).await;
+        assert_eq!(queue.available(), 1);
+        assert_eq!(queue.pop().await, 1);
+        assert_eq!(queue.available(), 0);
+        assert_eq!(queue.available_permits(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_pop_dropped_after_item_arrived() {
+        let queue: Queue<usize> = Queue::with_capacity(2);
+        let mut pop = Box::pin(queue.pop());
+        poll_once(&mut pop).await;
+        queue.push(1).await;
+        drop(pop);
+        assert_eq!(queue.len(), 1);
+        assert_eq!(queue.available(), 1);
+        assert_eq!(queue.try_pop(), Some(1));
+        assert_eq!(queue.available(), 0);
+        assert_eq!(queue.available_permits(), 2);
+    }
+}
@@ -0,0 +1,48 @@
+//! Tests for the `Queue` trait shared by the unlimited, limited and
+//! resizable queues. Every test only needs the feature of its queue.
+#[cfg(any(feature = "unlimited", feature = "limited", feature = "resizable"))]
+mod tests {
+
+    use deadqueue::Queue;
+
+    fn fill<Q: Queue<Item = usize>>(queue: &Q, n: usize) -> usize {
+        (0..n).take_while(|&i| queue.try_push(i).is_ok()).count()
+    }
+
+    fn drain<Q: Queue<Item = usize>>(queue: &Q) -> Vec<usize> {
+        std::iter::from_fn(|| queue.try_pop()).collect()
+    }
+
+    fn check<Q: Queue<Item = usize>>(queue: Q, capacity: Option<usize>) {
+        assert_eq!(queue.capacity(), capacity);
+        assert!(queue.is_empty());
+        let pushed = fill(&queue, 4);
+        assert_eq!(pushed, capacity.unwrap_or(4).min(4));
+        assert_eq!(queue.len(), pushed);
+        assert_eq!(queue.available(), pushed as isize);
+        assert_eq!(drain(&queue), (0..pushed).collect::<Vec<_>>());
+        assert!(!queue.is_closed());
+        queue.close();
+        assert!(queue.is_closed());
+        assert_eq!(queue.try_push(42), Err(42));
+        assert!(queue.is_empty());
+    }
+
+    #[cfg(feature = "unlimited")]
+    #[test]
+    fn test_unlimited() {
+        check(deadqueue::unlimited::Queue::new(), None);
+    }
+
+    #[cfg(feature = "limited")]
+    #[test]
+    fn test_limited() {
+        check(deadqueue::limited::Queue::with_capacity(2), Some(2));
+    }
+
+    #[cfg(feature = "resizable")]
+    #[test]
+    fn test_resizable() {
+        check(deadqueue::resizable::Queue::with_capacity(2), Some(2));
+    }
+}
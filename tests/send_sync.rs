@@ -0,0 +1,68 @@
+//! Compile time assertions of the `Send` and `Sync` bounds of the queues.
+//!
+//! The queues only require `T: Send` to be both `Send` and `Sync`. The
+//! item type used below is `Send` but not `Sync`.
+
+use std::cell::Cell;
+
+type Item = Cell<usize>;
+
+fn assert_send_sync<T: Send + Sync>() {}
+
+#[cfg(feature = "unlimited")]
+fn assert_send<T: Send>() {}
+
+#[cfg(feature = "unlimited")]
+#[test]
+fn test_unlimited_send_sync() {
+    use deadqueue::unlimited::{DrainIter, MappedQueue, PopGuard, Queue};
+    assert_send_sync::<Queue<Item>>();
+    assert_send_sync::<MappedQueue<Item, Item, fn(Item) -> Item>>();
+    assert_send_sync::<DrainIter<'static, Item>>();
+    // The guard hands out references to the item so it is only `Sync`
+    // if the item is.
+    assert_send::<PopGuard<'static, Item>>();
+    assert_send_sync::<PopGuard<'static, usize>>();
+}
+
+#[cfg(feature = "dedup")]
+#[test]
+fn test_dedup_send_sync() {
+    use deadqueue::unlimited::DedupQueue;
+    // The index stores clones of the items and is protected by a mutex.
+    assert_send_sync::<DedupQueue<usize>>();
+}
+
+#[cfg(feature = "limited")]
+#[test]
+fn test_limited_send_sync() {
+    use deadqueue::limited::{DrainIter, FixedQueue, MappedQueue, PollPop, Queue, Reservation};
+    assert_send_sync::<Queue<Item>>();
+    assert_send_sync::<FixedQueue<Item, 4>>();
+    assert_send_sync::<MappedQueue<Item, Item, fn(Item) -> Item>>();
+    assert_send_sync::<DrainIter<'static, Item>>();
+    assert_send_sync::<Reservation<'static, Item>>();
+    assert_send_sync::<PollPop<'static, Item>>();
+}
+
+#[cfg(feature = "resizable")]
+#[test]
+fn test_resizable_send_sync() {
+    use deadqueue::resizable::{DrainIter, MappedQueue, Queue, Reservation};
+    assert_send_sync::<Queue<Item>>();
+    assert_send_sync::<MappedQueue<Item, Item, fn(Item) -> Item>>();
+    assert_send_sync::<DrainIter<'static, Item>>();
+    assert_send_sync::<Reservation<'static, Item>>();
+}
+
+#[cfg(feature = "ttl")]
+#[test]
+fn test_ttl_send_sync() {
+    assert_send_sync::<deadqueue::ttl::Queue<Item>>();
+}
+
+#[cfg(feature = "classed")]
+#[test]
+fn test_classed_send_sync() {
+    assert_send_sync::<deadqueue::classed::Queue<Item>>();
+}
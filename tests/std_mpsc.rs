@@ -0,0 +1,28 @@
+//! Tests for the adapter enabled by the `std-mpsc` feature.
+#[cfg(all(feature = "std-mpsc", feature = "limited"))]
+mod tests {
+
+    use std::sync::{mpsc, Arc};
+    use std::thread;
+
+    use deadqueue::limited::Queue;
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn test_pump_from_std() {
+        let queue: Arc<Queue<usize>> = Arc::new(Queue::with_capacity(2));
+        let (tx, rx) = mpsc::channel();
+        let pump = queue.clone().pump_from_std(rx);
+        let producer = thread::spawn(move || {
+            for i in 0..100 {
+                tx.send(i).unwrap();
+            }
+        });
+        for i in 0..100 {
+            assert_eq!(queue.pop().await, i);
+            assert!(queue.len() <= 2);
+        }
+        producer.join().unwrap();
+        pump.await.unwrap();
+        assert!(queue.is_empty());
+    }
+}
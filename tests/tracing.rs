@@ -0,0 +1,65 @@
+//! Tests for the events emitted when the `tracing` feature is enabled.
+#[cfg(all(feature = "tracing", feature = "unlimited"))]
+mod tests {
+
+    use std::fmt::Debug;
+    use std::sync::atomic::{AtomicU64, Ordering};
+    use std::sync::{Arc, Mutex};
+
+    use deadqueue::unlimited::Queue;
+    use tracing::field::{Field, Visit};
+    use tracing::span::{Attributes, Id, Record};
+    use tracing::{Event, Metadata, Subscriber};
+
+    /// Subscriber which records the fields of all events as strings
+    #[derive(Default)]
+    struct Recorder {
+        next_id: AtomicU64,
+        events: Mutex<Vec<String>>,
+    }
+
+    struct FieldVisitor(String);
+
+    impl Visit for FieldVisitor {
+        fn record_debug(&mut self, field: &Field, value: &dyn Debug) {
+            self.0.push_str(&format!("{}={:?} ", field.name(), value));
+        }
+    }
+
+    impl Subscriber for Recorder {
+        fn enabled(&self, _: &Metadata<'_>) -> bool {
+            true
+        }
+        fn new_span(&self, _: &Attributes<'_>) -> Id {
+            Id::from_u64(self.next_id.fetch_add(1, Ordering::Relaxed) + 1)
+        }
+        fn record(&self, _: &Id, _: &Record<'_>) {}
+        fn record_follows_from(&self, _: &Id, _: &Id) {}
+        fn event(&self, event: &Event<'_>) {
+            let mut visitor = FieldVisitor(String::new());
+            event.record(&mut visitor);
+            self.events.lock().unwrap().push(visitor.0);
+        }
+        fn enter(&self, _: &Id) {}
+        fn exit(&self, _: &Id) {}
+    }
+
+    #[tokio::test]
+    async fn test_blocked_pop_event() {
+        let recorder = Arc::new(Recorder::default());
+        let _guard = tracing::subscriber::set_default(recorder.clone());
+        let queue: Queue<usize> = Queue::new();
+        queue.push(1);
+        assert_eq!(queue.pop().await, 1);
+        assert!(recorder.events.lock().unwrap().is_empty());
+        let (item, _) = tokio::join!(queue.pop(), async {
+            tokio::task::yield_now().await;
+            queue.push(2);
+        });
+        assert_eq!(item, 2);
+        let events = recorder.events.lock().unwrap();
+        assert_eq!(events.len(), 2);
+        assert!(events[0].starts_with("message=blocked op=\"pop\" len=0"));
+        assert!(events[1].starts_with("message=resolved op=\"pop\" len=1 wait_us="));
+    }
+}
@@ -0,0 +1,104 @@
+//! Tests for the span propagation enabled by the `tracing-context` feature.
+#[cfg(feature = "tracing-context")]
+mod tests {
+
+    use std::sync::atomic::{AtomicU64, Ordering};
+    use std::sync::{Arc, Mutex};
+
+    use tracing::span::{Attributes, Id, Record};
+    use tracing::{Event, Metadata, Subscriber};
+    use tracing_core::span::Current;
+
+    /// Subscriber which only keeps track of the entered spans
+    #[derive(Default)]
+    struct SpanStack {
+        next_id: AtomicU64,
+        stack: Mutex<Vec<(Id, &'static Metadata<'static>)>>,
+        metadata: Mutex<Vec<(Id, &'static Metadata<'static>)>>,
+    }
+
+    impl Subscriber for SpanStack {
+        fn enabled(&self, _: &Metadata<'_>) -> bool {
+            true
+        }
+        fn new_span(&self, attrs: &Attributes<'_>) -> Id {
+            let id = Id::from_u64(self.next_id.fetch_add(1, Ordering::Relaxed) + 1);
+            self.metadata
+                .lock()
+                .unwrap()
+                .push((id.clone(), attrs.metadata()));
+            id
+        }
+        fn record(&self, _: &Id, _: &Record<'_>) {}
+        fn record_follows_from(&self, _: &Id, _: &Id) {}
+        fn event(&self, _: &Event<'_>) {}
+        fn enter(&self, id: &Id) {
+            let metadata = self.metadata.lock().unwrap();
+            let (_, meta) = metadata.iter().find(|(span, _)| span == id).unwrap();
+            self.stack.lock().unwrap().push((id.clone(), meta));
+        }
+        fn exit(&self, _: &Id) {
+            self.stack.lock().unwrap().pop();
+        }
+        fn current_span(&self) -> Current {
+            match self.stack.lock().unwrap().last() {
+                Some((id, meta)) => Current::new(id.clone(), meta),
+                None => Current::none(),
+            }
+        }
+    }
+
+    #[cfg(feature = "unlimited")]
+    #[tokio::test]
+    async fn test_pop_with_span_unlimited() {
+        let _guard = tracing::subscriber::set_default(Arc::new(SpanStack::default()));
+        let queue: deadqueue::unlimited::SpanQueue<usize> = deadqueue::unlimited::SpanQueue::new();
+        let first = tracing::info_span!("first");
+        let second = tracing::info_span!("second");
+        first.in_scope(|| queue.push_spanned(1));
+        second.in_scope(|| queue.push_spanned(2));
+        queue.push_spanned(3);
+        let (span, item) = queue.pop_with_span().await;
+        assert_eq!(item, 1);
+        assert_eq!(span.id(), first.id());
+        let (span, item) = queue.try_pop_with_span().unwrap();
+        assert_eq!(item, 2);
+        assert_eq!(span.id(), second.id());
+        let (span, item) = queue.pop_with_span().await;
+        assert_eq!(item, 3);
+        assert!(span.is_none());
+    }
+
+    #[cfg(feature = "limited")]
+    #[tokio::test]
+    async fn test_pop_with_span_limited() {
+        use tracing::Instrument;
+
+        let _guard = tracing::subscriber::set_default(Arc::new(SpanStack::default()));
+        let queue: deadqueue::limited::SpanQueue<usize> =
+            deadqueue::limited::SpanQueue::with_capacity(2);
+        let producer = tracing::info_span!("producer");
+        queue.push_spanned(1).instrument(producer.clone()).await;
+        producer.in_scope(|| queue.try_push_spanned(2)).unwrap();
+        assert_eq!(queue.try_push_spanned(3), Err(3));
+        let (span, item) = queue.pop_with_span().await;
+        assert_eq!(item, 1);
+        assert_eq!(span.id(), producer.id());
+        let (span, item) = queue.try_pop_with_span().unwrap();
+        assert_eq!(item, 2);
+        assert_eq!(span.id(), producer.id());
+    }
+
+    #[cfg(feature = "resizable")]
+    #[tokio::test]
+    async fn test_pop_with_span_resizable() {
+        let _guard = tracing::subscriber::set_default(Arc::new(SpanStack::default()));
+        let queue: deadqueue::resizable::SpanQueue<usize> =
+            deadqueue::resizable::SpanQueue::with_capacity(2);
+        let producer = tracing::info_span!("producer");
+        producer.in_scope(|| queue.try_push_spanned(1)).unwrap();
+        let (span, item) = queue.pop_with_span().await;
+        assert_eq!(item, 1);
+        assert_eq!(span.id(), producer.id());
+    }
+}
@@ -0,0 +1,72 @@
+//! Tests for the queue enabled by the `ttl` feature.
+#[cfg(feature = "ttl")]
+mod tests {
+
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    use deadqueue::ttl::Queue;
+
+    #[tokio::test]
+    async fn test_sweeper_removes_expired_items() {
+        let queue: Arc<Queue<usize>> = Arc::new(Queue::new(Duration::from_millis(50)));
+        let sweeper = queue.clone().spawn_sweeper(Duration::from_millis(10));
+        for i in 0..10 {
+            queue.push(i);
+        }
+        assert_eq!(queue.len(), 10);
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert_eq!(queue.len(), 0);
+        assert_eq!(queue.available(), 0);
+        assert_eq!(queue.try_pop(), None);
+        queue.push(10);
+        assert_eq!(queue.pop().await, 10);
+        drop(queue);
+        sweeper.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_pop_skips_expired_items() {
+        let queue: Queue<usize> = Queue::new(Duration::from_millis(200));
+        queue.push(1);
+        queue.push(2);
+        tokio::time::sleep(Duration::from_millis(120)).await;
+        queue.push(3);
+        tokio::time::sleep(Duration::from_millis(120)).await;
+        queue.push(4);
+        assert_eq!(queue.len(), 4);
+        assert_eq!(queue.pop().await, 3);
+        assert_eq!(queue.try_pop(), Some(4));
+        assert_eq!(queue.try_pop(), None);
+        assert_eq!(queue.available(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_purge_expired_stops_at_live_item() {
+        let queue: Queue<usize> = Queue::new(Duration::from_millis(200));
+        queue.push(1);
+        tokio::time::sleep(Duration::from_millis(120)).await;
+        queue.push(2);
+        tokio::time::sleep(Duration::from_millis(120)).await;
+        assert_eq!(queue.purge_expired(), 1);
+        assert_eq!(queue.purge_expired(), 0);
+        assert_eq!(queue.len(), 1);
+        assert_eq!(queue.available(), 1);
+        assert_eq!(queue.pop().await, 2);
+    }
+
+    #[tokio::test]
+    async fn test_pop_waits_for_push() {
+        let queue: Arc<Queue<usize>> = Arc::new(Queue::new(Duration::from_secs(60)));
+        let pop = {
+            let queue = queue.clone();
+            tokio::spawn(async move { queue.pop().await })
+        };
+        while queue.available() > -1 {
+            tokio::task::yield_now().await;
+        }
+        queue.push(1);
+        assert_eq!(pop.await.unwrap(), 1);
+        assert_eq!(queue.available(), 0);
+    }
+}
@@ -2,9 +2,12 @@
 mod tests {
 
     use std::iter::FromIterator;
+    use std::sync::atomic::{AtomicUsize, Ordering};
     use std::sync::Arc;
+    use std::sync::OnceLock;
+    use std::time::Duration;
 
-    use deadqueue::unlimited::Queue;
+    use deadqueue::unlimited::{BoxQueue, Order, Queue};
 
     #[tokio::test]
     async fn test_basics() {
@@ -141,6 +144,1184 @@ mod tests {
     fn test_debug() {
         struct NoDebug {}
         let queue: Queue<NoDebug> = Queue::new();
-        format!("{:?}", queue);
+        queue.push(NoDebug {});
+        let debug = format!("{:?}", queue);
+        assert!(debug.contains("len: 1"));
+        assert!(debug.contains("available: 1"));
+    }
+
+    #[test]
+    fn test_pop_blocking() {
+        let queue: Arc<Queue<usize>> = Arc::new(Queue::new());
+        let consumer_queue = queue.clone();
+        let consumer = std::thread::spawn(move || {
+            (0..100)
+                .map(|_| consumer_queue.pop_blocking())
+                .collect::<Vec<_>>()
+        });
+        for i in 0..100 {
+            queue.push(i);
+        }
+        assert_eq!(consumer.join().unwrap(), (0..100).collect::<Vec<_>>());
+        assert_eq!(queue.len(), 0);
+        assert_eq!(queue.available(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_order_fifo() {
+        let queue: Queue<usize> = Queue::with_order(Order::Fifo);
+        for i in 0..5 {
+            queue.push(i);
+        }
+        for i in 0..5 {
+            assert_eq!(queue.pop().await, i);
+        }
+        assert_eq!(queue.available(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_order_lifo() {
+        let queue: Queue<usize> = Queue::with_order(Order::Lifo);
+        for i in 0..5 {
+            queue.push(i);
+        }
+        assert_eq!(queue.len(), 5);
+        for i in (0..5).rev() {
+            assert_eq!(queue.pop().await, i);
+        }
+        assert!(queue.try_pop().is_none());
+        assert!(queue.is_empty());
+        assert_eq!(queue.available(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_close() {
+        let queue: Queue<usize> = Queue::new();
+        queue.push(1);
+        queue.close();
+        assert!(queue.is_closed());
+        queue.push(2);
+        assert_eq!(queue.len(), 1);
+        assert_eq!(queue.pop().await, 1);
+    }
+
+    #[tokio::test]
+    async fn test_shutdown_timeout() {
+        let queue: Arc<Queue<usize>> = Arc::new(Queue::from_iter(0..10));
+        let consumer_queue = queue.clone();
+        tokio::spawn(async move {
+            loop {
+                consumer_queue.pop().await;
+                tokio::time::sleep(Duration::from_millis(50)).await;
+            }
+        });
+        let remaining = queue
+            .shutdown(Duration::from_millis(120))
+            .await
+            .unwrap_err();
+        assert!(remaining > 0 && remaining < 10);
+    }
+
+    #[tokio::test]
+    async fn test_available_dropped_pops() {
+        const N: usize = 1000;
+        let queue: Arc<Queue<usize>> = Arc::new(Queue::new());
+        let mut futures = Vec::new();
+        for _ in 0..2 * N {
+            let queue = queue.clone();
+            futures.push(tokio::spawn(async move { queue.pop().await }));
+        }
+        // Wait for all pop futures to be parked
+        while queue.available() > -(2 * N as isize) {
+            tokio::task::yield_now().await;
+        }
+        // Drop every second pop future. Each dropped future must roll
+        // back its pending decrement of the available counter.
+        let (dropped, futures): (Vec<_>, Vec<_>) = futures
+            .into_iter()
+            .enumerate()
+            .partition(|(i, _)| i % 2 == 0);
+        for (_, future) in dropped {
+            future.abort();
+            assert!(future.await.unwrap_err().is_cancelled());
+        }
+        assert_eq!(queue.available(), -(N as isize));
+        for i in 0..N {
+            queue.push(i);
+        }
+        for (_, future) in futures {
+            future.await.unwrap();
+        }
+        assert_eq!(queue.len(), 0);
+        assert_eq!(queue.available(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_dropped_pop_empty_notification() {
+        let queue: Arc<Queue<usize>> = Arc::new(Queue::new());
+        let pop_a = tokio::spawn({
+            let queue = queue.clone();
+            async move { queue.pop().await }
+        });
+        let pop_b = tokio::spawn({
+            let queue = queue.clone();
+            async move { queue.pop().await }
+        });
+        while queue.available() > -2 {
+            tokio::task::yield_now().await;
+        }
+        let empty = queue.subscribe_empty();
+        queue.push(1);
+        queue.push(2);
+        // Drop the second pop future after it has been assigned an item
+        // but before it could take it out of the queue.
+        pop_b.abort();
+        assert!(pop_b.await.unwrap_err().is_cancelled());
+        assert_eq!(pop_a.await.unwrap(), 1);
+        assert_eq!(queue.len(), 1);
+        assert_eq!(queue.available(), 1);
+        assert!(!empty.has_changed().unwrap());
+        assert_eq!(queue.pop().await, 2);
+        assert!(empty.has_changed().unwrap());
+        assert_eq!(queue.available(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_warn_threshold() {
+        let warnings = Arc::new(AtomicUsize::new(0));
+        let observer_warnings = warnings.clone();
+        let queue: Queue<usize> = Queue::with_warn_threshold(3, move |len| {
+            assert_eq!(len, 3);
+            observer_warnings.fetch_add(1, Ordering::Relaxed);
+        });
+        for i in 0..10 {
+            queue.push(i);
+        }
+        assert_eq!(warnings.load(Ordering::Relaxed), 1);
+        while queue.len() >= 3 {
+            queue.pop().await;
+        }
+        assert_eq!(warnings.load(Ordering::Relaxed), 1);
+        queue.push(10);
+        assert_eq!(warnings.load(Ordering::Relaxed), 2);
+    }
+
+    #[tokio::test]
+    async fn test_allocated_capacity() {
+        for order in [Order::Fifo, Order::Lifo] {
+            let queue: Queue<usize> = Queue::with_order(order);
+            let mut allocated_capacity = queue.allocated_capacity();
+            for i in 0..1000 {
+                queue.push(i);
+                assert!(queue.allocated_capacity() >= allocated_capacity);
+                allocated_capacity = queue.allocated_capacity();
+                assert!(allocated_capacity >= queue.len());
+            }
+            while queue.try_pop().is_some() {
+                assert!(queue.allocated_capacity() >= queue.len());
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_map() {
+        let queue: Queue<usize> = Queue::new();
+        let queue = queue.map(|item| format!("item {}", item));
+        queue.push(1);
+        queue.push(2);
+        assert_eq!(queue.len(), 2);
+        assert_eq!(queue.pop().await, "item 1");
+        assert_eq!(queue.try_pop(), Some("item 2".to_string()));
+        assert_eq!(queue.try_pop(), None);
+        assert!(queue.into_inner().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_for_each_concurrent() {
+        const N: usize = 100;
+        const CONCURRENCY: usize = 4;
+        let queue: Arc<Queue<usize>> = Arc::new(Queue::new());
+        for i in 0..N {
+            queue.push(i);
+        }
+        queue.close();
+        let running = AtomicUsize::new(0);
+        let max_running = AtomicUsize::new(0);
+        let sum = AtomicUsize::new(0);
+        queue
+            .for_each_concurrent(CONCURRENCY, |item| {
+                let running = &running;
+                let max_running = &max_running;
+                let sum = &sum;
+                async move {
+                    let now = running.fetch_add(1, Ordering::Relaxed) + 1;
+                    max_running.fetch_max(now, Ordering::Relaxed);
+                    tokio::task::yield_now().await;
+                    sum.fetch_add(item, Ordering::Relaxed);
+                    running.fetch_sub(1, Ordering::Relaxed);
+                }
+            })
+            .await;
+        assert_eq!(sum.load(Ordering::Relaxed), N * (N - 1) / 2);
+        assert_eq!(max_running.load(Ordering::Relaxed), CONCURRENCY);
+        assert!(queue.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_for_each_concurrent_close() {
+        let queue: Arc<Queue<usize>> = Arc::new(Queue::new());
+        let handled = Arc::new(AtomicUsize::new(0));
+        let consumer = {
+            let queue = queue.clone();
+            let handled = handled.clone();
+            tokio::spawn(async move {
+                queue
+                    .for_each_concurrent(2, |_| async {
+                        handled.fetch_add(1, Ordering::Relaxed);
+                    })
+                    .await
+            })
+        };
+        tokio::task::yield_now().await;
+        queue.push(1);
+        queue.close();
+        tokio::time::timeout(Duration::from_secs(1), consumer)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(handled.load(Ordering::Relaxed), 1);
+    }
+
+    #[tokio::test]
+    async fn test_occupancy() {
+        let queue: Arc<Queue<usize>> = Arc::new(Queue::new());
+        assert_eq!(queue.occupancy(), (0, 0));
+        let poppers: Vec<_> = (0..3)
+            .map(|_| {
+                let queue = queue.clone();
+                tokio::spawn(async move { queue.pop().await })
+            })
+            .collect();
+        while queue.available() > -3 {
+            tokio::task::yield_now().await;
+        }
+        assert_eq!(queue.occupancy(), (0, 3));
+        queue.push(1);
+        queue.push(2);
+        for popper in poppers.into_iter().take(2) {
+            popper.await.unwrap();
+        }
+        assert_eq!(queue.occupancy(), (0, 1));
+        assert_eq!(queue.available(), -1);
+    }
+
+    #[tokio::test]
+    async fn test_pop_n_timeout() {
+        let queue: Arc<Queue<usize>> = Arc::new(Queue::new());
+        let producer_queue = queue.clone();
+        let producer = tokio::spawn(async move {
+            for i in 0..10 {
+                producer_queue.push(i);
+                tokio::time::sleep(Duration::from_millis(20)).await;
+            }
+        });
+        // The cap is reached before the deadline
+        let items = queue.pop_n_timeout(2, Duration::from_secs(10)).await;
+        assert_eq!(items, vec![0, 1]);
+        // The deadline is reached before the cap
+        let start = std::time::Instant::now();
+        let items = queue.pop_n_timeout(100, Duration::from_millis(70)).await;
+        let elapsed = start.elapsed();
+        assert!(!items.is_empty() && items.len() < 8);
+        assert!(elapsed >= Duration::from_millis(70));
+        assert!(elapsed < Duration::from_secs(1));
+        producer.await.unwrap();
+        let items = queue.pop_n_timeout(100, Duration::from_millis(0)).await;
+        assert!(items.iter().all(|&item| item >= 3));
+        assert!(queue
+            .pop_n_timeout(1, Duration::from_millis(10))
+            .await
+            .is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_late_subscriber() {
+        let queue: Arc<Queue<usize>> = Arc::new(Queue::new());
+        // Empty the queue repeatedly without anybody listening
+        for i in 0..10 {
+            queue.push(i);
+            queue.try_pop().unwrap();
+        }
+        let mut empty = queue.subscribe_empty();
+        queue.push(1);
+        let consumer_queue = queue.clone();
+        tokio::spawn(async move {
+            consumer_queue.pop().await;
+        });
+        tokio::time::timeout(Duration::from_secs(1), empty.changed())
+            .await
+            .unwrap()
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_box_queue() {
+        let queue: BoxQueue<dyn Fn() -> String + Send> = BoxQueue::new();
+        let name = String::from("job");
+        queue.push(Box::new(|| String::from("closure")));
+        queue.push(Box::new(move || name.clone()));
+        queue.push(Box::new(|| 42.to_string()));
+        let mut results = Vec::new();
+        while let Some(job) = queue.try_pop() {
+            results.push(job());
+        }
+        assert_eq!(results, vec!["closure", "job", "42"]);
+    }
+
+    #[tokio::test]
+    async fn test_wait_stats() {
+        let queue: Arc<Queue<usize>> = Arc::new(Queue::new().record_waits());
+        queue.push(1);
+        assert_eq!(queue.pop().await, 1);
+        let producer_queue = queue.clone();
+        tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            producer_queue.push(2);
+        });
+        assert_eq!(queue.pop().await, 2);
+        let stats = queue.wait_stats();
+        assert_eq!(stats.count, 2);
+        assert!(stats.max >= Duration::from_millis(50));
+        assert!(stats.total >= stats.max);
+    }
+
+    #[tokio::test]
+    async fn test_wait_stats_disabled() {
+        let queue: Queue<usize> = Queue::new();
+        queue.push(1);
+        queue.pop().await;
+        assert_eq!(queue.wait_stats().count, 0);
+    }
+
+    #[test]
+    fn test_contains() {
+        for order in [Order::Fifo, Order::Lifo] {
+            let queue: Queue<usize> = Queue::with_order(order);
+            assert!(!queue.contains(&1));
+            queue.push(1);
+            queue.push(2);
+            queue.push(3);
+            assert!(queue.contains(&2));
+            assert!(!queue.contains(&9));
+            assert_eq!(queue.len(), 3);
+            assert_eq!(queue.available(), 3);
+            let items: Vec<usize> = std::iter::from_fn(|| queue.try_pop()).collect();
+            match order {
+                Order::Fifo => assert_eq!(items, vec![1, 2, 3]),
+                Order::Lifo => assert_eq!(items, vec![3, 2, 1]),
+            }
+        }
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn test_contains_concurrent_pop() {
+        const N: usize = 10_000;
+        let queue: Arc<Queue<usize>> = Arc::new((0..N).collect());
+        let consumer_queue = queue.clone();
+        let consumer = tokio::spawn(async move {
+            for i in 0..N {
+                assert_eq!(consumer_queue.pop().await, i);
+            }
+        });
+        while !consumer.is_finished() {
+            queue.bump_to_front(|&i| i == N);
+            queue.contains(&N);
+            tokio::task::yield_now().await;
+        }
+        consumer.await.unwrap();
+        assert_eq!(queue.len(), 0);
+        assert_eq!(queue.available(), 0);
+    }
+
+    #[test]
+    fn test_push_unique() {
+        let queue: Queue<usize> = Queue::new();
+        assert!(queue.push_unique(1));
+        assert!(queue.push_unique(2));
+        assert!(!queue.push_unique(1));
+        assert_eq!(queue.len(), 2);
+        assert_eq!(queue.try_pop(), Some(1));
+        assert!(queue.push_unique(1));
+        assert_eq!(queue.len(), 2);
+        queue.close();
+        assert!(!queue.push_unique(3));
+    }
+
+    #[cfg(feature = "dedup")]
+    #[tokio::test]
+    async fn test_dedup_queue() {
+        use deadqueue::unlimited::DedupQueue;
+        let queue: DedupQueue<String> = DedupQueue::new();
+        assert!(queue.push_unique("a".into()));
+        assert!(queue.push_unique("b".into()));
+        assert!(!queue.push_unique("a".into()));
+        assert!(queue.contains(&"a".into()));
+        assert_eq!(queue.len(), 2);
+        assert_eq!(queue.pop().await, "a");
+        assert!(!queue.contains(&"a".into()));
+        assert!(queue.push_unique("a".into()));
+        assert_eq!(queue.try_pop().as_deref(), Some("b"));
+        assert_eq!(queue.try_pop().as_deref(), Some("a"));
+        assert!(queue.is_empty());
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn test_wait_empty_race() {
+        let queue: Arc<Queue<usize>> = Arc::new(Queue::new());
+        for i in 0..1000 {
+            queue.push(i);
+            let consumer_queue = queue.clone();
+            let consumer = tokio::spawn(async move { consumer_queue.pop().await });
+            tokio::time::timeout(Duration::from_secs(1), queue.wait_empty())
+                .await
+                .expect("wait_empty missed the notification");
+            assert_eq!(consumer.await.unwrap(), i);
+        }
+    }
+
+    #[test]
+    fn test_bump_to_front() {
+        for order in [Order::Fifo, Order::Lifo] {
+            let queue: Queue<char> = Queue::with_order(order);
+            let items = match order {
+                Order::Fifo => ['a', 'b', 'c'],
+                Order::Lifo => ['c', 'b', 'a'],
+            };
+            for item in items {
+                queue.push(item);
+            }
+            assert!(!queue.bump_to_front(|item| *item == 'x'));
+            assert!(queue.bump_to_front(|item| *item == 'c'));
+            assert_eq!(queue.available(), 3);
+            let items: Vec<char> = std::iter::from_fn(|| queue.try_pop()).collect();
+            assert_eq!(items, vec!['c', 'a', 'b']);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_static() {
+        static QUEUE: OnceLock<Queue<usize>> = OnceLock::new();
+        let queue = QUEUE.get_or_init(Queue::new);
+        let producer = tokio::spawn(async {
+            QUEUE.get_or_init(Queue::new).push(1);
+        });
+        assert_eq!(queue.pop().await, 1);
+        producer.await.unwrap();
+    }
+
+    #[cfg(feature = "testing")]
+    #[tokio::test]
+    async fn test_invariants_random_ops() {
+        let queue: Arc<Queue<usize>> = Arc::new(Queue::new());
+        let mut seed: u32 = 0x2545_f491;
+        for i in 0..1000 {
+            seed ^= seed << 13;
+            seed ^= seed >> 17;
+            seed ^= seed << 5;
+            match seed % 4 {
+                0 | 1 => queue.push(i),
+                2 => {
+                    queue.try_pop();
+                }
+                _ => {
+                    if !queue.is_empty() {
+                        queue.pop().await;
+                    }
+                }
+            }
+            assert!(queue.check_invariants());
+        }
+    }
+
+    #[cfg(feature = "testing")]
+    #[tokio::test]
+    async fn test_invariants_with_waiters() {
+        let queue: Arc<Queue<usize>> = Arc::new(Queue::new());
+        let mut waiters = Vec::new();
+        for i in 0..3 {
+            let q = queue.clone();
+            waiters.push(tokio::spawn(async move { q.pop().await }));
+            while queue.available() > -(i + 1) {
+                tokio::task::yield_now().await;
+            }
+            assert!(queue.check_invariants());
+        }
+        for i in 0..3 {
+            queue.push(i);
+        }
+        for waiter in waiters {
+            waiter.await.unwrap();
+        }
+        assert!(queue.check_invariants());
+        assert_eq!(queue.available(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_peek_copy() {
+        let queue: Queue<usize> = Queue::new();
+        assert_eq!(queue.peek_copy(), None);
+        queue.push(1);
+        queue.push(2);
+        assert_eq!(queue.peek_copy(), Some(1));
+        assert_eq!(queue.len(), 2);
+        assert_eq!(queue.try_pop(), Some(1));
+        assert_eq!(queue.peek_copy(), Some(2));
+        assert_eq!(queue.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_peek_copy_lifo() {
+        let queue: Queue<usize> = Queue::with_order(Order::Lifo);
+        queue.push(1);
+        queue.push(2);
+        assert_eq!(queue.peek_copy(), Some(2));
+        assert_eq!(queue.len(), 2);
+        assert_eq!(queue.pop().await, 2);
+    }
+
+    #[tokio::test]
+    async fn test_items_ready_pending_pops() {
+        let queue: Arc<Queue<usize>> = Arc::new(Queue::new());
+        let mut poppers = Vec::new();
+        for i in 0..3 {
+            let q = queue.clone();
+            poppers.push(tokio::spawn(async move { q.pop().await }));
+            while queue.pending_pops() < i + 1 {
+                tokio::task::yield_now().await;
+            }
+            assert_eq!(queue.items_ready(), 0);
+        }
+        assert_eq!(queue.pending_pops(), 3);
+        for i in 0..5 {
+            queue.push(i);
+        }
+        for popper in poppers {
+            popper.await.unwrap();
+        }
+        assert_eq!(queue.items_ready(), 2);
+        assert_eq!(queue.pending_pops(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_closed() {
+        let queue: Arc<Queue<usize>> = Arc::new(Queue::new());
+        let consumer = {
+            let queue = queue.clone();
+            tokio::spawn(async move { queue.closed().await })
+        };
+        tokio::task::yield_now().await;
+        assert!(!consumer.is_finished());
+        let closer = queue.clone();
+        tokio::spawn(async move { closer.close() }).await.unwrap();
+        tokio::time::timeout(Duration::from_secs(1), consumer)
+            .await
+            .unwrap()
+            .unwrap();
+        // Resolves immediately once closed
+        queue.closed().await;
+    }
+
+    #[tokio::test]
+    async fn test_pop_guard() {
+        let queue: Queue<usize> = Queue::new();
+        queue.push(1);
+        queue.push(2);
+        {
+            let guard = queue.pop_guard().await;
+            assert_eq!(*guard, 1);
+            assert_eq!(queue.len(), 1);
+            // Dropped without commit, e.g. by a panic
+        }
+        assert_eq!(queue.len(), 2);
+        assert_eq!(queue.available(), 2);
+        let guard = queue.pop_guard().await;
+        assert_eq!(guard.commit(), 1);
+        assert_eq!(queue.pop().await, 2);
+        assert!(queue.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_pop_guard_panic() {
+        let queue: Arc<Queue<usize>> = Arc::new(Queue::new());
+        queue.push(1);
+        let consumer = queue.clone();
+        let result = tokio::spawn(async move {
+            let guard = consumer.pop_guard().await;
+            if *guard == 1 {
+                panic!("processing failed");
+            }
+            guard.commit();
+        })
+        .await;
+        assert!(result.is_err());
+        assert_eq!(queue.try_pop(), Some(1));
+    }
+
+    #[tokio::test]
+    async fn test_drain_iter() {
+        let queue: Queue<usize> = Queue::new();
+        for i in 0..5 {
+            queue.push(i);
+        }
+        assert_eq!(queue.drain_iter().collect::<Vec<_>>(), vec![0, 1, 2, 3, 4]);
+        assert!(queue.is_empty());
+        assert_eq!(queue.available(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_push_boxed_roundtrip() {
+        let queue: BoxQueue<[u8; 4096]> = BoxQueue::new();
+        let item = Box::new([7u8; 4096]);
+        let ptr: *const [u8; 4096] = &*item;
+        queue.push_boxed(item);
+        let item = queue.try_pop().unwrap();
+        assert!(std::ptr::eq(ptr, &*item));
+        queue.push_boxed(item);
+        assert_eq!(queue.pop_unboxed().await, [7u8; 4096]);
+        assert_eq!(queue.try_pop_unboxed(), None);
+    }
+
+    #[tokio::test]
+    async fn test_wait_until_len_range() {
+        let queue: Arc<Queue<usize>> = Arc::new(Queue::new());
+        let waiter = {
+            let queue = queue.clone();
+            tokio::spawn(
+                async move { queue.wait_until(|stats| (3..=5).contains(&stats.len)).await },
+            )
+        };
+        for i in 0..2 {
+            queue.push(i);
+            tokio::task::yield_now().await;
+            assert!(!waiter.is_finished());
+        }
+        queue.push(2);
+        tokio::time::timeout(Duration::from_secs(1), waiter)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(queue.stats_snapshot().len, 3);
+    }
+
+    #[tokio::test]
+    async fn test_wait_len() {
+        let queue: Arc<Queue<usize>> = Arc::new(Queue::new());
+        let batch = {
+            let queue = queue.clone();
+            tokio::spawn(async move {
+                queue.wait_len(3).await;
+                queue.drain_iter().collect::<Vec<_>>()
+            })
+        };
+        let single = {
+            let queue = queue.clone();
+            tokio::spawn(async move { queue.wait_not_empty().await })
+        };
+        tokio::task::yield_now().await;
+        assert!(!single.is_finished());
+        queue.push(0);
+        single.await.unwrap();
+        queue.push(1);
+        tokio::task::yield_now().await;
+        assert!(!batch.is_finished());
+        queue.push(2);
+        let items = tokio::time::timeout(Duration::from_secs(1), batch)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(items, vec![0, 1, 2]);
+    }
+
+    #[tokio::test]
+    async fn test_try_pop_slice() {
+        let queue: Queue<usize> = Queue::new();
+        for i in 0..3 {
+            queue.push(i);
+        }
+        let mut out = [None; 8];
+        assert_eq!(queue.try_pop_slice(&mut out), 3);
+        assert_eq!(out[..4], [Some(0), Some(1), Some(2), None]);
+        assert!(queue.is_empty());
+        assert_eq!(queue.available(), 0);
+        for i in 0..3 {
+            queue.push(i);
+        }
+        let mut out = [None; 2];
+        assert_eq!(queue.try_pop_slice(&mut out), 2);
+        assert_eq!(out, [Some(0), Some(1)]);
+        assert_eq!(queue.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_close_wakes_poppers() {
+        let queue: Arc<Queue<usize>> = Arc::new(Queue::new());
+        let mut poppers = Vec::new();
+        for i in 0..5 {
+            let q = queue.clone();
+            poppers.push(tokio::spawn(async move { q.pop_or_closed().await }));
+            while queue.pending_pops() < i + 1 {
+                tokio::task::yield_now().await;
+            }
+        }
+        queue.close();
+        for popper in poppers {
+            let item = tokio::time::timeout(Duration::from_secs(1), popper)
+                .await
+                .unwrap()
+                .unwrap();
+            assert_eq!(item, None);
+        }
+        assert_eq!(queue.available(), 0);
+        assert_eq!(queue.pending_pops(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_subscribe_len() {
+        let queue: Queue<usize> = Queue::new();
+        queue.push(0);
+        let mut len = queue.subscribe_len();
+        assert_eq!(*len.borrow(), 1);
+        assert!(!len.has_changed().unwrap());
+        queue.push(1);
+        len.changed().await.unwrap();
+        assert_eq!(*len.borrow_and_update(), 2);
+        queue.pop().await;
+        queue.pop().await;
+        // Updates are coalesced
+        len.changed().await.unwrap();
+        assert_eq!(*len.borrow_and_update(), 0);
+        assert!(!len.has_changed().unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_spin_pop() {
+        let queue: Queue<usize> = Queue::new();
+        queue.push(1);
+        assert_eq!(queue.spin_pop(1), Some(1));
+        assert_eq!(queue.spin_pop(100), None);
+        assert_eq!(queue.spin_pop(0), None);
+        assert_eq!(queue.available(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_with_capacity_hint() {
+        let queue: Queue<usize> = Queue::with_capacity_hint(1024);
+        assert!(queue.is_empty());
+        for i in 0..2048 {
+            queue.push(i);
+        }
+        assert_eq!(queue.len(), 2048);
+        for i in 0..2048 {
+            assert_eq!(queue.pop().await, i);
+        }
+        assert_eq!(queue.available(), 0);
+    }
+
+    #[cfg(feature = "limited")]
+    #[tokio::test]
+    async fn test_into_limited() {
+        let queue: Queue<usize> = (0..3).collect();
+        let queue = queue.into_limited(3).unwrap();
+        assert_eq!(queue.capacity(), 3);
+        assert!(queue.is_full());
+        assert_eq!(queue.available(), 3);
+        assert_eq!(queue.try_push(3), Err(3));
+        for i in 0..3 {
+            assert_eq!(queue.pop().await, i);
+        }
+        queue.push(3).await;
+        assert_eq!(queue.try_pop(), Some(3));
+    }
+
+    #[cfg(feature = "limited")]
+    #[test]
+    fn test_into_limited_too_small() {
+        let queue: Queue<usize> = (0..3).collect();
+        let queue = queue.into_limited(2).unwrap_err();
+        assert_eq!(queue.len(), 3);
+        assert_eq!(queue.try_pop(), Some(0));
+    }
+
+    #[cfg(feature = "limited")]
+    #[test]
+    fn test_into_limited_closed() {
+        let queue: Queue<usize> = (0..1).collect();
+        queue.close();
+        let queue = queue.into_limited(2).unwrap();
+        assert!(queue.is_closed());
+        assert_eq!(queue.try_pop(), Some(0));
+    }
+
+    #[cfg(feature = "resizable")]
+    #[tokio::test]
+    async fn test_into_resizable() {
+        let queue: Queue<usize> = (0..3).collect();
+        let queue = queue.into_resizable(4).unwrap();
+        assert_eq!(queue.capacity(), 4);
+        assert_eq!(queue.len(), 3);
+        assert_eq!(queue.available(), 3);
+        queue.push(3).await;
+        assert!(queue.is_full());
+        queue.resize(5).await;
+        queue.push(4).await;
+        for i in 0..5 {
+            assert_eq!(queue.pop().await, i);
+        }
+    }
+
+    #[cfg(feature = "resizable")]
+    #[test]
+    fn test_into_resizable_too_small() {
+        let queue: Queue<usize> = (0..3).collect();
+        let queue = queue.into_resizable(2).unwrap_err();
+        assert_eq!(queue.len(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_idle_duration() {
+        let queue: Queue<usize> = Queue::new();
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        assert!(queue.idle_duration() >= Duration::from_millis(20));
+        queue.push(1);
+        assert_eq!(queue.pop().await, 1);
+        assert!(queue.idle_duration() < Duration::from_millis(20));
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert!(queue.idle_duration() >= Duration::from_millis(50));
+        assert_eq!(queue.try_pop(), None);
+        assert!(queue.idle_duration() >= Duration::from_millis(50));
+    }
+
+    #[test]
+    fn test_subscribe_empty_before_final_pop() {
+        let queue: Queue<usize> = Queue::new();
+        for i in 0..3 {
+            queue.push(i);
+        }
+        queue.try_pop().unwrap();
+        queue.try_pop().unwrap();
+        let empty = queue.subscribe_empty();
+        assert!(!empty.has_changed().unwrap());
+        queue.try_pop().unwrap();
+        assert!(empty.has_changed().unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_try_pop_with_waiters() {
+        let queue: Arc<Queue<usize>> = Arc::new(Queue::new());
+        assert_eq!(queue.try_pop_with_waiters(), None);
+        let poppers: Vec<_> = (0..2)
+            .map(|_| {
+                let queue = queue.clone();
+                tokio::spawn(async move { queue.pop().await })
+            })
+            .collect();
+        while queue.pending_pops() < 2 {
+            tokio::task::yield_now().await;
+        }
+        // The only item is handed to one of the parked poppers.
+        queue.push(0);
+        assert_eq!(queue.try_pop_with_waiters(), None);
+        assert_eq!(queue.pending_pops(), 1);
+        // The remaining popper is served first and the next item is
+        // free to be taken.
+        queue.push(1);
+        queue.push(2);
+        queue.push(3);
+        let (_, waiters) = queue.try_pop_with_waiters().unwrap();
+        assert_eq!(waiters, 0);
+        for popper in poppers {
+            popper.await.unwrap();
+        }
+        assert_eq!(
+            queue.try_pop_with_waiters().map(|(_, waiters)| waiters),
+            Some(0)
+        );
+        assert_eq!(queue.try_pop_with_waiters(), None);
+    }
+
+    #[tokio::test]
+    async fn test_peek_n() {
+        let queue: Queue<usize> = (1..=3).collect();
+        assert_eq!(queue.peek_n(2), vec![1, 2]);
+        assert_eq!(queue.len(), 3);
+        assert_eq!(queue.available(), 3);
+        assert_eq!(queue.peek_n(5), vec![1, 2, 3]);
+        assert_eq!(queue.peek_n(0), Vec::<usize>::new());
+        assert_eq!(queue.pop().await, 1);
+        let queue: Queue<usize> = Queue::with_order(Order::Lifo);
+        queue.push(1);
+        queue.push(2);
+        assert_eq!(queue.peek_n(2), vec![2, 1]);
+        assert_eq!(queue.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_pop_yields_to_other_tasks() {
+        let queue: Queue<usize> = (0..10_000).collect();
+        let ticks = Arc::new(AtomicUsize::new(0));
+        let ticker = {
+            let ticks = ticks.clone();
+            tokio::spawn(async move {
+                loop {
+                    ticks.fetch_add(1, Ordering::Relaxed);
+                    tokio::task::yield_now().await;
+                }
+            })
+        };
+        // The current thread runtime only runs the ticker if draining the
+        // queue yields to the scheduler.
+        for _ in 0..10_000 {
+            queue.pop().await;
+        }
+        assert!(ticks.load(Ordering::Relaxed) > 0);
+        ticker.abort();
+    }
+
+    #[tokio::test]
+    async fn test_subscribe_empty_repeated_transitions() {
+        let queue: Arc<Queue<usize>> = Arc::new(Queue::new());
+        let mut empty = queue.subscribe_empty();
+        for round in 0..2 {
+            queue.push(round);
+            let consumer_queue = queue.clone();
+            tokio::spawn(async move {
+                consumer_queue.pop().await;
+            });
+            tokio::time::timeout(Duration::from_secs(1), empty.changed())
+                .await
+                .unwrap()
+                .unwrap();
+            assert!(queue.is_empty());
+        }
+    }
+
+    #[test]
+    fn test_try_pop_delta() {
+        let queue: Queue<usize> = Queue::new();
+        assert_eq!(queue.try_pop_delta(), None);
+        for i in 0..3 {
+            queue.push(i);
+        }
+        for i in 0..3 {
+            let (item, len_before, len_after) = queue.try_pop_delta().unwrap();
+            assert_eq!(item, i);
+            assert_eq!(len_before, len_after + 1);
+            assert_eq!(len_after, 2 - i);
+        }
+        assert_eq!(queue.try_pop_delta(), None);
+    }
+
+    #[cfg(feature = "limited")]
+    #[tokio::test]
+    async fn test_drain_into() {
+        let src: Queue<usize> = (0..100).collect();
+        let dst: Arc<deadqueue::limited::Queue<usize>> =
+            Arc::new(deadqueue::limited::Queue::with_capacity(10));
+        let consumer = {
+            let dst = dst.clone();
+            tokio::spawn(async move {
+                for i in 0..100 {
+                    assert_eq!(dst.pop().await, i);
+                    assert!(dst.len() <= 10);
+                }
+            })
+        };
+        assert_eq!(src.drain_into(&dst).await, 100);
+        consumer.await.unwrap();
+        assert!(src.is_empty());
+        assert!(dst.is_empty());
+    }
+
+    #[cfg(feature = "limited")]
+    #[tokio::test]
+    async fn test_drain_into_closed() {
+        let src: Queue<usize> = (0..5).collect();
+        let dst = deadqueue::limited::Queue::with_capacity(10);
+        dst.close();
+        assert_eq!(src.drain_into(&dst).await, 0);
+        assert_eq!(src.len(), 5);
+        assert_eq!(src.try_pop(), Some(0));
+    }
+
+    #[cfg(feature = "limited")]
+    #[tokio::test]
+    async fn test_drain_into_full_then_closed() {
+        let src: Arc<Queue<usize>> = Arc::new((0..5).collect());
+        let dst: Arc<deadqueue::limited::Queue<usize>> =
+            Arc::new(deadqueue::limited::Queue::with_capacity(1));
+        dst.try_push(42).unwrap();
+        let drain = {
+            let src = src.clone();
+            let dst = dst.clone();
+            tokio::spawn(async move { src.drain_into(&dst).await })
+        };
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        assert!(!drain.is_finished());
+        dst.close();
+        assert_eq!(drain.await.unwrap(), 0);
+        assert_eq!(src.len(), 5);
+        assert_eq!(src.try_pop(), Some(0));
+        assert_eq!(dst.try_pop(), Some(42));
+    }
+
+    #[test]
+    fn test_with_name() {
+        let queue: Queue<usize> = Queue::new();
+        assert_eq!(queue.name(), None);
+        let queue = queue.with_name("jobs");
+        assert_eq!(queue.name(), Some("jobs"));
+        assert!(format!("{:?}", queue).contains("name: Some(\"jobs\")"));
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn test_total_pushed_popped() {
+        let queue: Arc<Queue<usize>> = Arc::new(Queue::new());
+        let mut tasks = Vec::new();
+        for t in 0..4 {
+            let queue = queue.clone();
+            tasks.push(tokio::spawn(async move {
+                for i in 0..250 {
+                    queue.push(i);
+                    if (t + i) % 3 != 0 {
+                        queue.try_pop();
+                    }
+                }
+            }));
+        }
+        for task in tasks {
+            task.await.unwrap();
+        }
+        assert_eq!(queue.total_pushed(), 1000);
+        assert_eq!(
+            queue.total_pushed(),
+            queue.total_popped() + queue.len() as u64
+        );
+        assert!(format!("{:?}", queue).contains("total_pushed: 1000"));
+    }
+
+    #[test]
+    fn test_inner() {
+        let queue: Queue<usize> = Queue::new();
+        queue.push(1);
+        queue.push(2);
+        // SAFETY: The inner queue is only read.
+        let inner = unsafe { queue.inner() }.unwrap();
+        assert_eq!(inner.len(), queue.len());
+        assert!(!inner.is_empty());
+        let lifo: Queue<usize> = Queue::with_order(Order::Lifo);
+        // SAFETY: No inner queue is returned at all.
+        assert!(unsafe { lifo.inner() }.is_none());
+    }
+
+    #[test]
+    fn test_try_pop_find() {
+        let queue: Queue<usize> = Queue::new();
+        for i in 1..=4 {
+            queue.push(i);
+        }
+        assert_eq!(queue.try_pop_find(|item| item % 3 == 0), Some(3));
+        assert_eq!(queue.len(), 1);
+        assert_eq!(queue.available(), 1);
+        assert_eq!(queue.total_popped(), 3);
+        assert_eq!(queue.try_pop_find(|item| *item > 10), None);
+        assert!(queue.is_empty());
+        assert_eq!(queue.available(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_drain_for() {
+        let queue: Queue<usize> = (0..100).collect();
+        assert_eq!(queue.drain_for(Duration::ZERO).await, vec![]);
+        assert_eq!(queue.len(), 100);
+        let items = queue.drain_for(Duration::from_secs(1)).await;
+        assert_eq!(items, (0..100).collect::<Vec<_>>());
+        assert!(queue.is_empty());
+        assert_eq!(queue.available(), 0);
+        // An empty queue returns right away without waiting for items.
+        let items = tokio::time::timeout(
+            Duration::from_secs(1),
+            queue.drain_for(Duration::from_secs(60)),
+        )
+        .await
+        .unwrap();
+        assert!(items.is_empty());
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn test_peek_concurrent_push_pop() {
+        const N: usize = 10_000;
+        let queue: Arc<Queue<usize>> = Arc::new(Queue::new());
+        let producer_queue = queue.clone();
+        let producer = tokio::spawn(async move {
+            for i in 0..N {
+                producer_queue.push(i);
+                if i % 100 == 0 {
+                    tokio::task::yield_now().await;
+                }
+            }
+        });
+        let consumer_queue = queue.clone();
+        let consumer = tokio::spawn(async move {
+            let mut items = Vec::with_capacity(N);
+            for _ in 0..N {
+                items.push(consumer_queue.pop().await);
+            }
+            items
+        });
+        while !consumer.is_finished() {
+            queue.peek_copy();
+            queue.peek_n(3);
+            tokio::task::yield_now().await;
+        }
+        producer.await.unwrap();
+        // The scans must neither reorder, lose nor duplicate any items.
+        let items = consumer.await.unwrap();
+        assert_eq!(items, (0..N).collect::<Vec<_>>());
+        assert_eq!(queue.len(), 0);
+        assert_eq!(queue.available(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_idle_duration_first_query() {
+        let queue: Queue<usize> = Queue::new();
+        queue.push(1);
+        assert_eq!(queue.pop().await, 1);
+        // The pop is timed when it happens and not when this is called.
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert!(queue.idle_duration() >= Duration::from_millis(50));
+    }
+
+    #[cfg(feature = "limited")]
+    #[tokio::test]
+    async fn test_drain_into_full_and_empty() {
+        let src: Arc<Queue<usize>> = Arc::new((0..2).collect());
+        let dst = deadqueue::limited::Queue::with_capacity(1);
+        dst.try_push(42).unwrap();
+        assert_eq!(Queue::<usize>::new().drain_into(&dst).await, 0);
+        // Stop waiting for room once another task empties the queue.
+        let consumer = {
+            let src = src.clone();
+            tokio::spawn(async move {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+                src.pop().await;
+                src.pop().await;
+            })
+        };
+        let moved = tokio::time::timeout(Duration::from_secs(1), src.drain_into(&dst))
+            .await
+            .unwrap();
+        assert_eq!(moved, 0);
+        consumer.await.unwrap();
+        assert_eq!(dst.try_pop(), Some(42));
     }
 }